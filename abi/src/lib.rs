@@ -1,13 +1,19 @@
 #![no_std]
 
+/// Host calls a guest makes through the `mmio::HYPERCALL_*` registers
+/// Arguments and result are listed per call; errors are negative errno.
 #[repr(u64)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HyperCall {
+    /// Print(offset, len): log `len` bytes at guest RAM `offset`; returns len
     Print = 0,
+    /// Exit(status): stop the guest with `status`; never returns
     Exit = 1,
+    /// Time(): nanoseconds since the host booted
+    Time = 2,
     // Future:
-    // DrawFrame = 2,
-    // Sleep = 3,
+    // DrawFrame = 3,
+    // Sleep = 4,
 }
 
 impl HyperCall {
@@ -15,15 +21,289 @@ impl HyperCall {
         match val {
             0 => Some(Self::Print),
             1 => Some(Self::Exit),
+            2 => Some(Self::Time),
             _ => None,
         }
     }
 }
 
+/// Guest physical memory map
+///
+/// Every address is an offset from the start of guest RAM. The regions
+/// don't overlap, all fit in `RAM_SIZE`, and none of them intrudes on the
+/// load area, so an image that fits there can't clobber them (all checked
+/// at compile time below).
+///
+/// ```text
+/// 0x000000 +-----------------------+
+///          | LOAD     (4MB)        |  guest image, loaded at 0
+/// 0x400000 +-----------------------+
+///          | CONTROL  (4KB)        |  keyboard, exit, hypercall registers
+/// 0x401000 +-----------------------+
+///          | (free guest RAM)      |
+/// 0x600000 +-----------------------+
+///          | DISK     (2MB)        |
+/// 0x800000 +-----------------------+
+///          | FB       (8MB)        |  32bpp shadow framebuffer
+/// 0x1000000+-----------------------+
+/// ```
 pub mod mmio {
     pub const RAM_SIZE: usize = 16 * 1024 * 1024; // 16MB
-    pub const FB_ADDR: usize = 0x100000;          // 1MB offset
-    pub const DISK_ADDR: usize = 0x300000;        // 3MB offset
-    pub const KEYBOARD_STATUS: usize = 0x80000;
-    pub const KEYBOARD_DATA: usize = 0x80004;
+
+    /// A named window of guest RAM
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Region {
+        pub name: &'static str,
+        pub offset: usize,
+        pub len: usize,
+    }
+
+    impl Region {
+        pub const fn new(name: &'static str, offset: usize, len: usize) -> Self {
+            Self { name, offset, len }
+        }
+
+        pub const fn end(&self) -> usize {
+            self.offset + self.len
+        }
+
+        pub const fn contains(&self, offset: usize, len: usize) -> bool {
+            offset >= self.offset && offset + len <= self.end()
+        }
+
+        pub const fn overlaps(&self, other: &Region) -> bool {
+            self.offset < other.end() && other.offset < self.end()
+        }
+
+        /// The region's first byte, for guest RAM starting at `base`
+        pub fn at(&self, base: *mut u8) -> *mut u8 {
+            base.wrapping_add(self.offset)
+        }
+    }
+
+    /// Where the guest image is loaded; images must fit in it
+    pub const LOAD: Region = Region::new("LOAD", 0, 0x400000);
+    /// Host/guest registers, laid out below
+    pub const CONTROL: Region = Region::new("CONTROL", 0x400000, 0x1000);
+    /// Disk window
+    pub const DISK: Region = Region::new("DISK", 0x600000, 0x200000);
+    /// Shadow framebuffer, large enough for 1920x1080 at 32bpp
+    pub const FRAMEBUFFER: Region = Region::new("FB", 0x800000, 0x800000);
+
+    /// Every region, for checks that walk the map
+    pub const REGIONS: [Region; 4] = [LOAD, CONTROL, DISK, FRAMEBUFFER];
+
+    pub const FB_ADDR: usize = FRAMEBUFFER.offset;
+    pub const DISK_ADDR: usize = DISK.offset;
+
+    // CONTROL page: keyboard block at +0x000
+    pub const KEYBOARD_STATUS: usize = CONTROL.offset;                 // u32, KEYBOARD_STATUS_* flags
+    pub const KEYBOARD_DATA: usize = CONTROL.offset + 0x04; // Last key injected (legacy single slot)
+
+    // Keyboard ring buffer (u32 entries, one char each).
+    // Host advances HEAD after writing an entry, guest advances TAIL after reading one.
+    // Indices are free-running u32s; the slot is `index % KEYBOARD_RING_LEN`.
+    pub const KEYBOARD_HEAD: usize = CONTROL.offset + 0x08;
+    pub const KEYBOARD_TAIL: usize = CONTROL.offset + 0x0C;
+    pub const KEYBOARD_RING: usize = CONTROL.offset + 0x10;
+    pub const KEYBOARD_RING_LEN: usize = 64;
+
+    // CONTROL page: exit block at +0x200
+    // Guest exit: write the status to GUEST_EXIT_STATUS, then any nonzero
+    // value to GUEST_EXIT. The host stops running the guest from then on.
+    pub const GUEST_EXIT_STATUS: usize = CONTROL.offset + 0x200; // i32
+    pub const GUEST_EXIT: usize = CONTROL.offset + 0x204;        // u32, nonzero = exit requested
+
+    // CONTROL page: hypercall block at +0x300
+    // Hypercall doorbell. The guest writes the HyperCall number and its
+    // arguments, then DOORBELL_RING to HYPERCALL_DOORBELL. The host writes
+    // the result to HYPERCALL_RET and sets DOORBELL_DONE; the guest reads
+    // the result and puts the doorbell back to DOORBELL_IDLE. Pointers are
+    // offsets into guest RAM, like every address in this map.
+    pub const HYPERCALL_NR: usize = CONTROL.offset + 0x300;       // u64
+    pub const HYPERCALL_ARGS: usize = CONTROL.offset + 0x308;     // HYPERCALL_ARG_COUNT x u64
+    pub const HYPERCALL_ARG_COUNT: usize = 4;
+    pub const HYPERCALL_RET: usize = CONTROL.offset + 0x328;      // i64
+    pub const HYPERCALL_DOORBELL: usize = CONTROL.offset + 0x330; // u32
+
+    // HYPERCALL_DOORBELL values
+    pub const DOORBELL_IDLE: u32 = 0;
+    pub const DOORBELL_RING: u32 = 1;   // Request posted, host hasn't answered
+    pub const DOORBELL_DONE: u32 = 2;   // HYPERCALL_RET holds the answer
+
+    // KEYBOARD_STATUS flag bits. Host and guest both change this word, so
+    // each side sets/clears its bits with atomic or/and, never a plain store.
+    // READY is a hint: HEAD != TAIL is what says the ring holds keys; a guest
+    // that drains it clears READY and then re-checks HEAD.
+    pub const KEYBOARD_STATUS_EMPTY: u32 = 0;
+    pub const KEYBOARD_STATUS_READY: u32 = 1 << 0;    // Data available (set by the host)
+    pub const KEYBOARD_STATUS_OVERFLOW: u32 = 1 << 1; // A key was dropped because the ring
+                                                      // was full; sticky until the guest clears it
+
+    /// Bytes of framebuffer a `width` x `height` mode needs, if it fits
+    pub const fn framebuffer_len(width: usize, height: usize) -> Option<usize> {
+        match width.checked_mul(height) {
+            Some(pixels) if pixels <= FRAMEBUFFER.len / 4 => Some(pixels * 4),
+            _ => None,
+        }
+    }
+
+    const fn layout_is_sound() -> bool {
+        let mut i = 0;
+        while i < REGIONS.len() {
+            if REGIONS[i].end() > RAM_SIZE {
+                return false;
+            }
+            let mut j = i + 1;
+            while j < REGIONS.len() {
+                if REGIONS[i].overlaps(&REGIONS[j]) {
+                    return false;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    const _: () = assert!(layout_is_sound(), "MMIO regions overlap or exceed RAM_SIZE");
+    const _: () = assert!(framebuffer_len(1920, 1080).is_some());
+    const _: () = assert!(CONTROL.contains(KEYBOARD_RING, KEYBOARD_RING_LEN * 4));
+    const _: () = assert!(KEYBOARD_RING + KEYBOARD_RING_LEN * 4 <= GUEST_EXIT_STATUS);
+    const _: () = assert!(GUEST_EXIT + 4 <= HYPERCALL_NR);
+    const _: () = assert!(CONTROL.contains(HYPERCALL_DOORBELL, 4));
+    const _: () = assert!(HYPERCALL_ARGS + HYPERCALL_ARG_COUNT * 8 <= HYPERCALL_RET);
+}
+
+/// Syscall numbers (Linux x86_64 ABI), shared by the kernel and user programs
+pub mod syscall {
+    // Core I/O
+    pub const SYS_READ: usize = 0;
+    pub const SYS_WRITE: usize = 1;
+    pub const SYS_OPEN: usize = 2;
+    pub const SYS_CLOSE: usize = 3;
+    pub const SYS_STAT: usize = 4;
+    pub const SYS_FSTAT: usize = 5;
+    pub const SYS_POLL: usize = 7;
+    pub const SYS_LSEEK: usize = 8;
+    pub const SYS_MMAP: usize = 9;
+    pub const SYS_MPROTECT: usize = 10;
+    pub const SYS_BRK: usize = 12;
+    pub const SYS_IOCTL: usize = 16;
+    pub const SYS_PREAD64: usize = 17;
+    pub const SYS_PWRITE64: usize = 18;
+    
+    // File descriptors
+    pub const SYS_DUP: usize = 32;
+    pub const SYS_DUP2: usize = 33;
+    pub const SYS_DUP3: usize = 292;
+    pub const SYS_PIPE: usize = 22;
+    pub const SYS_SENDFILE: usize = 40;
+    pub const SYS_SOCKETPAIR: usize = 53;
+    
+    // Sockets
+    pub const SYS_SOCKET: usize = 41;
+    pub const SYS_CONNECT: usize = 42;
+    pub const SYS_ACCEPT: usize = 43;
+    pub const SYS_SENDTO: usize = 44;
+    pub const SYS_RECVFROM: usize = 45;
+    pub const SYS_BIND: usize = 49;
+    pub const SYS_LISTEN: usize = 50;
+    
+    // Event notification
+    pub const SYS_EPOLL_WAIT: usize = 232;
+    pub const SYS_EPOLL_CTL: usize = 233;
+    pub const SYS_EVENTFD2: usize = 290;
+    pub const SYS_EPOLL_CREATE1: usize = 291;
+    
+    // Process
+    pub const SYS_GETPID: usize = 39;
+    pub const SYS_CLONE: usize = 56;
+    pub const SYS_FORK: usize = 57;
+    pub const SYS_EXECVE: usize = 59;
+    pub const SYS_EXIT: usize = 60;
+    pub const SYS_EXIT_GROUP: usize = 231;
+    pub const SYS_WAIT4: usize = 61;
+    pub const SYS_FUTEX: usize = 202;
+    pub const SYS_TIMES: usize = 100;
+    
+    // Time
+    pub const SYS_GETTIMEOFDAY: usize = 96;
+    pub const SYS_NANOSLEEP: usize = 35;
+    pub const SYS_CLOCK_GETTIME: usize = 228;
+    
+    // Memory
+    pub const SYS_MUNMAP: usize = 11;
+    
+    // Misc
+    pub const SYS_UNAME: usize = 63;
+    pub const SYS_GETRUSAGE: usize = 98;
+    pub const SYS_GETCWD: usize = 79;
+    pub const SYS_CHDIR: usize = 80;
+    pub const SYS_RENAME: usize = 82;
+    pub const SYS_UTIMENSAT: usize = 280;
+    pub const SYS_CHMOD: usize = 90;
+    pub const SYS_FCHMOD: usize = 91;
+    pub const SYS_CHOWN: usize = 92;
+    pub const SYS_FCHOWN: usize = 93;
+    pub const SYS_UMASK: usize = 95;
+    pub const SYS_GETDENTS64: usize = 217;
+    pub const SYS_GETUID: usize = 102;
+    pub const SYS_GETGID: usize = 104;
+    pub const SYS_GETEUID: usize = 107;
+    pub const SYS_GETEGID: usize = 108;
+    pub const SYS_SETUID: usize = 105;
+    pub const SYS_SETGID: usize = 106;
+    pub const SYS_SETHOSTNAME: usize = 170;
+    pub const SYS_STATFS: usize = 137;
+    pub const SYS_FSTATFS: usize = 138;
+}
+
+/// Error numbers; syscalls return them negated
+pub mod errno {
+    pub const EPERM: isize = 1;
+    pub const ENOENT: isize = 2;
+    pub const ESRCH: isize = 3;
+    pub const EINTR: isize = 4;
+    pub const EIO: isize = 5;
+    pub const E2BIG: isize = 7;
+    pub const ENOEXEC: isize = 8;
+    pub const EBADF: isize = 9;
+    pub const ECHILD: isize = 10;
+    pub const EAGAIN: isize = 11;
+    pub const ENOMEM: isize = 12;
+    pub const EACCES: isize = 13;
+    pub const EFAULT: isize = 14;
+    pub const EBUSY: isize = 16;
+    pub const EEXIST: isize = 17;
+    pub const EXDEV: isize = 18;
+    pub const ENODEV: isize = 19;
+    pub const ENOTDIR: isize = 20;
+    pub const EISDIR: isize = 21;
+    pub const EINVAL: isize = 22;
+    pub const ENFILE: isize = 23;
+    pub const EMFILE: isize = 24;
+    pub const ENOTTY: isize = 25;
+    pub const ENOSPC: isize = 28;
+    pub const ESPIPE: isize = 29;
+    pub const EROFS: isize = 30;
+    pub const EPIPE: isize = 32;
+    pub const ERANGE: isize = 34;
+    pub const ENAMETOOLONG: isize = 36;
+    pub const ENOSYS: isize = 38;
+    pub const ENOTEMPTY: isize = 39;
+    pub const ELOOP: isize = 40;
+    pub const ENOTSOCK: isize = 88;
+    pub const EPROTONOSUPPORT: isize = 93;
+    pub const ESOCKTNOSUPPORT: isize = 94;
+    pub const EOPNOTSUPP: isize = 95;
+    pub const EAFNOSUPPORT: isize = 97;
+    pub const EADDRINUSE: isize = 98;
+    pub const EADDRNOTAVAIL: isize = 99;
+    pub const ENETUNREACH: isize = 101;
+    pub const ECONNRESET: isize = 104;
+    pub const EISCONN: isize = 106;
+    pub const ENOTCONN: isize = 107;
+    pub const ETIMEDOUT: isize = 110;
+    pub const ECONNREFUSED: isize = 111;
 }
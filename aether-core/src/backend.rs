@@ -1,10 +1,35 @@
+use crate::scheduler::ProcessId;
+
+/// Why `Backend::step` returned
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExitReason {
-    Yield,      // Time slice expired or voluntary yield
-    Io(u16),    // IO Access (Port)
-    Mmio(u64),  // MMIO Access (Address)
-    Halt,       // Guest Halted
-    Unknown,    // Other exit reasons
+    /// The time slice expired (the timer preempted the guest) or it yielded.
+    /// Produced by every backend; the guest can simply be stepped again.
+    Yield,
+    /// The guest accessed an I/O port the backend must emulate.
+    /// Only virtualizing backends produce this.
+    Io(u16),
+    /// The guest accessed an MMIO address the backend must emulate.
+    /// Only virtualizing backends produce this.
+    Mmio(u64),
+    /// The guest is idle until an external event (an interrupt to inject).
+    /// Only virtualizing backends produce this.
+    Halt,
+    /// The guest finished with this status, by posting it through
+    /// `mmio::GUEST_EXIT` or by returning from its entry point (status 0).
+    /// Every later step reports the same; the scheduler reaps the guest.
+    Exited(i32),
+    /// The guest hit an unrecoverable CPU exception. The scheduler reaps it.
+    /// Backends that can't catch guest faults (UefiBackend) never produce it.
+    Fault,
+    /// The step could not run (e.g. nested stepping) or the exit was not
+    /// understood.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackendError {
+    ImageTooLarge { size: usize, max: usize }, // Guest image doesn't fit in the load area
 }
 
 pub trait Backend: Sync + Send {
@@ -18,4 +43,24 @@ pub trait Backend: Sync + Send {
 
     // Inject a key press into the Guest
     fn inject_key(&self, _c: char) {}
+
+    /// Called by the scheduler once the backend has been assigned a PID
+    fn attach(&self, _pid: ProcessId) {}
+
+    /// Answer whatever the guest has asked of the host since the last call
+    /// (hypercalls); the scheduler calls this on every pass
+    fn service(&self) {}
+
+    /// Whether the guest has finished for good; the scheduler then
+    /// terminates its process
+    /// This is the only exit signal the scheduler sees, so a backend whose
+    /// `step` returned `Exited` or `Fault` must report true from then on.
+    fn has_exited(&self) -> bool {
+        false
+    }
+
+    /// Called by the scheduler just before it drops a terminated process:
+    /// undo `attach` and anything else that points into the guest, since
+    /// its memory goes with the last reference to the backend
+    fn shutdown(&self, _pid: ProcessId) {}
 }
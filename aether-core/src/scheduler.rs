@@ -4,12 +4,28 @@ use alloc::collections::VecDeque;
 
 pub type ProcessId = u64;
 
+/// Lifecycle: Ready <-> Running, Running -> Blocked -> Ready, any of those
+/// -> Terminated, and Terminated -> Reaped once it is off the CPU for good
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ProcessState {
     Ready,
     Running,
     Blocked,
     Terminated,
+    Reaped,
+}
+
+impl ProcessState {
+    /// Whether the lifecycle allows going from `self` to `next`
+    pub fn can_become(self, next: ProcessState) -> bool {
+        use ProcessState::*;
+        matches!(
+            (self, next),
+            (Ready, Running) | (Running, Ready) | (Running, Blocked) | (Blocked, Ready)
+                | (Ready | Running | Blocked, Terminated)
+                | (Terminated, Reaped)
+        )
+    }
 }
 
 pub struct Process {
@@ -20,6 +36,14 @@ pub struct Process {
     pub stack_pointer: usize,
 }
 
+impl Process {
+    /// Move to `next`, which the lifecycle must allow
+    pub fn set_state(&mut self, next: ProcessState) {
+        debug_assert!(self.state.can_become(next), "process {}: {:?} -> {:?}", self.id, self.state, next);
+        self.state = next;
+    }
+}
+
 pub struct Scheduler {
     pub processes: VecDeque<Process>,
     pub next_pid: ProcessId,
@@ -36,6 +60,7 @@ impl Scheduler {
     }
 
     pub fn spawn(&mut self, backend: Arc<dyn Backend>) -> ProcessId {
+        self.free_reaped();
         let pid = self.next_pid;
         self.next_pid += 1;
         
@@ -51,6 +76,8 @@ impl Scheduler {
         // Ensure 16-byte alignment
         let stack_pointer = stack_end & !0xF;
 
+        backend.attach(pid);
+
         self.processes.push_back(Process {
             id: pid,
             backend,
@@ -64,37 +91,44 @@ impl Scheduler {
     }
 
     /// Simple Round-Robin Scheduler
-    /// Returns the PID of the process to switch TO.
-    /// Returns None if no process is ready (or only 1 process running).
+    /// Returns the PID of the process to switch TO, which is always Ready
+    /// (never Terminated) and still in `processes`.
+    /// Returns None if no process is ready (or only 1 process running),
+    /// including once every process has exited.
+    ///
+    /// Terminated processes other than the current one are reaped first:
+    /// nothing runs on their stacks any more. The current one is reaped on
+    /// the next call, after the switch away from it. Reaped processes stay
+    /// in `processes` until `free_reaped`.
     pub fn schedule(&mut self) -> Option<ProcessId> {
+        self.poll_backends();
+        self.reap();
         if self.processes.is_empty() {
             return None;
         }
 
         let current_index = self.current_pid.and_then(|pid| {
             self.processes.iter().position(|p| p.id == pid)
-        }).unwrap_or(0);
+        });
+        let start = current_index.unwrap_or(self.processes.len() - 1);
 
         // Find next Ready process starting from current + 1
-        let mut next_index = current_index;
-        
-        // Try to find a Ready process (scan at most once full circle)
+        // (scan at most once full circle, ending on the current one)
+        let mut next_index = start;
         for _ in 0..self.processes.len() {
             next_index = (next_index + 1) % self.processes.len();
-            if self.processes[next_index].state == ProcessState::Ready || 
-               self.processes[next_index].state == ProcessState::Running {
-                
-                let next_pid = self.processes[next_index].id;
-                
-                // If it's the same as current, no switch needed?
-                // Actually for preemptive time slicing, we might want to switch to self 
-                // to refresh time slice, but here we just return None to avoid overhead.
-                if let Some(curr) = self.current_pid {
-                    if curr == next_pid {
-                        return None;
+            if Some(next_index) == current_index {
+                // Nothing else is ready: keep running the current one
+                return None;
+            }
+            if self.processes[next_index].state == ProcessState::Ready {
+                if let Some(i) = current_index {
+                    if self.processes[i].state == ProcessState::Running {
+                        self.processes[i].set_state(ProcessState::Ready);
                     }
                 }
-                
+                self.processes[next_index].set_state(ProcessState::Running);
+                let next_pid = self.processes[next_index].id;
                 self.current_pid = Some(next_pid);
                 return Some(next_pid);
             }
@@ -104,8 +138,102 @@ impl Scheduler {
         None
     }
 
+    /// Mark `pid` Terminated; its stack stays until `schedule` has
+    /// switched away from it
+    pub fn exit(&mut self, pid: ProcessId) {
+        if let Some(process) = self.get_process_mut(pid) {
+            process.set_state(ProcessState::Terminated);
+        }
+    }
+
+    /// Let live backends service their guests, then terminate processes
+    /// whose guest has exited
+    /// `Backend::has_exited` is the only way a guest's exit reaches the
+    /// scheduler; nothing feeds it `step` results.
+    fn poll_backends(&mut self) {
+        let live = |p: &&mut Process| !matches!(p.state, ProcessState::Terminated | ProcessState::Reaped);
+        for p in self.processes.iter_mut().filter(live) {
+            p.backend.service();
+            if p.backend.has_exited() {
+                log::info!("[Scheduler] Process {} exited", p.id);
+                p.set_state(ProcessState::Terminated);
+            }
+        }
+    }
+
+    /// Mark Terminated processes that aren't on the CPU Reaped
+    /// This runs in the timer interrupt, so it only flips their state:
+    /// shutting a backend down and freeing its stack and guest RAM go
+    /// through the allocator, whose lock the interrupted code may hold.
+    fn reap(&mut self) {
+        let current = self.current_pid;
+        for p in self.processes.iter_mut() {
+            if p.state == ProcessState::Terminated && Some(p.id) != current {
+                p.set_state(ProcessState::Reaped);
+                log::info!("[Scheduler] Reaped Process {}", p.id);
+            }
+        }
+    }
+
+    /// Shut down and drop Reaped processes
+    /// Their backend is freed with them (unless someone else still holds
+    /// it). Call from the idle loop, never from an interrupt handler.
+    pub fn free_reaped(&mut self) {
+        self.processes.retain(|p| {
+            if p.state != ProcessState::Reaped {
+                return true;
+            }
+            p.backend.shutdown(p.id);
+            false
+        });
+    }
+
+    /// Where to save the outgoing context of `pid`
+    /// None for a Terminated (or unknown) process: nothing will resume it,
+    /// so its fields must not be a save target.
+    pub fn save_slot(&mut self, pid: ProcessId) -> Option<*mut usize> {
+        self.get_process_mut(pid)
+            .filter(|p| p.state != ProcessState::Terminated)
+            .map(|p| &mut p.stack_pointer as *mut usize)
+    }
+
     /// Get process by ID (mutable)
     pub fn get_process_mut(&mut self, pid: ProcessId) -> Option<&mut Process> {
         self.processes.iter_mut().find(|p| p.id == pid)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ExitReason;
+
+    struct Halted;
+
+    impl Backend for Halted {
+        fn name(&self) -> &str { "halted" }
+        fn step(&self) -> ExitReason { ExitReason::Halt }
+        unsafe fn get_framebuffer(&self, _width: usize, _height: usize) -> &[u32] { &[] }
+    }
+
+    fn ids(sched: &Scheduler) -> alloc::vec::Vec<ProcessId> {
+        sched.processes.iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn schedule_skips_exited_processes() {
+        let mut sched = Scheduler::new();
+        for _ in 0..4 {
+            sched.spawn(Arc::new(Halted));
+        }
+        sched.exit(2);
+        sched.exit(4);
+        assert_eq!(sched.schedule(), Some(1)); // 2 and 4 are reaped before picking
+        assert_eq!(sched.schedule(), Some(3));
+        sched.exit(3);                         // the current one
+        assert_eq!(sched.schedule(), Some(1));
+        assert_eq!(sched.schedule(), None);    // 1 has nobody to yield to
+        sched.free_reaped();
+        assert_eq!(ids(&sched), [1]);
+    }
+}
@@ -2,16 +2,17 @@
 #![no_main]
 
 use core::panic::PanicInfo;
-use core::arch::asm;
+use core::arch::{asm, global_asm};
 
-// ============================================================================
-// Syscall Numbers (Linux x86_64 ABI)
-// ============================================================================
+use aether_abi::syscall::{
+    SYS_CHDIR, SYS_CLOSE, SYS_EXECVE, SYS_EXIT_GROUP, SYS_FORK, SYS_FSTAT, SYS_GETCWD,
+    SYS_GETDENTS64, SYS_GETPID, SYS_OPEN, SYS_READ, SYS_UNAME, SYS_WAIT4, SYS_WRITE,
+};
 
-const SYS_READ: usize = 0;
-const SYS_WRITE: usize = 1;
-const SYS_EXIT: usize = 60;
-const SYS_GETPID: usize = 39;
+const O_RDONLY: usize = 0;
+const O_WRONLY: usize = 0o1;
+const O_CREAT: usize = 0o100;
+const O_TRUNC: usize = 0o1000;
 
 // ============================================================================
 // Syscall Wrappers
@@ -95,8 +96,9 @@ fn read(fd: usize, buf: &mut [u8]) -> isize {
     unsafe { syscall3(SYS_READ, fd, buf.as_ptr() as usize, buf.len()) }
 }
 
+/// Like libc's exit(): ends the whole process
 fn exit(code: usize) -> ! {
-    unsafe { syscall1(SYS_EXIT, code) };
+    unsafe { syscall1(SYS_EXIT_GROUP, code) };
     loop {}
 }
 
@@ -104,6 +106,38 @@ fn getpid() -> isize {
     unsafe { syscall1(SYS_GETPID, 0) }
 }
 
+/// struct utsname is six 65-byte fields
+fn uname(buf: &mut [u8; 390]) -> isize {
+    unsafe { syscall1(SYS_UNAME, buf.as_mut_ptr() as usize) }
+}
+
+fn getcwd(buf: &mut [u8]) -> isize {
+    unsafe { syscall3(SYS_GETCWD, buf.as_mut_ptr() as usize, buf.len(), 0) }
+}
+
+/// `path` must be NUL-terminated
+fn open(path: &[u8], flags: usize) -> isize {
+    unsafe { syscall3(SYS_OPEN, path.as_ptr() as usize, flags, 0) }
+}
+
+fn close(fd: usize) -> isize {
+    unsafe { syscall1(SYS_CLOSE, fd) }
+}
+
+/// struct stat is 144 bytes on x86_64
+fn fstat(fd: usize, buf: &mut [u8; 144]) -> isize {
+    unsafe { syscall3(SYS_FSTAT, fd, buf.as_mut_ptr() as usize, 0) }
+}
+
+fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    unsafe { syscall3(SYS_GETDENTS64, fd, buf.as_mut_ptr() as usize, buf.len()) }
+}
+
+/// `path` must be NUL-terminated
+fn chdir(path: &[u8]) -> isize {
+    unsafe { syscall1(SYS_CHDIR, path.as_ptr() as usize) }
+}
+
 fn print(s: &str) {
     write(1, s.as_bytes());
 }
@@ -113,12 +147,453 @@ fn println(s: &str) {
     print("\n");
 }
 
+// ============================================================================
+// Environment
+// ============================================================================
+
+/// Room for variables and their text; no heap, so everything lives in a fixed arena
+const ENV_MAX_VARS: usize = 32;
+const ENV_ARENA_SIZE: usize = 2048;
+
+/// Shell environment: "KEY=VALUE\0" strings packed into an arena
+struct Env {
+    arena: [u8; ENV_ARENA_SIZE],
+    used: usize,
+    // (offset, length without the NUL) of each live entry
+    vars: [(usize, usize); ENV_MAX_VARS],
+    count: usize,
+}
+
+impl Env {
+    const fn new() -> Self {
+        Self {
+            arena: [0; ENV_ARENA_SIZE],
+            used: 0,
+            vars: [(0, 0); ENV_MAX_VARS],
+            count: 0,
+        }
+    }
+    
+    /// Import envp from the initial stack: [argc][argv...][NULL][envp...][NULL]
+    unsafe fn import(&mut self, sp: *const usize) {
+        let argc = *sp;
+        let mut envp = sp.add(1 + argc + 1) as *const *const u8;
+        while !(*envp).is_null() {
+            if !self.set(cstr(*envp)) {
+                println("env: inherited environment truncated");
+                break;
+            }
+            envp = envp.add(1);
+        }
+    }
+    
+    fn entry(&self, i: usize) -> &[u8] {
+        let (off, len) = self.vars[i];
+        &self.arena[off..off + len]
+    }
+    
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        (0..self.count).find(|&i| {
+            let e = self.entry(i);
+            e.len() > key.len() && e.starts_with(key) && e[key.len()] == b'='
+        })
+    }
+    
+    /// Value of `key`
+    fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.find(key).map(|i| &self.entry(i)[key.len() + 1..])
+    }
+    
+    /// Set from "KEY=VALUE", replacing an existing KEY
+    /// Returns false if the arena or variable table is full.
+    fn set(&mut self, kv: &[u8]) -> bool {
+        let key_len = match kv.iter().position(|&b| b == b'=') {
+            Some(n) if n > 0 => n,
+            _ => return false,
+        };
+        let existing = self.find(&kv[..key_len]);
+        if existing.is_none() && self.count == ENV_MAX_VARS {
+            return false;
+        }
+        if self.used + kv.len() + 1 > ENV_ARENA_SIZE {
+            // Reclaim space left behind by overwritten values
+            self.compact();
+            let freed = existing.map_or(0, |i| self.vars[i].1 + 1);
+            if self.used - freed + kv.len() + 1 > ENV_ARENA_SIZE {
+                return false;
+            }
+            if let Some(i) = existing {
+                self.remove(i);
+                self.compact();
+            }
+            return self.set(kv);
+        }
+        
+        let off = self.used;
+        self.arena[off..off + kv.len()].copy_from_slice(kv);
+        self.arena[off + kv.len()] = 0;
+        self.used += kv.len() + 1;
+        match existing {
+            Some(i) => self.vars[i] = (off, kv.len()),
+            None => {
+                self.vars[self.count] = (off, kv.len());
+                self.count += 1;
+            }
+        }
+        true
+    }
+    
+    fn remove(&mut self, i: usize) {
+        self.vars.copy_within(i + 1..self.count, i);
+        self.count -= 1;
+    }
+    
+    /// Slide live entries down over dead space
+    fn compact(&mut self) {
+        let mut order = [0usize; ENV_MAX_VARS];
+        for (i, slot) in order.iter_mut().enumerate().take(self.count) {
+            *slot = i;
+        }
+        let vars = self.vars;
+        order[..self.count].sort_unstable_by_key(|&i| vars[i].0);
+        
+        let mut dst = 0;
+        for &i in &order[..self.count] {
+            let (off, len) = self.vars[i];
+            self.arena.copy_within(off..off + len + 1, dst);
+            self.vars[i] = (dst, len);
+            dst += len + 1;
+        }
+        self.used = dst;
+    }
+    
+    /// NULL-terminated envp array pointing into the arena
+    fn envp(&self) -> [*const u8; ENV_MAX_VARS + 1] {
+        let mut out = [core::ptr::null(); ENV_MAX_VARS + 1];
+        for (i, slot) in out.iter_mut().enumerate().take(self.count) {
+            *slot = self.arena[self.vars[i].0..].as_ptr();
+        }
+        out
+    }
+}
+
+/// `buf` up to its first NUL
+fn until_nul(buf: &[u8]) -> &[u8] {
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    &buf[..len]
+}
+
+fn cstr(ptr: *const u8) -> &'static [u8] {
+    let mut len = 0;
+    unsafe {
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        core::slice::from_raw_parts(ptr, len)
+    }
+}
+
+/// Message for a failed syscall's errno
+fn strerror(err: isize) -> &'static str {
+    match -err {
+        2 => "No such file or directory",
+        9 => "Bad file descriptor",
+        13 => "Permission denied",
+        20 => "Not a directory",
+        21 => "Is a directory",
+        22 => "Invalid argument",
+        _ => "Error",
+    }
+}
+
+/// cd [DIR | -]: no argument goes to /, `-` to $OLDPWD
+fn cd(env: &mut Env, arg: &[u8]) {
+    // Own copy of the target, NUL-terminated (it may come from the environment)
+    let mut target = [0u8; MAX_INPUT + 1];
+    let path: &[u8] = match arg {
+        b"" => b"/",
+        b"-" => match env.get(b"OLDPWD") {
+            Some(old) => old,
+            None => {
+                println("cd: OLDPWD not set");
+                return;
+            }
+        },
+        dir => dir,
+    };
+    let len = path.len().min(MAX_INPUT);
+    target[..len].copy_from_slice(&path[..len]);
+    
+    let mut old = [0u8; MAX_INPUT];
+    let have_old = getcwd(&mut old) > 0;
+    let ret = chdir(&target[..len + 1]);
+    if ret < 0 {
+        print("cd: ");
+        write(1, &target[..len]);
+        print(": ");
+        println(strerror(ret));
+        return;
+    }
+    
+    if have_old {
+        let mut kv = [0u8; MAX_INPUT + 7];
+        let old = until_nul(&old);
+        kv[..7].copy_from_slice(b"OLDPWD=");
+        kv[7..7 + old.len()].copy_from_slice(old);
+        env.set(&kv[..7 + old.len()]);
+    }
+    if arg == b"-" {
+        write(1, &target[..len]);
+        print("\n");
+    }
+}
+
+/// Echo words, expanding $NAME from the environment
+fn echo(env: &Env, args: &[u8]) {
+    let mut first = true;
+    for word in args.split(|&b| b == b' ').filter(|w| !w.is_empty()) {
+        if !first {
+            print(" ");
+        }
+        first = false;
+        if word.len() > 1 && word[0] == b'$' {
+            if let Some(value) = env.get(&word[1..]) {
+                write(1, value);
+            }
+        } else {
+            write(1, word);
+        }
+    }
+    print("\n");
+}
+
+// ============================================================================
+// File Builtins
+// ============================================================================
+
+/// Print "NAME: error" for a failed call on `name`
+fn report(cmd: &str, name: &[u8], err: isize) {
+    print(cmd);
+    print(": ");
+    write(1, name);
+    print(": ");
+    println(strerror(err));
+}
+
+/// `n` right-aligned in `width` columns
+fn print_padded(n: usize, width: usize) {
+    let mut digits = 1;
+    let mut rest = n / 10;
+    while rest > 0 {
+        digits += 1;
+        rest /= 10;
+    }
+    for _ in digits..width {
+        print(" ");
+    }
+    print_usize(n);
+}
+
+/// One `ls -l` line: type, size, name (stats "DIR/NAME")
+fn ls_long(dir: &[u8], name: &[u8]) {
+    let mut path = [0u8; 2 * MAX_INPUT + 2];
+    let len = (dir.len() + 1 + name.len()).min(path.len() - 1);
+    let dlen = dir.len().min(len);
+    path[..dlen].copy_from_slice(&dir[..dlen]);
+    if dlen < len {
+        path[dlen] = b'/';
+        let nlen = len - dlen - 1;
+        path[dlen + 1..len].copy_from_slice(&name[..nlen]);
+    }
+    
+    let mut st = [0u8; 144];
+    let fd = open(&path[..len + 1], O_RDONLY);
+    let ok = fd >= 0 && fstat(fd as usize, &mut st) == 0;
+    if fd >= 0 {
+        close(fd as usize);
+    }
+    if !ok {
+        print("?          ");
+    } else {
+        let mode = u32::from_ne_bytes([st[24], st[25], st[26], st[27]]);
+        let size = u64::from_ne_bytes([st[48], st[49], st[50], st[51], st[52], st[53], st[54], st[55]]);
+        let kind = match mode & 0o170000 {
+            0o040000 => "d",
+            0o020000 => "c",
+            0o010000 => "p",
+            0o120000 => "l",
+            _ => "-",
+        };
+        print(kind);
+        print(" ");
+        print_padded(size as usize, 9);
+        print(" ");
+    }
+    write(1, name);
+    print("\n");
+}
+
+/// ls [-l] [DIR]
+fn ls(args: &[u8]) {
+    let mut long = false;
+    let mut dir: &[u8] = b".";
+    for word in args.split(|&b| b == b' ').filter(|w| !w.is_empty()) {
+        if word == b"-l" {
+            long = true;
+        } else {
+            dir = word;
+        }
+    }
+    
+    let mut path = [0u8; MAX_INPUT + 1];
+    let dlen = dir.len().min(MAX_INPUT);
+    path[..dlen].copy_from_slice(&dir[..dlen]);
+    let fd = open(&path[..dlen + 1], O_RDONLY);
+    if fd < 0 {
+        report("ls", dir, fd);
+        return;
+    }
+    
+    // struct linux_dirent64: d_ino, d_off, d_reclen (u16 at 16), d_type, d_name
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = getdents64(fd as usize, &mut buf);
+        if n < 0 {
+            report("ls", dir, n);
+            break;
+        }
+        if n == 0 {
+            break;
+        }
+        let mut pos = 0;
+        while pos < n as usize {
+            let reclen = u16::from_ne_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+            let name = until_nul(&buf[pos + 19..pos + reclen]);
+            if long {
+                ls_long(dir, name);
+            } else {
+                write(1, name);
+                print("\n");
+            }
+            pos += reclen;
+        }
+    }
+    close(fd as usize);
+}
+
+/// Copy `fd` to stdout until EOF; false (after reporting) on a read error
+fn copy_to_stdout(fd: usize, name: &[u8]) -> bool {
+    let mut buf = [0u8; 512];
+    loop {
+        let n = read(fd, &mut buf);
+        if n < 0 {
+            report("cat", name, n);
+            return false;
+        }
+        if n == 0 {
+            return true;
+        }
+        write(1, &buf[..n as usize]);
+    }
+}
+
+/// cat [FILE...]: concatenate files, or stdin when none are given
+fn cat(args: &[u8]) {
+    let mut any = false;
+    for name in args.split(|&b| b == b' ').filter(|w| !w.is_empty()) {
+        any = true;
+        let mut path = [0u8; MAX_INPUT + 1];
+        let len = name.len().min(MAX_INPUT);
+        path[..len].copy_from_slice(&name[..len]);
+        let fd = open(&path[..len + 1], O_RDONLY);
+        if fd < 0 {
+            report("cat", name, fd);
+            continue;
+        }
+        copy_to_stdout(fd as usize, name);
+        close(fd as usize);
+    }
+    if !any {
+        copy_to_stdout(0, b"stdin");
+    }
+}
+
+/// write FILE TEXT: replace FILE (creating it) with TEXT and a newline
+fn write_file(args: &[u8]) {
+    let args = trim(args);
+    let (name, text) = match args.iter().position(|&b| b == b' ') {
+        Some(i) => (&args[..i], trim(&args[i + 1..])),
+        None => (args, &b""[..]),
+    };
+    if name.is_empty() {
+        println("usage: write FILE TEXT");
+        return;
+    }
+    
+    let mut path = [0u8; MAX_INPUT + 1];
+    let len = name.len().min(MAX_INPUT);
+    path[..len].copy_from_slice(&name[..len]);
+    let fd = open(&path[..len + 1], O_WRONLY | O_CREAT | O_TRUNC);
+    if fd < 0 {
+        report("write", name, fd);
+        return;
+    }
+    let ret = write(fd as usize, text);
+    if ret < 0 {
+        report("write", name, ret);
+    } else {
+        write(fd as usize, b"\n");
+    }
+    close(fd as usize);
+}
+
 // ============================================================================
 // Simple Shell
 // ============================================================================
 
-const PROMPT: &str = "aether> ";
 const MAX_INPUT: usize = 256;
+const PROMPT_MAX: usize = 128;
+/// Longer working directories are shown as "..." and their tail
+const PROMPT_CWD_MAX: usize = 32;
+
+/// "hostname:cwd$ ", rebuilt before every command
+struct Prompt {
+    buf: [u8; PROMPT_MAX],
+    len: usize,
+}
+
+impl Prompt {
+    fn build() -> Self {
+        let mut prompt = Self { buf: [0; PROMPT_MAX], len: 0 };
+        
+        let mut uts = [0u8; 390];
+        let host = if uname(&mut uts) == 0 { until_nul(&uts[65..130]) } else { b"aether" };
+        prompt.push(host);
+        prompt.push(b":");
+        
+        let mut path = [0u8; MAX_INPUT];
+        let cwd = if getcwd(&mut path) > 0 { until_nul(&path) } else { b"?" };
+        if cwd.len() > PROMPT_CWD_MAX {
+            prompt.push(b"...");
+            prompt.push(&cwd[cwd.len() - (PROMPT_CWD_MAX - 3)..]);
+        } else {
+            prompt.push(cwd);
+        }
+        prompt.push(b"$ ");
+        prompt
+    }
+    
+    /// Append, dropping whatever doesn't fit
+    fn push(&mut self, s: &[u8]) {
+        let n = s.len().min(PROMPT_MAX - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s[..n]);
+        self.len += n;
+    }
+    
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
 
 fn streq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
@@ -146,7 +621,7 @@ fn trim(s: &[u8]) -> &[u8] {
     &s[start..end]
 }
 
-fn process_command(input: &[u8]) {
+fn process_command(env: &mut Env, input: &[u8]) {
     let cmd = trim(input);
     
     if cmd.is_empty() {
@@ -160,16 +635,28 @@ fn process_command(input: &[u8]) {
     } else if streq(cmd, b"help") {
         println("Built-in commands:");
         println("  help  - Show this help");
-        println("  echo  - Echo arguments");
+        println("  echo  - Echo arguments ($VAR expands)");
         println("  pid   - Show process ID");
+        println("  cd [DIR|-] - Change directory");
+        println("  ls [-l] [DIR] - List a directory");
+        println("  cat [FILE...] - Print files (stdin if none)");
+        println("  write FILE TEXT - Replace FILE with TEXT");
+        println("  env   - Print the environment");
+        println("  export KEY=VALUE - Set a variable for children");
         println("  exit  - Exit shell");
     } else if cmd.starts_with(b"echo ") {
         // Echo the rest of the line
-        let rest = &cmd[5..];
-        write(1, rest);
-        print("\n");
+        echo(env, &cmd[5..]);
     } else if streq(cmd, b"echo") {
         print("\n");
+    } else if streq(cmd, b"cd") || cmd.starts_with(b"cd ") {
+        cd(env, trim(&cmd[2..]));
+    } else if streq(cmd, b"ls") || cmd.starts_with(b"ls ") {
+        ls(&cmd[2..]);
+    } else if streq(cmd, b"cat") || cmd.starts_with(b"cat ") {
+        cat(&cmd[3..]);
+    } else if streq(cmd, b"write") || cmd.starts_with(b"write ") {
+        write_file(&cmd[5..]);
     } else if streq(cmd, b"pid") {
         let pid = getpid();
         print("PID: ");
@@ -177,7 +664,19 @@ fn process_command(input: &[u8]) {
         let digit = (pid as u8) + b'0';
         write(1, &[digit]);
         print("\n");
-    } else {
+    } else if streq(cmd, b"env") {
+        for i in 0..env.count {
+            write(1, env.entry(i));
+            print("\n");
+        }
+    } else if cmd.starts_with(b"export ") {
+        let kv = trim(&cmd[7..]);
+        if !kv.contains(&b'=') || kv[0] == b'=' {
+            println("usage: export KEY=VALUE");
+        } else if !env.set(kv) {
+            println("export: environment full");
+        }
+    } else if !run_program(env, cmd) {
         print("Unknown command: ");
         write(1, cmd);
         print("\n");
@@ -185,47 +684,327 @@ fn process_command(input: &[u8]) {
     }
 }
 
+/// Fork and exec `cmd` (path followed by arguments) with the shell's environment
+/// Returns false if the program couldn't be started.
+fn run_program(env: &Env, cmd: &[u8]) -> bool {
+    const MAX_ARGS: usize = 16;
+    
+    // NUL-separated copy of the command line for argv
+    let mut line = [0u8; MAX_INPUT + 1];
+    line[..cmd.len()].copy_from_slice(cmd);
+    let mut argv = [core::ptr::null::<u8>(); MAX_ARGS + 1];
+    let mut argc = 0;
+    let mut i = 0;
+    while i < cmd.len() && argc < MAX_ARGS {
+        if line[i] == b' ' {
+            line[i] = 0;
+            i += 1;
+            continue;
+        }
+        argv[argc] = line[i..].as_ptr();
+        argc += 1;
+        while i < cmd.len() && line[i] != b' ' {
+            i += 1;
+        }
+    }
+    if argc == 0 || line[0] != b'/' {
+        return false;
+    }
+    let envp = env.envp();
+    
+    let pid = unsafe { syscall1(SYS_FORK, 0) };
+    if pid < 0 {
+        return false;
+    }
+    if pid == 0 {
+        unsafe {
+            syscall3(SYS_EXECVE, argv[0] as usize, argv.as_ptr() as usize, envp.as_ptr() as usize);
+        }
+        println("exec failed");
+        exit(127);
+    }
+    
+    let mut status = 0i32;
+    unsafe { syscall3(SYS_WAIT4, pid as usize, &mut status as *mut i32 as usize, 0) };
+    true
+}
+
+// ============================================================================
+// Line Input
+// ============================================================================
+
+const HISTORY_SIZE: usize = 16;
+
+/// Ring of recent command lines
+struct History {
+    lines: [[u8; MAX_INPUT]; HISTORY_SIZE],
+    lens: [usize; HISTORY_SIZE],
+    next: usize,  // Slot the next line goes into
+    count: usize,
+}
+
+impl History {
+    const fn new() -> Self {
+        Self {
+            lines: [[0; MAX_INPUT]; HISTORY_SIZE],
+            lens: [0; HISTORY_SIZE],
+            next: 0,
+            count: 0,
+        }
+    }
+    
+    /// Remember a line (empty lines and repeats of the last one are skipped)
+    fn push(&mut self, line: &[u8]) {
+        if line.is_empty() || self.get(1) == Some(line) {
+            return;
+        }
+        let len = line.len().min(MAX_INPUT);
+        self.lines[self.next][..len].copy_from_slice(&line[..len]);
+        self.lens[self.next] = len;
+        self.next = (self.next + 1) % HISTORY_SIZE;
+        self.count = (self.count + 1).min(HISTORY_SIZE);
+    }
+    
+    /// The line entered `back` commands ago (1 = most recent)
+    fn get(&self, back: usize) -> Option<&[u8]> {
+        if back == 0 || back > self.count {
+            return None;
+        }
+        let slot = (self.next + HISTORY_SIZE - back) % HISTORY_SIZE;
+        Some(&self.lines[slot][..self.lens[slot]])
+    }
+}
+
+/// A decoded keypress
+enum Key {
+    Char(u8),
+    Enter,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    Other,
+}
+
+fn read_byte() -> u8 {
+    let mut ch = [0u8; 1];
+    // No input available yet: keep polling
+    while read(0, &mut ch) <= 0 {}
+    ch[0]
+}
+
+/// Read one key, decoding VT100 cursor sequences
+/// (ESC [ A..D, ESC [ H/F, ESC O H/F, ESC [ n ~)
+fn read_key() -> Key {
+    match read_byte() {
+        b'\n' | b'\r' => Key::Enter,
+        0x08 | 0x7f => Key::Backspace,
+        0x01 => Key::Home, // Ctrl-A
+        0x05 => Key::End,  // Ctrl-E
+        0x1b => {
+            let intro = read_byte();
+            if intro != b'[' && intro != b'O' {
+                return Key::Other;
+            }
+            match read_byte() {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                n @ b'0'..=b'9' => {
+                    // ESC [ n ~ ; swallow anything up to the terminator
+                    let mut last = read_byte();
+                    while last != b'~' && last.is_ascii_digit() {
+                        last = read_byte();
+                    }
+                    match (n, last) {
+                        (b'1' | b'7', b'~') => Key::Home,
+                        (b'4' | b'8', b'~') => Key::End,
+                        (b'3', b'~') => Key::Delete,
+                        _ => Key::Other,
+                    }
+                }
+                _ => Key::Other,
+            }
+        }
+        c if c >= 0x20 => Key::Char(c),
+        _ => Key::Other,
+    }
+}
+
+fn print_usize(mut n: usize) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    write(1, &digits[i..]);
+}
+
+/// Replace the visible line with `line` and put the cursor at `cursor`
+fn redraw(prompt: &Prompt, line: &[u8], cursor: usize) {
+    print("\r");
+    write(1, prompt.as_bytes());
+    write(1, line);
+    print("\x1b[K"); // Erase anything left from a longer line
+    if cursor < line.len() {
+        print("\x1b[");
+        print_usize(line.len() - cursor);
+        print("D");
+    }
+}
+
+/// Read a line into `buf` with cursor editing; Up/Down recall history
+/// Returns the line length.
+fn read_line(buf: &mut [u8; MAX_INPUT], history: &History, prompt: &Prompt) -> usize {
+    let mut len = 0;
+    let mut cursor = 0;
+    // How far back we are browsing (0 = the line being typed)
+    let mut back = 0;
+    // The line being typed, kept while browsing history
+    let mut draft = [0u8; MAX_INPUT];
+    let mut draft_len = 0;
+    
+    loop {
+        match read_key() {
+            Key::Enter => {
+                print("\n");
+                return len;
+            }
+            Key::Backspace => {
+                if cursor > 0 {
+                    buf.copy_within(cursor..len, cursor - 1);
+                    cursor -= 1;
+                    len -= 1;
+                    if cursor == len {
+                        print("\x08 \x08");
+                    } else {
+                        redraw(prompt, &buf[..len], cursor);
+                    }
+                }
+            }
+            Key::Delete => {
+                if cursor < len {
+                    buf.copy_within(cursor + 1..len, cursor);
+                    len -= 1;
+                    redraw(prompt, &buf[..len], cursor);
+                }
+            }
+            Key::Left => {
+                if cursor > 0 {
+                    cursor -= 1;
+                    print("\x1b[D");
+                }
+            }
+            Key::Right => {
+                if cursor < len {
+                    cursor += 1;
+                    print("\x1b[C");
+                }
+            }
+            Key::Home => {
+                cursor = 0;
+                redraw(prompt, &buf[..len], cursor);
+            }
+            Key::End => {
+                cursor = len;
+                redraw(prompt, &buf[..len], cursor);
+            }
+            Key::Up => {
+                if let Some(line) = history.get(back + 1) {
+                    if back == 0 {
+                        draft[..len].copy_from_slice(&buf[..len]);
+                        draft_len = len;
+                    }
+                    back += 1;
+                    // Recalled lines are copied, so editing them leaves history alone
+                    len = line.len();
+                    buf[..len].copy_from_slice(line);
+                    cursor = len;
+                    redraw(prompt, &buf[..len], cursor);
+                }
+            }
+            Key::Down => {
+                if back > 0 {
+                    back -= 1;
+                    let line = if back == 0 { &draft[..draft_len] } else { history.get(back).unwrap_or(&[]) };
+                    len = line.len();
+                    buf[..len].copy_from_slice(line);
+                    cursor = len;
+                    redraw(prompt, &buf[..len], cursor);
+                }
+            }
+            Key::Char(c) => {
+                // Buffer full: drop the keystroke rather than the line's tail
+                if len < MAX_INPUT - 1 {
+                    buf.copy_within(cursor..len, cursor + 1);
+                    buf[cursor] = c;
+                    cursor += 1;
+                    len += 1;
+                    if cursor == len {
+                        write(1, &[c]);
+                    } else {
+                        redraw(prompt, &buf[..len], cursor);
+                    }
+                }
+            }
+            Key::Other => {}
+        }
+    }
+}
+
 // ============================================================================
 // Entry Point
 // ============================================================================
 
+// The kernel enters with the argc/argv/envp block at the stack pointer;
+// hand it to shell_main before anything else touches the stack.
+#[cfg(target_arch = "x86_64")]
+global_asm!(
+    ".globl _start",
+    "_start:",
+    "mov rdi, rsp",
+    "and rsp, -16",
+    "call shell_main",
+);
+
+#[cfg(target_arch = "aarch64")]
+global_asm!(
+    ".globl _start",
+    "_start:",
+    "mov x0, sp",
+    "bl shell_main",
+);
+
 #[no_mangle]
-pub extern "C" fn _start() -> ! {
+extern "C" fn shell_main(sp: *const usize) -> ! {
+    let mut env = Env::new();
+    unsafe { env.import(sp); }
+    
     println("Aether Shell v0.1");
     println("Type 'help' for available commands.");
     println("");
     
     let mut input_buf = [0u8; MAX_INPUT];
-    let mut input_len = 0usize;
+    let mut history = History::new();
     
     loop {
-        print(PROMPT);
-        
-        // Read line (simplified - assumes read returns full line)
-        input_len = 0;
-        loop {
-            let mut ch = [0u8; 1];
-            let n = read(0, &mut ch);
-            if n <= 0 {
-                // No input available, yield
-                continue;
-            }
-            
-            if ch[0] == b'\n' || ch[0] == b'\r' {
-                input_buf[input_len] = 0;
-                print("\n");
-                break;
-            }
-            
-            if input_len < MAX_INPUT - 1 {
-                input_buf[input_len] = ch[0];
-                input_len += 1;
-                // Echo character
-                write(1, &ch);
-            }
-        }
-        
-        process_command(&input_buf[..input_len]);
+        let prompt = Prompt::build();
+        write(1, prompt.as_bytes());
+        let input_len = read_line(&mut input_buf, &history, &prompt);
+        history.push(trim(&input_buf[..input_len]));
+        process_command(&mut env, &input_buf[..input_len]);
     }
 }
 
@@ -0,0 +1,23 @@
+#!/bin/bash
+# Build Aether (x86_64) with an embedded symbol table for panic backtraces.
+#
+# Pass 1 links with an lld-link map file; pass 2 turns that map into the
+# table. The table lives in .rdata, after .text, so code addresses don't
+# move between passes.
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
+TARGET=x86_64-unknown-uefi
+MAP="$SCRIPT_DIR/target/$TARGET/release/aether.map"
+
+# RUSTFLAGS replaces .cargo/config.toml's flags, so keep frame pointers here
+export RUSTFLAGS="-C force-frame-pointers=yes -C link-arg=/MAP:$MAP"
+
+echo "[Build] Pass 1: linking with map file..."
+cargo build --release --target "$TARGET" --features symbols
+
+echo "[Build] Pass 2: embedding symbol table..."
+AETHER_SYMBOL_MAP="$MAP" cargo build --release --target "$TARGET" --features symbols
+
+echo "[Build] Done: target/$TARGET/release/aether.efi"
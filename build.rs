@@ -0,0 +1,152 @@
+//! Build script
+//!
+//! Stamps the build time into `AETHER_BUILD_TIME` (for uname), honouring
+//! `SOURCE_DATE_EPOCH` for reproducible builds.
+//!
+//! With the `symbols` feature, turns an lld-link map file (named by
+//! `AETHER_SYMBOL_MAP`) into a sorted `(rva, name)` table that the panic
+//! path uses to print `function+offset`. See build-symbols.sh.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=AETHER_SYMBOL_MAP");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+    println!("cargo:rustc-env=AETHER_BUILD_TIME={}", format_utc(epoch));
+
+    if env::var_os("CARGO_FEATURE_SYMBOLS").is_none() {
+        return;
+    }
+
+    let mut symbols = Vec::new();
+    if let Some(map) = env::var_os("AETHER_SYMBOL_MAP") {
+        let map = Path::new(&map);
+        println!("cargo:rerun-if-changed={}", map.display());
+        let text = fs::read_to_string(map).expect("failed to read AETHER_SYMBOL_MAP");
+        symbols = parse_map(&text);
+    } else {
+        // First pass of a two-pass build: empty table, same code layout
+        println!("cargo:warning=symbols feature enabled but AETHER_SYMBOL_MAP is unset; table is empty");
+    }
+
+    symbols.sort();
+    symbols.dedup_by_key(|(rva, _)| *rva);
+
+    let mut out = String::from("pub static SYMBOLS: &[(u64, &str)] = &[\n");
+    for (rva, name) in &symbols {
+        out.push_str(&format!("    (0x{:x}, {:?}),\n", rva, name));
+    }
+    out.push_str("];\n");
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("symbols.rs");
+    fs::write(dest, out).unwrap();
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for a Unix timestamp
+fn format_utc(secs: u64) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil-from-days (Howard Hinnant), with eras starting 0000-03-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, rem / 3600, rem / 60 % 60, rem % 60
+    )
+}
+
+/// Parse the "Publics by Value" / "Static symbols" tables of a link.exe style map:
+/// ` 0001:00000000       _ZN6aether4main17h0123456789abcdefE 0000000140001000 f   aether.o`
+fn parse_map(text: &str) -> Vec<(u64, String)> {
+    let mut load_base = 0u64;
+    let mut symbols = Vec::new();
+
+    for line in text.lines() {
+        if let Some(rest) = line.trim().strip_prefix("Preferred load address is ") {
+            load_base = u64::from_str_radix(rest.trim(), 16).unwrap_or(0);
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 {
+            continue;
+        }
+        let (section, _) = match fields[0].split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        // Section 0 holds absolute/linker-defined symbols
+        if section.is_empty() || section.chars().all(|c| c == '0') {
+            continue;
+        }
+        let addr = match u64::from_str_radix(fields[2], 16) {
+            Ok(addr) if addr >= load_base => addr,
+            _ => continue,
+        };
+
+        symbols.push((addr - load_base, demangle(fields[1])));
+    }
+
+    symbols
+}
+
+/// Minimal legacy Rust demangler: `_ZN3foo3bar17h<hash>E` -> `foo::bar`
+fn demangle(name: &str) -> String {
+    let body = match name.strip_prefix("_ZN").and_then(|s| s.strip_suffix('E')) {
+        Some(body) => body,
+        None => return name.to_string(),
+    };
+
+    let mut parts = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let len: usize = match rest[..digits].parse() {
+            Ok(len) => len,
+            Err(_) => return name.to_string(),
+        };
+        if digits + len > rest.len() {
+            return name.to_string();
+        }
+        parts.push(&rest[digits..digits + len]);
+        rest = &rest[digits + len..];
+    }
+
+    // Drop the trailing hash component
+    if let Some(last) = parts.last() {
+        if last.len() == 17 && last.starts_with('h') && last[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            parts.pop();
+        }
+    }
+
+    parts
+        .iter()
+        .map(|p| {
+            p.replace("$LT$", "<")
+                .replace("$GT$", ">")
+                .replace("$RF$", "&")
+                .replace("$BP$", "*")
+                .replace("$C$", ",")
+                .replace("$u20$", " ")
+                .replace("$u7b$", "{")
+                .replace("$u7d$", "}")
+                .replace("..", "::")
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
@@ -4,9 +4,7 @@
 use core::panic::PanicInfo;
 use core::arch::asm;
 
-/// Syscall numbers
-const SYS_WRITE: usize = 1;
-const SYS_EXIT: usize = 60;
+use aether_abi::syscall::{SYS_EXIT, SYS_WRITE};
 
 // ================================================================================
 // x86_64 Syscall Wrappers
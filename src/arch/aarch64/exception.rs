@@ -33,6 +33,37 @@ struct ExceptionVectorTable {
     serror_lower_el_aarch32: [u8; 0x80],
 }
 
+/// User registers saved by `el0_sync_entry`
+/// Handlers read syscall arguments from here and write results back; the
+/// whole frame is restored before `eret`.
+#[repr(C)]
+pub struct TrapFrame {
+    /// x0-x30
+    pub x: [u64; 31],
+    pub sp_el0: u64,
+    pub elr: u64,
+    pub spsr: u64,
+}
+
+// el0_sync_entry hard-codes the frame layout
+const _: () = assert!(core::mem::size_of::<TrapFrame>() == 272);
+
+/// Read SPSR_EL1 (saved PSTATE of the interrupted context)
+pub fn read_spsr() -> u64 {
+    let val: u64;
+    unsafe {
+        asm!("mrs {}, spsr_el1", out(reg) val);
+    }
+    val
+}
+
+/// True if the exception being handled was taken from EL0
+///
+/// SPSR_EL1.M[3:0] == 0b0000 (EL0t) means we came from userspace.
+pub fn is_user() -> bool {
+    read_spsr() & 0xF == 0
+}
+
 /// Initialize exception handling
 pub fn init() {
     log::info!("[Exception] Setting up ARM64 exception vectors...");
@@ -103,7 +134,7 @@ unsafe extern "C" fn exception_vector_table() {
         // ========================================
         
         // Synchronous - Lower EL AArch64 (SVC from userspace)
-        "b sync_lower_el_handler",
+        "b el0_sync_entry",
         ".balign 0x80",
         
         // IRQ - Lower EL AArch64
@@ -140,9 +171,67 @@ extern "C" fn sync_exception_handler() {
     loop { core::hint::spin_loop(); }
 }
 
+/// Entry for synchronous exceptions from EL0
+/// Saves the user context as a `TrapFrame` on the kernel stack, hands it to
+/// `sync_lower_el_handler`, then restores it and returns to userspace.
+#[unsafe(naked)]
+#[no_mangle]
+unsafe extern "C" fn el0_sync_entry() {
+    core::arch::naked_asm!(
+        "sub sp, sp, #272",  // size_of::<TrapFrame>()
+        "stp x0, x1, [sp, #0]",
+        "stp x2, x3, [sp, #16]",
+        "stp x4, x5, [sp, #32]",
+        "stp x6, x7, [sp, #48]",
+        "stp x8, x9, [sp, #64]",
+        "stp x10, x11, [sp, #80]",
+        "stp x12, x13, [sp, #96]",
+        "stp x14, x15, [sp, #112]",
+        "stp x16, x17, [sp, #128]",
+        "stp x18, x19, [sp, #144]",
+        "stp x20, x21, [sp, #160]",
+        "stp x22, x23, [sp, #176]",
+        "stp x24, x25, [sp, #192]",
+        "stp x26, x27, [sp, #208]",
+        "stp x28, x29, [sp, #224]",
+        "mrs x9, sp_el0",
+        "stp x30, x9, [sp, #240]",
+        "mrs x9, elr_el1",
+        "mrs x10, spsr_el1",
+        "stp x9, x10, [sp, #256]",
+        
+        "mov x0, sp",
+        "bl sync_lower_el_handler",
+        
+        // The handler may have changed any of these (x0 = return value)
+        "ldp x9, x10, [sp, #256]",
+        "msr elr_el1, x9",
+        "msr spsr_el1, x10",
+        "ldp x30, x9, [sp, #240]",
+        "msr sp_el0, x9",
+        "ldp x0, x1, [sp, #0]",
+        "ldp x2, x3, [sp, #16]",
+        "ldp x4, x5, [sp, #32]",
+        "ldp x6, x7, [sp, #48]",
+        "ldp x8, x9, [sp, #64]",
+        "ldp x10, x11, [sp, #80]",
+        "ldp x12, x13, [sp, #96]",
+        "ldp x14, x15, [sp, #112]",
+        "ldp x16, x17, [sp, #128]",
+        "ldp x18, x19, [sp, #144]",
+        "ldp x20, x21, [sp, #160]",
+        "ldp x22, x23, [sp, #176]",
+        "ldp x24, x25, [sp, #192]",
+        "ldp x26, x27, [sp, #208]",
+        "ldp x28, x29, [sp, #224]",
+        "add sp, sp, #272",
+        "eret",
+    );
+}
+
 /// Synchronous exception from lower EL (userspace syscall)
 #[no_mangle]
-extern "C" fn sync_lower_el_handler() {
+extern "C" fn sync_lower_el_handler(frame: &mut TrapFrame) {
     // This is called when userspace executes SVC
     // Dispatch to syscall handler
     unsafe {
@@ -153,7 +242,11 @@ extern "C" fn sync_lower_el_handler() {
         
         if ec == 0x15 {
             // SVC from AArch64 (syscall)
-            crate::arch::aarch64::svc::handle_svc();
+            crate::arch::aarch64::svc::handle_svc(frame);
+        } else if is_user() {
+            // A user program faulted: kill only that task
+            log::error!("[OOPS] Unhandled exception from EL0: EC=0x{:x}, killing task", ec);
+            crate::sched::exit_current(11); // Killed by SIGSEGV
         } else {
             log::error!("[Exception] Unhandled exception from EL0: EC=0x{:x}", ec);
         }
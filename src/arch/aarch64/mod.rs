@@ -17,12 +17,17 @@ pub fn init() {
 /// Enter usermode (EL0) from kernel (EL1)
 /// 
 /// This function sets up SPSR_EL1 and ELR_EL1 to return to EL0,
-/// then executes `eret` to jump to userspace.
+/// then executes `eret` to jump to userspace. x0-x30 are zeroed: argc,
+/// argv and envp are on the stack, and nothing of the kernel's should
+/// reach EL0 in a register.
 /// 
 /// # Safety
 /// - `entry_point` must point to valid userspace code
-/// - `stack_pointer` must point to valid userspace stack
+/// - `stack_pointer` must point to valid userspace stack, 16-byte aligned
+///   (SP alignment checking faults on anything else)
 pub unsafe fn enter_usermode(entry_point: u64, stack_pointer: u64) -> ! {
+    assert!(stack_pointer % 16 == 0, "user stack 0x{:x} is not 16-byte aligned", stack_pointer);
+    
     // SPSR_EL1 value for returning to EL0:
     // - M[3:0] = 0b0000 (EL0t - EL0 with SP_EL0)
     // - All interrupt masks clear (enable interrupts in userspace)
@@ -39,6 +44,39 @@ pub unsafe fn enter_usermode(entry_point: u64, stack_pointer: u64) -> ! {
         // Set saved program status (return to EL0)
         "msr spsr_el1, {spsr}",
         
+        // Everything is in system registers now: clear the GPRs
+        "mov x0, xzr",
+        "mov x1, xzr",
+        "mov x2, xzr",
+        "mov x3, xzr",
+        "mov x4, xzr",
+        "mov x5, xzr",
+        "mov x6, xzr",
+        "mov x7, xzr",
+        "mov x8, xzr",
+        "mov x9, xzr",
+        "mov x10, xzr",
+        "mov x11, xzr",
+        "mov x12, xzr",
+        "mov x13, xzr",
+        "mov x14, xzr",
+        "mov x15, xzr",
+        "mov x16, xzr",
+        "mov x17, xzr",
+        "mov x18, xzr",
+        "mov x19, xzr",
+        "mov x20, xzr",
+        "mov x21, xzr",
+        "mov x22, xzr",
+        "mov x23, xzr",
+        "mov x24, xzr",
+        "mov x25, xzr",
+        "mov x26, xzr",
+        "mov x27, xzr",
+        "mov x28, xzr",
+        "mov x29, xzr",
+        "mov x30, xzr",
+        
         // Return to EL0
         "eret",
         
@@ -6,7 +6,7 @@
 //! - x0-x5 = arguments
 //! - x0 = return value
 
-use core::arch::asm;
+use super::exception::TrapFrame;
 
 /// Initialize SVC handling
 pub fn init() {
@@ -16,40 +16,16 @@ pub fn init() {
 }
 
 /// Handle SVC exception from userspace
-/// Called from exception.rs when ESR_EL1.EC == 0x15
-pub fn handle_svc() {
-    // Read syscall arguments from saved registers
-    // In a real implementation, we'd save/restore the full context
+/// Called from exception.rs when ESR_EL1.EC == 0x15, with the user
+/// registers saved in `frame`
+pub fn handle_svc(frame: &mut TrapFrame) {
+    let nr = frame.x[8] as usize;
+    let [arg0, arg1, arg2, arg3, arg4, arg5] = [0, 1, 2, 3, 4, 5].map(|i| frame.x[i] as usize);
     
-    let (nr, arg0, arg1, arg2): (usize, usize, usize, usize);
+    let result = syscall_dispatch_arm64(nr, arg0, arg1, arg2, arg3, arg4, arg5);
     
-    unsafe {
-        // These would normally come from saved context
-        // For now, read directly (simplified)
-        asm!(
-            "mov {nr}, x8",
-            "mov {a0}, x0",
-            "mov {a1}, x1",
-            "mov {a2}, x2",
-            nr = out(reg) nr,
-            a0 = out(reg) arg0,
-            a1 = out(reg) arg1,
-            a2 = out(reg) arg2,
-        );
-    }
-    
-    // Dispatch to Rust syscall handler
-    let result = crate::syscall::dispatch(nr, arg0, arg1, arg2);
-    
-    // Return value in x0
-    unsafe {
-        asm!(
-            "mov x0, {result}",
-            result = in(reg) result as u64,
-        );
-    }
-    
-    // Return to userspace via eret (done by exception return)
+    // Return value in x0, restored by the exception return path
+    frame.x[0] = result as u64;
 }
 
 /// ARM64 syscall dispatcher (alternative entry point)
@@ -59,8 +35,9 @@ pub extern "C" fn syscall_dispatch_arm64(
     arg0: usize,
     arg1: usize,
     arg2: usize,
-    _arg3: usize,
-    _arg4: usize,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
 ) -> isize {
-    crate::syscall::dispatch(nr, arg0, arg1, arg2)
+    crate::syscall::dispatch(nr, arg0, arg1, arg2, arg3, arg4, arg5)
 }
@@ -0,0 +1,57 @@
+//! Frame-Pointer Stack Walker
+//!
+//! Relies on `-C force-frame-pointers=yes` (see .cargo/config.toml).
+//! Every frame starts with `[rbp] = caller's rbp, [rbp + 8] = return address`.
+
+use core::arch::asm;
+
+/// Give up after this many frames (corrupt chains can loop)
+pub const MAX_FRAMES: usize = 32;
+
+extern "C" {
+    // Synthesized by the PE/COFF linker at the start of the loaded image
+    static __ImageBase: u8;
+}
+
+/// Load address of the kernel image, for turning return addresses into RVAs
+pub fn image_base() -> u64 {
+    unsafe { &raw const __ImageBase as u64 }
+}
+
+pub fn current_rbp() -> u64 {
+    let rbp: u64;
+    unsafe { asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack)); }
+    rbp
+}
+
+pub fn current_rsp() -> u64 {
+    let rsp: u64;
+    unsafe { asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack)); }
+    rsp
+}
+
+/// Walk the RBP chain starting at `rbp`, calling `f(depth, return_address)`
+/// for each frame.
+///
+/// # Safety
+/// `rbp` must be zero or point into a readable stack.
+pub unsafe fn walk(mut rbp: u64, mut f: impl FnMut(usize, u64)) {
+    for depth in 0..MAX_FRAMES {
+        if rbp == 0 || rbp & 0x7 != 0 {
+            break;
+        }
+        
+        let ret = *((rbp + 8) as *const u64);
+        if ret == 0 {
+            break;
+        }
+        f(depth, ret);
+        
+        // Callers' frames live at higher addresses; anything else is garbage
+        let next = *(rbp as *const u64);
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+}
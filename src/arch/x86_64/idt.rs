@@ -5,3 +5,18 @@
 
 /// Re-export from main interrupts module
 pub use crate::interrupts::init_idt;
+
+use x86_64::structures::idt::InterruptStackFrame;
+
+/// Privilege-level queries on the frame the CPU pushed for an exception
+pub trait TrapFrameExt {
+    /// True if the exception was raised while running in Ring 3
+    fn is_user(&self) -> bool;
+}
+
+impl TrapFrameExt for InterruptStackFrame {
+    fn is_user(&self) -> bool {
+        // RPL (low 2 bits) of the saved CS is the privilege level we came from
+        self.code_segment & 3 == 3
+    }
+}
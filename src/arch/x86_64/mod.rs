@@ -1,20 +1,30 @@
 //! Architecture-specific code for x86_64
 
+pub mod backtrace;
 pub mod gdt;
 pub mod idt;
 pub mod paging;
+pub mod percpu;
 pub mod syscall;
 
 /// Initialize x86_64 architecture
 pub fn init() {
     gdt::init();
+    percpu::init();
     // interrupts::init_idt(); // Moved to main.rs for now or here
     syscall::init();
 }
 
 /// Jump to userspace (Ring 3)
 /// Does not return.
+///
+/// `stack_pointer` must point at argc and be 16-byte aligned: `_start` is
+/// jumped to, not called, so the SysV ABI wants it aligned rather than
+/// 8 below alignment as at a function entry. Every general-purpose
+/// register is zeroed so nothing of the kernel's reaches Ring 3.
 pub unsafe fn enter_usermode(entry_point: u64, stack_pointer: u64) -> ! {
+    assert!(stack_pointer % 16 == 0, "user stack 0x{:x} is not 16-byte aligned", stack_pointer);
+    
     let user_cs = gdt::user_cs();
     let user_ds = gdt::user_ds();
     
@@ -26,13 +36,31 @@ pub unsafe fn enter_usermode(entry_point: u64, stack_pointer: u64) -> ! {
         "mov ds, {ds:x}",
         "mov es, {ds:x}",
         "mov fs, {ds:x}",
-        "mov gs, {ds:x}",
+        // GS keeps its selector: reloading it would wipe the per-CPU base
         
         "push {ss}",  // SS
         "push {rsp}", // RSP
         "push {rflags}", // RFLAGS
         "push {cs}",  // CS
         "push {rip}", // RIP
+        
+        // The frame is built: the registers can go
+        "xor eax, eax",
+        "xor ebx, ebx",
+        "xor ecx, ecx",
+        "xor edx, edx",
+        "xor esi, esi",
+        "xor edi, edi",
+        "xor ebp, ebp",
+        "xor r8d, r8d",
+        "xor r9d, r9d",
+        "xor r10d, r10d",
+        "xor r11d, r11d",
+        "xor r12d, r12d",
+        "xor r13d, r13d",
+        "xor r14d, r14d",
+        "xor r15d, r15d",
+        "swapgs",     // Per-CPU base out, user GS base in
         "iretq",
         ds = in(reg) user_ds,
         ss = in(reg) user_ds as u64, // Pushed as u64
@@ -0,0 +1,133 @@
+//! Per-CPU Data
+//!
+//! Each CPU's `PerCpu` block is reached through the GS segment base. In
+//! the kernel GS_BASE points at it; while user code runs it sits in
+//! IA32_KERNEL_GS_BASE instead, and every path in from Ring 3 (the
+//! syscall entry, interrupt handlers) executes `swapgs` to get it back.
+//! There is one CPU for now, so there is one block.
+
+use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::mem::offset_of;
+use x86_64::structures::idt::InterruptStackFrame;
+
+use super::idt::TrapFrameExt;
+use super::syscall::wrmsr;
+
+pub const MSR_GS_BASE: u32 = 0xC0000101;        // GS base in use
+pub const MSR_KERNEL_GS_BASE: u32 = 0xC0000102; // GS base that swapgs swaps in
+
+const KERNEL_STACK_SIZE: usize = 4096 * 4;
+
+#[repr(C, align(16))]
+struct KernelStack([u8; KERNEL_STACK_SIZE]);
+
+/// A static owned by one CPU
+/// Only that CPU touches it, with interrupts off or before they are
+/// enabled, so handing out raw pointers to it is sound.
+struct CpuLocal<T>(UnsafeCell<T>);
+
+unsafe impl<T> Sync for CpuLocal<T> {}
+
+impl<T> CpuLocal<T> {
+    const fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+    
+    fn get(&self) -> *mut T {
+        self.0.get()
+    }
+}
+
+/// Syscall stack until the scheduler installs the first task's own
+static KERNEL_STACK: CpuLocal<KernelStack> = CpuLocal::new(KernelStack([0; KERNEL_STACK_SIZE]));
+
+/// What GS points at in kernel mode
+/// The syscall entry reads the first two fields by offset.
+#[repr(C)]
+pub struct PerCpu {
+    /// Top of the kernel stack syscalls run on (the current task's)
+    pub kernel_rsp: u64,
+    /// User RSP, parked here while the syscall entry switches stacks
+    pub user_rsp: u64,
+    /// `Arc::as_ptr` of the running task, 0 before the scheduler starts
+    pub current_task: u64,
+    /// Index of this CPU
+    pub cpu_id: u64,
+}
+
+static BOOT_CPU: CpuLocal<PerCpu> = CpuLocal::new(PerCpu { kernel_rsp: 0, user_rsp: 0, current_task: 0, cpu_id: 0 });
+
+/// Point GS at the boot CPU's block
+/// Must run after gdt::init: loading the GS selector resets the base.
+pub fn init() {
+    let cpu = BOOT_CPU.get();
+    unsafe {
+        (*cpu).kernel_rsp = KERNEL_STACK.get() as u64 + KERNEL_STACK_SIZE as u64;
+        wrmsr(MSR_GS_BASE, cpu as u64);
+        // User space starts with a zero GS base
+        wrmsr(MSR_KERNEL_GS_BASE, 0);
+    }
+    log::info!("[Arch] Per-CPU data for CPU {} at GS", cpu_id());
+}
+
+unsafe fn read(offset: usize) -> u64 {
+    let value: u64;
+    asm!("mov {}, gs:[{}]", out(reg) value, in(reg) offset, options(nostack, readonly, preserves_flags));
+    value
+}
+
+unsafe fn write(offset: usize, value: u64) {
+    asm!("mov gs:[{}], {}", in(reg) offset, in(reg) value, options(nostack, preserves_flags));
+}
+
+/// Index of the CPU we are running on
+pub fn cpu_id() -> u32 {
+    unsafe { read(offset_of!(PerCpu, cpu_id)) as u32 }
+}
+
+/// Top of this CPU's syscall stack
+pub fn kernel_stack_top() -> u64 {
+    unsafe { read(offset_of!(PerCpu, kernel_rsp)) }
+}
+
+/// Make syscalls on this CPU run on the stack ending at `top`
+/// Only with interrupts off, while switching tasks.
+pub fn set_kernel_stack_top(top: u64) {
+    unsafe { write(offset_of!(PerCpu, kernel_rsp), top) }
+}
+
+/// Address of the task running on this CPU (0 if none yet)
+pub fn current_task() -> u64 {
+    unsafe { read(offset_of!(PerCpu, current_task)) }
+}
+
+/// Record the task now running on this CPU
+pub fn set_current_task(task: u64) {
+    unsafe { write(offset_of!(PerCpu, current_task), task) }
+}
+
+/// Kernel GS for the lifetime of an interrupt handler
+/// Swaps GS on entry from Ring 3 and back when dropped; handlers that
+/// never return to user mode just never drop it.
+pub struct KernelGs {
+    from_user: bool,
+}
+
+impl KernelGs {
+    pub fn enter(frame: &InterruptStackFrame) -> Self {
+        let from_user = frame.is_user();
+        if from_user {
+            unsafe { asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+        Self { from_user }
+    }
+}
+
+impl Drop for KernelGs {
+    fn drop(&mut self) {
+        if self.from_user {
+            unsafe { asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+    }
+}
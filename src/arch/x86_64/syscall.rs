@@ -10,20 +10,39 @@
 use core::arch::asm;
 
 /// Model Specific Registers for SYSCALL
+pub const MSR_EFER: u32 = 0xC0000080;     // Extended features (SCE = bit 0)
 pub const MSR_STAR: u32 = 0xC0000081;     // Segment selectors
 pub const MSR_LSTAR: u32 = 0xC0000082;    // RIP for syscall handler
 pub const MSR_SFMASK: u32 = 0xC0000084;   // RFLAGS mask
 
+const EFER_SCE: u64 = 1 << 0;
+
+/// CPUID 0x80000001 EDX bit 11: SYSCALL/SYSRET in 64-bit mode
+fn cpu_has_syscall() -> bool {
+    use core::arch::x86_64::__cpuid;
+    __cpuid(0x8000_0000).eax >= 0x8000_0001 && __cpuid(0x8000_0001).edx & (1 << 11) != 0
+}
+
 /// Initialize SYSCALL/SYSRET mechanism
 pub fn init() {
+    if !cpu_has_syscall() {
+        log::error!("[Syscall] CPU does not support SYSCALL/SYSRET");
+        return;
+    }
+    
     let k_cs = super::gdt::kernel_cs() as u64;
+    let k_ds = super::gdt::kernel_ds() as u64;
+    let u_cs = super::gdt::user_cs() as u64;
     let u_ds = super::gdt::user_ds() as u64;
     
-    // For SYSRET: CS = STAR[63:48] + 16, SS = STAR[63:48] + 8.
-    // Our GDT: UserData (idx 3), UserCode (idx 4).
-    // So Base = UserData - 8.
-    // Use (u_ds & !3) to get base index, then -8, then |3 (RPL).
-    let sysret_base = ((u_ds & !3) - 8) | 3;
+    // SYSCALL: CS = STAR[47:32], SS = STAR[47:32] + 8.
+    // SYSRET:  CS = STAR[63:48] + 16, SS = STAR[63:48] + 8 (RPL forced to 3).
+    // Both selectors come from the GDT; the layout it builds has to fit.
+    let sysret_base = (u_cs & !3) - 16;
+    debug_assert_eq!(k_ds & !3, (k_cs & !3) + 8, "kernel SS must follow kernel CS for SYSCALL");
+    debug_assert_eq!(u_ds & !3, sysret_base + 8, "user SS must sit 8 below user CS for SYSRET");
+    debug_assert_eq!(u_cs & 3, 3, "user CS must have RPL 3");
+    let sysret_base = sysret_base | 3;
     
     unsafe {
         // STAR: [63:48] = User CS/SS base, [47:32] = Kernel CS/SS base
@@ -35,13 +54,31 @@ pub fn init() {
         
         // SFMASK: Flags to clear on syscall (IF, TF, DF)
         wrmsr(MSR_SFMASK, 0x300); // Clear IF and DF
+        
+        // Without SCE the syscall instruction is #UD
+        wrmsr(MSR_EFER, rdmsr(MSR_EFER) | EFER_SCE);
+        log::info!("[Syscall] EFER.SCE set (EFER=0x{:x})", rdmsr(MSR_EFER));
     }
     
     log::info!("[Syscall] x86_64 SYSCALL/SYSRET initialized");
 }
 
+/// Read a Model Specific Register
+pub(super) unsafe fn rdmsr(msr: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    asm!(
+        "rdmsr",
+        in("ecx") msr,
+        out("eax") low,
+        out("edx") high,
+        options(nostack, nomem)
+    );
+    ((high as u64) << 32) | low as u64
+}
+
 /// Write to Model Specific Register
-unsafe fn wrmsr(msr: u32, value: u64) {
+pub(super) unsafe fn wrmsr(msr: u32, value: u64) {
     let low = value as u32;
     let high = (value >> 32) as u32;
     asm!(
@@ -55,16 +92,29 @@ unsafe fn wrmsr(msr: u32, value: u64) {
 
 /// Syscall entry point (naked function)
 /// Called when userspace executes `syscall` instruction
+///
+/// `syscall` leaves RSP on the user stack, so the first thing done is
+/// `swapgs` to reach the `PerCpu` block and move onto its kernel
+/// stack; the user RSP is kept on that stack and reloaded, with the user
+/// GS base swapped back, right before `sysretq`. IF stays clear (SFMASK)
+/// until the switch is done.
+///
+/// Only rax comes back changed. rcx and r11 are the user RIP/RFLAGS that
+/// `sysretq` consumes, and every other GPR, the argument registers
+/// included, is restored to the value user space had: Linux preserves
+/// them across `syscall`, and the dispatcher leaves kernel pointers in
+/// the caller-saved ones that must not reach Ring 3.
 #[unsafe(naked)]
-// But wait, #[naked] is feature(naked_functions). I didn't enable it in main.rs?
-// main.rs has #![feature(abi_x86_interrupt)].
-// I should add #![feature(naked_functions)] to lib.rs or main.rs if it's a bin.
-// But this module is part of the binary crate 'aether'.
 #[no_mangle]
 pub unsafe extern "C" fn syscall_entry() {
     core::arch::naked_asm!(
-        // Save user stack pointer (in rcx after syscall)
-        // rcx = user RIP, r11 = user RFLAGS
+        // rcx = user RIP, r11 = user RFLAGS, rsp = user stack
+        
+        // Switch to the kernel stack, keeping the user RSP on it
+        "swapgs",
+        "mov gs:[{user_rsp}], rsp",
+        "mov rsp, gs:[{kernel_rsp}]",
+        "push qword ptr gs:[{user_rsp}]",
         
         // Push callee-saved registers
         "push rbx",
@@ -78,19 +128,44 @@ pub unsafe extern "C" fn syscall_entry() {
         "push rcx",  // User RIP
         "push r11",  // User RFLAGS
         
-        // Arguments are already in correct registers for our dispatch
-        // rax = syscall number
-        // rdi = arg0, rsi = arg1, rdx = arg2, r10 = arg3, r8 = arg4, r9 = arg5
+        // Save the argument registers; the call below clobbers them
+        "push rdi",
+        "push rsi",
+        "push rdx",
+        "push r10",
+        "push r8",
+        "push r9",
         
-        // Move r10 to rcx for C calling convention (arg3)
-        "mov rcx, r10",
+        // Shuffle the syscall ABI into SysV order for syscall_dispatch:
+        //   syscall: rax = nr, rdi, rsi, rdx, r10, r8, r9 = arg0..arg5
+        //   SysV:    rdi, rsi, rdx, rcx, r8, r9, [rsp] = nr, arg0..arg5
+        // rbp (already saved) remembers rsp so the stack can be realigned.
+        "mov rbp, rsp",
+        "and rsp, -16",
+        "sub rsp, 8",
+        "push r9",        // arg5 goes on the stack; rsp is 16-byte aligned again
+        "mov r9, r8",     // arg4
+        "mov r8, r10",    // arg3
+        "mov rcx, rdx",   // arg2
+        "mov rdx, rsi",   // arg1
+        "mov rsi, rdi",   // arg0
+        "mov rdi, rax",   // nr
         
         // Call Rust syscall dispatcher
         // fn syscall_dispatch(nr: usize, a0: usize, a1: usize, a2: usize, a3: usize, a4: usize, a5: usize) -> isize
         "call syscall_dispatch",
+        "mov rsp, rbp",
         
         // Return value is in rax
         
+        // Restore the user's argument registers over the kernel's values
+        "pop r9",
+        "pop r8",
+        "pop r10",
+        "pop rdx",
+        "pop rsi",
+        "pop rdi",
+        
         // Restore user RFLAGS and RIP
         "pop r11",
         "pop rcx",
@@ -103,8 +178,14 @@ pub unsafe extern "C" fn syscall_entry() {
         "pop rbp",
         "pop rbx",
         
+        // Back onto the user stack, with the user GS base
+        "pop rsp",
+        "swapgs",
+        
         // Return to userspace
         "sysretq",
+        kernel_rsp = const core::mem::offset_of!(super::percpu::PerCpu, kernel_rsp),
+        user_rsp = const core::mem::offset_of!(super::percpu::PerCpu, user_rsp),
     );
 }
 
@@ -117,7 +198,10 @@ pub extern "C" fn syscall_dispatch(
     arg2: usize,
     arg3: usize,
     arg4: usize,
-    _arg5: usize,
+    arg5: usize,
 ) -> isize {
-    crate::syscall::dispatch(nr, arg0, arg1, arg2)
+    let ret = crate::syscall::dispatch(nr, arg0, arg1, arg2, arg3, arg4, arg5);
+    #[cfg(feature = "watchdog")]
+    crate::sched::watchdog::progress();
+    ret
 }
@@ -1,6 +1,30 @@
 use alloc::vec::Vec;
-use aether_core::backend::{Backend, ExitReason};
-use aether_abi::mmio::RAM_SIZE;
+use aether_core::backend::{Backend, BackendError, ExitReason};
+use aether_core::scheduler::ProcessId;
+use aether_abi::mmio::{LOAD, RAM_SIZE};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+
+/// How a guest gets CPU time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecMode {
+    /// Guest runs as a scheduler process; the timer interrupt switches into it
+    /// and `step` is a no-op that returns `Yield`.
+    Scheduled,
+    /// The owner drives the guest by calling `step`, which switches onto the
+    /// guest's own stack and comes back on the next exit event.
+    Stepped,
+}
+
+/// Stack size for guests driven through `step`
+const STEP_STACK_SIZE: usize = 64 * 1024;
+
+// Encoded ExitReason handed from the guest side back to `step`
+const EXIT_YIELD: u8 = 0;
+const EXIT_RETURNED: u8 = 1;
+
+/// Backend currently inside `step` (null when no stepped guest is running)
+static STEPPING: AtomicPtr<UefiBackend> = AtomicPtr::new(ptr::null_mut());
 
 pub struct UefiBackend {
     // We hold the guest memory buffer.
@@ -9,6 +33,20 @@ pub struct UefiBackend {
     #[allow(dead_code)]
     mem: Vec<u8>,
     
+    mode: ExecMode,
+    
+    // Stepped mode state: the guest runs on `step_stack`, and we bounce
+    // between `host_sp` and `guest_sp` with switch_context.
+    #[allow(dead_code)]
+    step_stack: Vec<u8>,
+    guest_sp: AtomicUsize,
+    host_sp: AtomicUsize,
+    on_guest: AtomicBool,
+    exit_code: AtomicU8,
+    /// The guest is done, with `status`; see `Backend::has_exited`
+    exited: AtomicBool,
+    status: AtomicI32,
+    
     // UEFI specific handles
 }
 
@@ -18,35 +56,55 @@ unsafe impl Send for UefiBackend {}
 unsafe impl Sync for UefiBackend {}
 
 impl UefiBackend {
-    pub fn new(_guest_image: Vec<u8>) -> Self {
-        log::info!("[Aether::UefiBackend] initializing...");
+    pub fn new(guest_image: Vec<u8>) -> Result<Self, BackendError> {
+        Self::with_mode(guest_image, ExecMode::Scheduled)
+    }
+
+    pub fn with_mode(guest_image: Vec<u8>, mode: ExecMode) -> Result<Self, BackendError> {
+        log::info!("[Aether::UefiBackend] initializing ({:?})...", mode);
+        
+        let guest_bin = guest_image;
+        
+        // Anything past the load area would land on the MMIO regions
+        if guest_bin.len() > LOAD.len {
+            return Err(BackendError::ImageTooLarge { size: guest_bin.len(), max: LOAD.len });
+        }
         
         // 1. Allocate Guest Memory
         let mut mem = alloc::vec![0u8; RAM_SIZE];
         log::info!("[Aether::UefiBackend] Allocated {} MB for Guest RAM", RAM_SIZE / 1024 / 1024);
         
-        let guest_bin = _guest_image;
-        
-        if guest_bin.len() > mem.len() {
-            panic!("Guest binary larger than RAM");
-        }
-        
         unsafe {
             // Copy guest to start of memory (Load Addr 0)
             core::ptr::copy_nonoverlapping(guest_bin.as_ptr(), mem.as_mut_ptr(), guest_bin.len());
-            
-            // Register Framebuffer Bridge
-            // Guest writes to mem + FB_ADDR
-            // We tell video module that's where the shadow buffer is.
-            let fb_ptr = mem.as_ptr().add(aether_abi::mmio::FB_ADDR as usize);
-            crate::video::set_guest_buffer(fb_ptr);
         }
         log::info!("[Aether::UefiBackend] Guest Loaded: {} bytes", guest_bin.len());
         
-        UefiBackend {
-            mem
+        // Stepped guests get their own stack whose first switch lands in guest_entry
+        let mut step_stack = Vec::new();
+        let mut guest_sp = 0;
+        if mode == ExecMode::Stepped {
+            step_stack = alloc::vec![0u8; STEP_STACK_SIZE];
+            guest_sp = crate::multitasking::init_stack(
+                &mut step_stack,
+                guest_entry as *const () as usize,
+                mem.as_ptr() as usize,
+            ).expect("step stack is larger than the initial frame");
         }
+        
+        Ok(UefiBackend {
+            mem,
+            mode,
+            step_stack,
+            guest_sp: AtomicUsize::new(guest_sp),
+            host_sp: AtomicUsize::new(0),
+            on_guest: AtomicBool::new(false),
+            exit_code: AtomicU8::new(EXIT_YIELD),
+            exited: AtomicBool::new(false),
+            status: AtomicI32::new(0),
+        })
     }
+
     pub fn entry_point(&self) -> usize {
         self.mem.as_ptr() as usize
     }
@@ -54,6 +112,35 @@ impl UefiBackend {
     pub fn base_address(&self) -> usize {
         self.mem.as_ptr() as usize
     }
+
+    /// `len` bytes of guest RAM at `offset`, if they lie inside it
+    pub fn guest_bytes(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        self.mem.get(offset..offset.checked_add(len)?)
+    }
+
+    /// Record that the guest finished with `status` (the first one sticks)
+    pub fn finish(&self, status: i32) {
+        if !self.exited.load(Ordering::Acquire) {
+            self.status.store(status, Ordering::Relaxed);
+            self.exited.store(true, Ordering::Release);
+        }
+    }
+
+    /// The guest's exit status, if it has finished
+    /// Picks up an exit the guest posted through GUEST_EXIT since last asked.
+    fn exit_status(&self) -> Option<i32> {
+        use aether_abi::mmio::{GUEST_EXIT, GUEST_EXIT_STATUS};
+        
+        if !self.exited.load(Ordering::Acquire) {
+            unsafe {
+                let base = self.mem.as_ptr();
+                if (base.add(GUEST_EXIT) as *const u32).read_volatile() != 0 {
+                    self.finish((base.add(GUEST_EXIT_STATUS) as *const i32).read_volatile());
+                }
+            }
+        }
+        self.exited.load(Ordering::Acquire).then(|| self.status.load(Ordering::Relaxed))
+    }
 }
 
 impl Backend for UefiBackend {
@@ -63,33 +150,174 @@ impl Backend for UefiBackend {
         "UEFI Bare Metal (No Virtualization)"
     }
 
+    /// In `Scheduled` mode execution happens via context switching from the
+    /// timer, so this returns `Yield` until the guest has exited.
+    ///
+    /// In `Stepped` mode this runs the guest until the next exit event:
+    /// - `Yield`: the timer tick preempted the guest
+    /// - `Exited(status)`: the guest posted an exit through GUEST_EXIT, or
+    ///   its entry point returned (status 0); further steps don't run it
+    ///
+    /// There is no virtualization here, so guest MMIO and port accesses are
+    /// plain memory/IO operations and never produce `Mmio`, `Io`, `Halt`
+    /// or `Fault`.
     fn step(&self) -> ExitReason {
-        // In Multi-Unikernel mode, 'step' is not used for execution.
-        // Execution happens via Context Switching.
-        ExitReason::Yield
+        if let Some(status) = self.exit_status() {
+            return ExitReason::Exited(status);
+        }
+        if self.mode != ExecMode::Stepped {
+            return ExitReason::Yield;
+        }
+        
+        let prev = STEPPING.swap(self as *const _ as *mut _, Ordering::AcqRel);
+        if !prev.is_null() {
+            // Nested stepping would clobber the other guest's host_sp
+            STEPPING.store(prev, Ordering::Release);
+            return ExitReason::Unknown;
+        }
+        
+        let host_irqs = x86_64::instructions::interrupts::are_enabled();
+        unsafe {
+            crate::multitasking::switch_context(
+                self.guest_sp.load(Ordering::Acquire),
+                self.host_sp.as_ptr(),
+            );
+        }
+        // A preempted guest hands back from inside the timer interrupt,
+        // with IF clear and no iretq ahead of us to set it again
+        if host_irqs {
+            x86_64::instructions::interrupts::enable();
+        }
+        
+        STEPPING.store(ptr::null_mut(), Ordering::Release);
+        
+        // A guest waiting on the doorbell spins until preempted
+        self.service();
+        if self.exit_code.load(Ordering::Acquire) == EXIT_RETURNED {
+            self.finish(0);
+        }
+        match self.exit_status() {
+            Some(status) => ExitReason::Exited(status),
+            None => ExitReason::Yield,
+        }
     }
 
-    unsafe fn get_framebuffer(&self, _width: usize, _height: usize) -> &[u32] {
-        // Return dummy or actual FB if we had access to GOP here.
-        // Without storing GOP in the struct, we can't.
-        &[]
+    unsafe fn get_framebuffer(&self, width: usize, height: usize) -> &[u32] {
+        // The guest's shadow framebuffer lives at mem + FB_ADDR (32bpp)
+        let fb_addr = aether_abi::mmio::FB_ADDR;
+        if aether_abi::mmio::framebuffer_len(width, height).is_none() {
+            log::warn!("[Aether::UefiBackend] Framebuffer {}x{} exceeds the FB region", width, height);
+            return &[];
+        }
+        
+        let fb_ptr = self.mem.as_ptr().add(fb_addr) as *const u32;
+        core::slice::from_raw_parts(fb_ptr, width * height)
+    }
+
+    fn attach(&self, pid: ProcessId) {
+        // Register Framebuffer Bridge
+        // Guest writes to mem + FB_ADDR
+        // We tell video module that's where this guest's shadow buffer is.
+        let fb_ptr = unsafe { self.mem.as_ptr().add(aether_abi::mmio::FB_ADDR) };
+        crate::video::register_guest_buffer(pid, fb_ptr);
+    }
+
+    /// Answer a pending hypercall (see `crate::hypercall`)
+    fn service(&self) {
+        crate::hypercall::service(self);
+    }
+
+    fn has_exited(&self) -> bool {
+        self.exit_status().is_some()
+    }
+
+    /// Take the guest off screen; `mem` and the step stack are freed when
+    /// the scheduler drops its process
+    fn shutdown(&self, pid: ProcessId) {
+        crate::video::unregister_guest_buffer(pid);
+        log::info!("[Aether::UefiBackend] Guest {} shut down", pid);
     }
 
     fn inject_key(&self, c: char) {
-        // Write to MMIO buffer
-        // KEYBOARD_STATUS = 0x80000
-        // KEYBOARD_DATA = 0x80004
-        // Use aether_abi constants
+        use aether_abi::mmio::{
+            KEYBOARD_STATUS, KEYBOARD_DATA, KEYBOARD_HEAD, KEYBOARD_TAIL, KEYBOARD_RING,
+            KEYBOARD_RING_LEN, KEYBOARD_STATUS_READY, KEYBOARD_STATUS_OVERFLOW,
+        };
+        use core::sync::atomic::AtomicU32;
+        
+        // Producer side of the guest keyboard ring.
+        // The guest owns TAIL, we own HEAD.
         unsafe {
-            let status_ptr = self.mem.as_ptr().add(aether_abi::mmio::KEYBOARD_STATUS as usize) as *mut u32;
-            let data_ptr = self.mem.as_ptr().add(aether_abi::mmio::KEYBOARD_DATA as usize) as *mut u32;
+            let base = self.mem.as_ptr() as *mut u8;
+            // The guest clears bits in STATUS too, so only ever or them in
+            let status = AtomicU32::from_ptr(base.add(KEYBOARD_STATUS) as *mut u32);
+            let data_ptr = base.add(KEYBOARD_DATA) as *mut u32;
+            let head_ptr = base.add(KEYBOARD_HEAD) as *mut u32;
+            let tail_ptr = base.add(KEYBOARD_TAIL) as *const u32;
+            let ring_ptr = base.add(KEYBOARD_RING) as *mut u32;
             
-            // Only write if buffer is empty (Status == 0) to avoid overwriting?
-            // Actually, we are just a simple buffer, overwriting is fine for now.
-            // But real hardware has a buffer.
+            let head = head_ptr.read_volatile();
+            let tail = tail_ptr.read_volatile();
+            let used = head.wrapping_sub(tail) as usize;
             
+            if used >= KEYBOARD_RING_LEN {
+                // Guest isn't keeping up; drop the key rather than overwrite unread input
+                status.fetch_or(KEYBOARD_STATUS_OVERFLOW, Ordering::AcqRel);
+                return;
+            }
+            
+            ring_ptr.add(head as usize % KEYBOARD_RING_LEN).write_volatile(c as u32);
             data_ptr.write_volatile(c as u32);
-            status_ptr.write_volatile(1); // Set Status = Ready
+            
+            // Entry must be visible before the guest sees the new HEAD
+            core::sync::atomic::fence(Ordering::Release);
+            head_ptr.write_volatile(head.wrapping_add(1));
+            
+            status.fetch_or(KEYBOARD_STATUS_READY, Ordering::AcqRel);
         }
     }
 }
+
+/// Switch from the running stepped guest back into `step`.
+/// Does nothing unless we're actually on a stepped guest's stack.
+fn exit_to_host(code: u8) {
+    let backend = STEPPING.load(Ordering::Acquire);
+    if backend.is_null() {
+        return;
+    }
+    let backend = unsafe { &*backend };
+    if !backend.on_guest.swap(false, Ordering::AcqRel) {
+        return;
+    }
+    
+    backend.exit_code.store(code, Ordering::Release);
+    unsafe {
+        crate::multitasking::switch_context(
+            backend.host_sp.load(Ordering::Acquire),
+            backend.guest_sp.as_ptr(),
+        );
+    }
+    
+    // Resumed by the next step()
+    backend.on_guest.store(true, Ordering::Release);
+}
+
+/// Called from the timer interrupt (after EOI): end the current step with `Yield`
+/// `step` resumes with IF clear and restores the host's own setting.
+pub fn preempt_stepped_guest() {
+    exit_to_host(EXIT_YIELD);
+}
+
+/// First code run on a stepped guest's stack (via init_stack's trampoline)
+extern "C" fn guest_entry(entry_point: usize) -> ! {
+    let backend = STEPPING.load(Ordering::Acquire);
+    unsafe { (*backend).on_guest.store(true, Ordering::Release); }
+    
+    let guest: extern "C" fn() = unsafe { core::mem::transmute(entry_point) };
+    guest();
+    
+    // Guest returned: it exited with status 0 (step won't resume it)
+    loop {
+        exit_to_host(EXIT_RETURNED);
+    }
+}
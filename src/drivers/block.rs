@@ -1,2 +1,83 @@
-// Block Device Driver Stub
+//! Block Devices
+//!
+//! `BlockDevice` is what filesystems read their image through. There is no
+//! disk controller driver yet; `RamDisk` serves an image held in memory
+//! (an initrd or a file read from another filesystem).
+
+use alloc::vec::Vec;
+use spin::RwLock;
+
+/// Bytes per sector, the unit of every `BlockDevice` transfer
+pub const SECTOR_SIZE: usize = 512;
+
+/// Random-access storage addressed in `SECTOR_SIZE` sectors
+pub trait BlockDevice: Send + Sync {
+    /// Number of sectors on the device
+    fn sector_count(&self) -> u64;
+
+    /// Read `buf.len() / SECTOR_SIZE` sectors starting at `sector`
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), &'static str>;
+
+    /// Write `buf.len() / SECTOR_SIZE` sectors starting at `sector`
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), &'static str>;
+
+    /// Read an arbitrary byte range, going through whole sectors
+    fn read_bytes(&self, offset: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let skip = (pos % SECTOR_SIZE as u64) as usize;
+            let len = (SECTOR_SIZE - skip).min(buf.len() - done);
+            self.read_sectors(pos / SECTOR_SIZE as u64, &mut sector)?;
+            buf[done..done + len].copy_from_slice(&sector[skip..skip + len]);
+            done += len;
+        }
+        Ok(())
+    }
+}
+
+/// A disk image in memory
+pub struct RamDisk {
+    data: RwLock<Vec<u8>>,
+}
+
+impl RamDisk {
+    /// Wrap `image`, padded with zeroes to a whole number of sectors
+    pub fn new(mut image: Vec<u8>) -> Self {
+        image.resize(image.len().next_multiple_of(SECTOR_SIZE), 0);
+        Self { data: RwLock::new(image) }
+    }
+
+    fn range(&self, sector: u64, len: usize) -> Result<core::ops::Range<usize>, &'static str> {
+        if !len.is_multiple_of(SECTOR_SIZE) {
+            return Err("transfer is not a whole number of sectors");
+        }
+        let start = usize::try_from(sector).ok().and_then(|s| s.checked_mul(SECTOR_SIZE));
+        let end = start.and_then(|s| s.checked_add(len));
+        match (start, end) {
+            (Some(start), Some(end)) if end <= self.data.read().len() => Ok(start..end),
+            _ => Err("sector out of range"),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn sector_count(&self) -> u64 {
+        (self.data.read().len() / SECTOR_SIZE) as u64
+    }
+
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        let range = self.range(sector, buf.len())?;
+        buf.copy_from_slice(&self.data.read()[range]);
+        Ok(())
+    }
+
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), &'static str> {
+        let range = self.range(sector, buf.len())?;
+        self.data.write()[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
 pub fn init() {}
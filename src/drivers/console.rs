@@ -1,2 +1,482 @@
-// Console Driver Stub
+//! Console/TTY Driver
+//!
+//! Keeps a character grid mirroring what user programs have drawn, and
+//! interprets the ANSI/VT100 escape sequences they write: cursor motion
+//! (CSI A/B/C/D/H), erase (CSI J/K) and SGR colours/attributes (CSI m).
+//! Raw bytes are forwarded to COM1 so a serial terminal shows the same
+//! screen; unknown sequences are consumed and never reach the grid.
+//!
+//! Rows scrolled off the top are kept in a scrollback ring which
+//! Shift+PageUp/PageDown pages through; new output snaps back to the bottom.
+//!
+//! Typed characters are queued as UTF-8 for stdin (fd 0) until read.
+
+use alloc::collections::VecDeque;
+use spin::Mutex;
+
+pub const COLS: usize = 80;
+pub const ROWS: usize = 25;
+/// Rows retained after scrolling off the top of the screen
+pub const SCROLLBACK: usize = 200;
+/// Typed bytes held for stdin; further keystrokes are dropped
+const INPUT_CAPACITY: usize = 4096;
+
+/// Parameters accepted per CSI sequence; extras are dropped
+const MAX_PARAMS: usize = 8;
+const TAB_WIDTH: usize = 8;
+
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+/// Colour and rendition of a cell (colours are the 16 ANSI indices)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Attr {
+    pub fg: u8,
+    pub bg: u8,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Attr {
+    pub const DEFAULT: Attr = Attr {
+        fg: DEFAULT_FG,
+        bg: DEFAULT_BG,
+        bold: false,
+        underline: false,
+        reverse: false,
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cell {
+    pub ch: u8,
+    pub attr: Attr,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell { ch: b' ', attr: Attr::DEFAULT };
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EscState {
+    Normal,
+    /// Saw ESC, waiting for the sequence introducer
+    Escape,
+    /// Inside ESC [ ... collecting parameters until a final byte
+    Csi,
+}
+
+pub struct Console {
+    cells: [[Cell; COLS]; ROWS],
+    /// Ring of rows that scrolled off; `hist_head` is the next slot to fill
+    history: [[Cell; COLS]; SCROLLBACK],
+    hist_head: usize,
+    hist_len: usize,
+    /// Rows the view is scrolled back from the live screen (0 = live)
+    view: usize,
+    row: usize,
+    col: usize,
+    attr: Attr,
+    state: EscState,
+    params: [u16; MAX_PARAMS],
+    nparams: usize,
+    /// Set by '?' and friends; such private-mode sequences are ignored
+    private: bool,
+}
+
+impl Console {
+    pub const fn new() -> Self {
+        Console {
+            cells: [[Cell::BLANK; COLS]; ROWS],
+            history: [[Cell::BLANK; COLS]; SCROLLBACK],
+            hist_head: 0,
+            hist_len: 0,
+            view: 0,
+            row: 0,
+            col: 0,
+            attr: Attr::DEFAULT,
+            state: EscState::Normal,
+            params: [0; MAX_PARAMS],
+            nparams: 0,
+            private: false,
+        }
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    pub fn attr(&self) -> Attr {
+        self.attr
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        self.cells[row][col]
+    }
+
+    /// Row `i` of what is currently on display, taking scrollback into account
+    pub fn visible_row(&self, i: usize) -> &[Cell; COLS] {
+        if i >= self.view {
+            return &self.cells[i - self.view];
+        }
+        // The view starts `view` rows into history, counting back from the newest
+        let back = self.view - i;
+        &self.history[(self.hist_head + SCROLLBACK - back) % SCROLLBACK]
+    }
+
+    pub fn view_offset(&self) -> usize {
+        self.view
+    }
+
+    /// Scroll the view back by `rows`; returns whether it moved
+    pub fn scroll_back(&mut self, rows: usize) -> bool {
+        let old = self.view;
+        self.view = self.view.saturating_add(rows).min(self.hist_len);
+        self.view != old
+    }
+
+    /// Scroll the view towards the live screen; returns whether it moved
+    pub fn scroll_forward(&mut self, rows: usize) -> bool {
+        let old = self.view;
+        self.view = self.view.saturating_sub(rows);
+        self.view != old
+    }
+
+    /// RIS: clear the screen and attributes, keeping scrollback
+    fn reset(&mut self) {
+        self.cells.iter_mut().for_each(|row| row.fill(Cell::BLANK));
+        self.row = 0;
+        self.col = 0;
+        self.attr = Attr::DEFAULT;
+    }
+
+    /// Feed one byte through the escape-sequence state machine
+    pub fn write_byte(&mut self, byte: u8) {
+        match self.state {
+            EscState::Normal => self.control_or_print(byte),
+            EscState::Escape => match byte {
+                b'[' => {
+                    self.params = [0; MAX_PARAMS];
+                    self.nparams = 0;
+                    self.private = false;
+                    self.state = EscState::Csi;
+                }
+                b'c' => {
+                    self.reset();
+                    self.state = EscState::Normal;
+                }
+                // Intermediate bytes (e.g. charset selection ESC ( B) take one more byte
+                0x20..=0x2F => {}
+                _ => self.state = EscState::Normal,
+            },
+            EscState::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.nparams == 0 {
+                        self.nparams = 1;
+                    }
+                    if let Some(p) = self.params.get_mut(self.nparams - 1) {
+                        *p = p.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+                }
+                b';' => {
+                    if self.nparams == 0 {
+                        self.nparams = 1;
+                    }
+                    self.nparams += 1;
+                }
+                b'<'..=b'?' => self.private = true,
+                // Intermediate bytes
+                0x20..=0x2F => {}
+                0x40..=0x7E => {
+                    self.state = EscState::Normal;
+                    if !self.private {
+                        self.csi_dispatch(byte);
+                    }
+                }
+                // CAN/SUB abort the sequence; anything else is malformed
+                _ => self.state = EscState::Normal,
+            },
+        }
+    }
+
+    fn control_or_print(&mut self, byte: u8) {
+        match byte {
+            0x1B => self.state = EscState::Escape,
+            b'\n' => {
+                // Cooked output: LF implies CR (ONLCR)
+                self.col = 0;
+                self.line_feed();
+            }
+            b'\r' => self.col = 0,
+            0x08 => self.col = self.col.saturating_sub(1),
+            b'\t' => self.col = ((self.col / TAB_WIDTH + 1) * TAB_WIDTH).min(COLS - 1),
+            0x20..=0x7E => self.put(byte),
+            // BEL and other control characters have no visible effect
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, ch: u8) {
+        if self.col >= COLS {
+            self.col = 0;
+            self.line_feed();
+        }
+        self.cells[self.row][self.col] = Cell { ch, attr: self.attr };
+        self.col += 1;
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < ROWS {
+            self.row += 1;
+        } else {
+            self.scroll_up();
+        }
+    }
+
+    fn scroll_up(&mut self) {
+        self.history[self.hist_head] = self.cells[0];
+        self.hist_head = (self.hist_head + 1) % SCROLLBACK;
+        self.hist_len = (self.hist_len + 1).min(SCROLLBACK);
+        self.cells.copy_within(1.., 0);
+        self.cells[ROWS - 1] = [self.blank(); COLS];
+    }
+
+    /// Erased cells keep the current background, as on a VT100
+    fn blank(&self) -> Cell {
+        Cell { ch: b' ', attr: Attr { bg: self.attr.bg, ..Attr::DEFAULT } }
+    }
+
+    /// Parameter `i`, with 0/missing replaced by `default`
+    fn param(&self, i: usize, default: u16) -> u16 {
+        match self.params.get(i) {
+            Some(&p) if i < self.nparams && p != 0 => p,
+            _ => default,
+        }
+    }
+
+    fn csi_dispatch(&mut self, cmd: u8) {
+        let n = self.param(0, 1) as usize;
+        match cmd {
+            b'A' => self.row = self.row.saturating_sub(n),
+            b'B' => self.row = (self.row + n).min(ROWS - 1),
+            b'C' => self.col = (self.col + n).min(COLS - 1),
+            b'D' => self.col = self.col.min(COLS - 1).saturating_sub(n),
+            b'H' | b'f' => {
+                self.row = (self.param(0, 1) as usize - 1).min(ROWS - 1);
+                self.col = (self.param(1, 1) as usize - 1).min(COLS - 1);
+            }
+            b'J' => self.erase_display(self.param(0, 0)),
+            b'K' => self.erase_line(self.param(0, 0)),
+            b'm' => self.sgr(),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for row in self.row + 1..ROWS {
+                    self.cells[row] = [blank; COLS];
+                }
+            }
+            1 => {
+                self.erase_line(1);
+                for row in 0..self.row {
+                    self.cells[row] = [blank; COLS];
+                }
+            }
+            2 => self.cells.iter_mut().for_each(|row| row.fill(blank)),
+            3 => {
+                self.cells.iter_mut().for_each(|row| row.fill(blank));
+                self.hist_len = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let col = self.col.min(COLS - 1);
+        let line = &mut self.cells[self.row];
+        match mode {
+            0 => line[col..].fill(blank),
+            1 => line[..=col].fill(blank),
+            2 => line.fill(blank),
+            _ => {}
+        }
+    }
+
+    fn sgr(&mut self) {
+        // "ESC [ m" is SGR 0
+        let count = self.nparams.clamp(1, MAX_PARAMS);
+        for i in 0..count {
+            match self.params[i] {
+                0 => self.attr = Attr::DEFAULT,
+                1 => self.attr.bold = true,
+                4 => self.attr.underline = true,
+                7 => self.attr.reverse = true,
+                22 => self.attr.bold = false,
+                24 => self.attr.underline = false,
+                27 => self.attr.reverse = false,
+                p @ 30..=37 => self.attr.fg = (p - 30) as u8,
+                39 => self.attr.fg = DEFAULT_FG,
+                p @ 40..=47 => self.attr.bg = (p - 40) as u8,
+                49 => self.attr.bg = DEFAULT_BG,
+                p @ 90..=97 => self.attr.fg = (p - 90 + 8) as u8,
+                p @ 100..=107 => self.attr.bg = (p - 100 + 8) as u8,
+                _ => {}
+            }
+        }
+    }
+}
+
+pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+
 pub fn init() {}
+
+/// Write path for stdout/stderr: update the grid and mirror to serial
+pub fn write(bytes: &[u8]) {
+    let mut console = CONSOLE.lock();
+    if console.scroll_forward(usize::MAX) {
+        render(&console);
+    }
+    for &byte in bytes {
+        console.write_byte(byte);
+        emit(byte);
+    }
+}
+
+/// Keystrokes waiting to be read from stdin
+static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// Queue a typed character for stdin (called from the keyboard interrupt,
+/// so never spin on the lock; readers hold it with interrupts off)
+pub fn push_input(key: char) {
+    let Some(mut input) = INPUT.try_lock() else {
+        return;
+    };
+    let mut utf8 = [0u8; 4];
+    let bytes = key.encode_utf8(&mut utf8).as_bytes();
+    if input.len() + bytes.len() <= INPUT_CAPACITY {
+        input.extend(bytes.iter().copied());
+    }
+}
+
+/// Whether a stdin read would return something
+pub fn input_pending() -> bool {
+    without_interrupts(|| !INPUT.lock().is_empty())
+}
+
+/// Move queued keystrokes into `buf`, returning the bytes filled
+pub fn read_input(buf: &mut [u8]) -> usize {
+    without_interrupts(|| {
+        let mut input = INPUT.lock();
+        let len = buf.len().min(input.len());
+        for (dst, src) in buf.iter_mut().zip(input.drain(..len)) {
+            *dst = src;
+        }
+        len
+    })
+}
+
+fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(target_arch = "x86_64")]
+    return x86_64::instructions::interrupts::without_interrupts(f);
+    #[cfg(not(target_arch = "x86_64"))]
+    f()
+}
+
+/// Shift+PageUp: page back through scrollback
+pub fn page_up() {
+    let mut console = CONSOLE.lock();
+    if console.scroll_back(ROWS - 1) {
+        render(&console);
+    }
+}
+
+/// Shift+PageDown: page towards the live screen
+pub fn page_down() {
+    let mut console = CONSOLE.lock();
+    if console.scroll_forward(ROWS - 1) {
+        render(&console);
+    }
+}
+
+fn emit(byte: u8) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if byte == b'\n' {
+            super::serial::write_byte(b'\r');
+        }
+        super::serial::write_byte(byte);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = byte;
+}
+
+fn emit_str(s: &[u8]) {
+    s.iter().for_each(|&b| emit(b));
+}
+
+fn emit_num(mut n: usize) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    emit_str(&digits[i..]);
+}
+
+fn emit_sgr(attr: Attr) {
+    emit_str(b"\x1b[0");
+    if attr.bold {
+        emit_str(b";1");
+    }
+    if attr.underline {
+        emit_str(b";4");
+    }
+    if attr.reverse {
+        emit_str(b";7");
+    }
+    // Bright colours 8..15 use the 90/100 ranges
+    let (fg, bg) = (attr.fg as usize, attr.bg as usize);
+    emit(b';');
+    emit_num(if fg < 8 { 30 + fg } else { 90 + fg - 8 });
+    emit(b';');
+    emit_num(if bg < 8 { 40 + bg } else { 100 + bg - 8 });
+    emit(b'm');
+}
+
+/// Redraw the whole terminal from the grid (and scrollback, if viewing it)
+fn render(console: &Console) {
+    emit_str(b"\x1b[0m\x1b[2J");
+    for i in 0..ROWS {
+        emit_str(b"\x1b[");
+        emit_num(i + 1);
+        emit_str(b";1H");
+        let mut attr = None;
+        for cell in console.visible_row(i) {
+            if attr != Some(cell.attr) {
+                emit_sgr(cell.attr);
+                attr = Some(cell.attr);
+            }
+            emit(cell.ch);
+        }
+    }
+    // Put the terminal's cursor and pen back where the program left them
+    let (row, col) = console.cursor();
+    emit_str(b"\x1b[");
+    emit_num(row + 1);
+    emit(b';');
+    emit_num(col.min(COLS - 1) + 1);
+    emit(b'H');
+    emit_sgr(console.attr());
+}
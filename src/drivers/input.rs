@@ -0,0 +1,242 @@
+//! Input Event Device (/dev/input/event0)
+//!
+//! Keyboard presses and releases as Linux `input_event` records, the way
+//! evdev hands them to applications: an EV_KEY event carrying the KEY_*
+//! code (1 = press, 0 = release, 2 = autorepeat), then an EV_SYN report.
+//! The keyboard interrupt feeds the queue; reads block until it is
+//! nonempty. Each device has a single queue, shared by every open
+//! descriptor.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, StatFs, POLLIN};
+use crate::sched::wait::WaitQueue;
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const SYN_REPORT: u16 = 0;
+pub const SYN_DROPPED: u16 = 3;
+
+/// Size of `struct input_event` on a 64-bit kernel
+pub const EVENT_SIZE: usize = 24;
+
+/// Events buffered; on overflow the queue is emptied and SYN_DROPPED sent
+const QUEUE_CAPACITY: usize = 256;
+
+/// `struct input_event`
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct InputEvent {
+    pub tv_sec: u64,
+    pub tv_usec: u64,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    fn now(kind: u16, code: u16, value: i32) -> Self {
+        let ns = crate::sched::clock::uptime_ns();
+        Self {
+            tv_sec: ns / 1_000_000_000,
+            tv_usec: (ns % 1_000_000_000) / 1000,
+            kind,
+            code,
+            value,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; EVENT_SIZE] {
+        let mut out = [0u8; EVENT_SIZE];
+        out[0..8].copy_from_slice(&self.tv_sec.to_ne_bytes());
+        out[8..16].copy_from_slice(&self.tv_usec.to_ne_bytes());
+        out[16..18].copy_from_slice(&self.kind.to_ne_bytes());
+        out[18..20].copy_from_slice(&self.code.to_ne_bytes());
+        out[20..24].copy_from_slice(&self.value.to_ne_bytes());
+        out
+    }
+}
+
+/// One device's events, with the readers waiting on them
+pub struct EventQueue {
+    events: Mutex<VecDeque<InputEvent>>,
+    /// Readers waiting for events
+    readers: WaitQueue,
+    /// Some reader could not be woken from the interrupt; retried every tick
+    wake_pending: AtomicBool,
+    /// Keys currently held down, to tell autorepeat from a fresh press
+    keys_down: Mutex<[u64; 4]>,
+}
+
+impl EventQueue {
+    pub const fn new() -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            readers: WaitQueue::new(),
+            wake_pending: AtomicBool::new(false),
+            keys_down: Mutex::new([0; 4]),
+        }
+    }
+
+    /// Report a key press or release (called from the keyboard interrupt,
+    /// so never spin on a lock; readers touch the queue with interrupts off,
+    /// so on one CPU it is only busy if this interrupt nested in another)
+    pub fn report_key(&self, code: u16, pressed: bool) {
+        let value = match self.keys_down.try_lock() {
+            Some(mut down) => {
+                let (word, bit) = (code as usize / 64 % 4, 1u64 << (code % 64));
+                let was_down = down[word] & bit != 0;
+                if pressed {
+                    down[word] |= bit;
+                } else {
+                    down[word] &= !bit;
+                }
+                match (pressed, was_down) {
+                    (true, true) => 2,
+                    (true, false) => 1,
+                    (false, _) => 0,
+                }
+            }
+            None => pressed as i32,
+        };
+
+        match self.events.try_lock() {
+            Some(mut events) => {
+                if events.len() + 2 > QUEUE_CAPACITY {
+                    // Tell the reader it missed some, as evdev does
+                    events.clear();
+                    events.push_back(InputEvent::now(EV_SYN, SYN_DROPPED, 0));
+                }
+                events.push_back(InputEvent::now(EV_KEY, code, value));
+                events.push_back(InputEvent::now(EV_SYN, SYN_REPORT, 0));
+            }
+            None => return,
+        }
+        self.wake_readers();
+    }
+
+    /// Retry a wakeup the keyboard interrupt could not deliver (timer interrupt)
+    pub fn retry_wakeup(&self) {
+        if self.wake_pending.load(Ordering::Relaxed) {
+            self.wake_readers();
+        }
+    }
+
+    fn wake_readers(&self) {
+        self.wake_pending.store(!self.readers.try_wake_all(), Ordering::Relaxed);
+    }
+
+    /// Move whole events into `buf`, returning the bytes filled
+    fn drain_into(&self, buf: &mut [u8]) -> usize {
+        let mut events = self.events.lock();
+        let mut len = 0;
+        for chunk in buf.chunks_exact_mut(EVENT_SIZE) {
+            match events.pop_front() {
+                Some(event) => chunk.copy_from_slice(&event.to_bytes()),
+                None => break,
+            }
+            len += EVENT_SIZE;
+        }
+        len
+    }
+
+    /// Whether a read would return something
+    fn pending(&self) -> bool {
+        without_keyboard(|| !self.events.lock().is_empty())
+    }
+}
+
+/// The keyboard's queue, behind /dev/input/event0
+static EVENT0: EventQueue = EventQueue::new();
+
+/// Report a keyboard key press or release (keyboard interrupt)
+pub fn report_key(code: u16, pressed: bool) {
+    EVENT0.report_key(code, pressed);
+}
+
+/// Retry a keyboard wakeup that could not be delivered (timer interrupt)
+pub fn retry_wakeup() {
+    EVENT0.retry_wakeup();
+}
+
+/// An event device node, reading from `queue`
+pub struct EventDevice {
+    queue: &'static EventQueue,
+}
+
+impl EventDevice {
+    pub fn new(queue: &'static EventQueue) -> Self {
+        Self { queue }
+    }
+}
+
+impl Inode for EventDevice {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        self.read(offset, buf, true).unwrap_or(0)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> usize {
+        0
+    }
+
+    /// Whole events only; blocks while the queue is empty unless `nonblock`
+    fn read(&self, _offset: u64, buf: &mut [u8], nonblock: bool) -> Result<usize, FsError> {
+        if buf.len() < EVENT_SIZE {
+            return Err(FsError::InvalidInput);
+        }
+        loop {
+            let len = without_keyboard(|| self.queue.drain_into(buf));
+            if len > 0 {
+                return Ok(len);
+            }
+            if nonblock {
+                return Err(FsError::WouldBlock);
+            }
+            self.queue.readers.wait_until(|| self.queue.pending());
+        }
+    }
+
+    /// Injecting events is not supported
+    fn write(&self, _offset: u64, _buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        Err(FsError::InvalidInput)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode(0o660),
+            file_type: FileType::Device,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn readiness(&self) -> u16 {
+        if self.queue.pending() { POLLIN } else { 0 }
+    }
+
+    /// Linux keeps /dev on a devtmpfs
+    fn statfs(&self) -> StatFs {
+        StatFs::pseudo(crate::fs::ramfs::TMPFS_MAGIC)
+    }
+}
+
+/// Run `f` with interrupts off, so the keyboard interrupt never finds the
+/// queue locked halfway through a read
+fn without_keyboard<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(target_arch = "x86_64")]
+    return x86_64::instructions::interrupts::without_interrupts(f);
+    #[cfg(not(target_arch = "x86_64"))]
+    f()
+}
+
+/// Device node for `/dev/input/<name>`
+pub fn lookup(name: &str) -> Result<Arc<dyn Inode>, FsError> {
+    match name {
+        "event0" => Ok(Arc::new(EventDevice::new(&EVENT0))),
+        _ => Err(FsError::NotFound),
+    }
+}
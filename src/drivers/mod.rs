@@ -2,8 +2,19 @@
 
 pub mod block;   // Block device abstraction
 pub mod console; // Console/TTY driver
+pub mod input;   // /dev/input event device
+#[cfg(target_arch = "x86_64")]
+pub mod serial;  // COM1 UART
+#[cfg(target_arch = "x86_64")]
+pub mod pci;     // PCI configuration space
+#[cfg(target_arch = "x86_64")]
+pub mod virtio_net; // virtio-net NIC
 
 /// Initialize drivers
 pub fn init() {
     // TODO: Probe and initialize devices
+    #[cfg(target_arch = "x86_64")]
+    serial::init();
+    #[cfg(target_arch = "x86_64")]
+    virtio_net::init();
 }
@@ -0,0 +1,110 @@
+//! PCI Configuration Space
+//!
+//! Legacy port I/O access (0xCF8/0xCFC) and a brute-force scan of bus 0-255.
+//! Enough to find a device by vendor/device ID and read its BARs.
+
+use alloc::vec::Vec;
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// Command register bits
+const COMMAND_IO_SPACE: u16 = 1 << 0;
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+
+/// A function found by `scan`
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+}
+
+fn address(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    1 << 31
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xFC) as u32
+}
+
+pub fn read_u32(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    unsafe {
+        Port::<u32>::new(CONFIG_ADDRESS).write(address(bus, device, function, offset));
+        Port::<u32>::new(CONFIG_DATA).read()
+    }
+}
+
+pub fn write_u32(bus: u8, device: u8, function: u8, offset: u8, value: u32) {
+    unsafe {
+        Port::<u32>::new(CONFIG_ADDRESS).write(address(bus, device, function, offset));
+        Port::<u32>::new(CONFIG_DATA).write(value);
+    }
+}
+
+impl PciDevice {
+    fn read(&self, offset: u8) -> u32 {
+        read_u32(self.bus, self.device, self.function, offset)
+    }
+
+    fn write(&self, offset: u8, value: u32) {
+        write_u32(self.bus, self.device, self.function, offset, value)
+    }
+
+    /// Raw value of base address register `index` (0-5)
+    pub fn bar(&self, index: u8) -> u32 {
+        self.read(0x10 + index * 4)
+    }
+
+    /// Port base of an I/O space BAR, or None for memory BARs
+    pub fn io_bar(&self, index: u8) -> Option<u16> {
+        let bar = self.bar(index);
+        (bar & 1 == 1).then_some((bar & !0x3) as u16)
+    }
+
+    /// Let the device decode its I/O BARs and DMA into RAM
+    pub fn enable_io_and_dma(&self) {
+        let command = self.read(0x04);
+        self.write(0x04, command | (COMMAND_IO_SPACE | COMMAND_BUS_MASTER) as u32);
+    }
+}
+
+/// Every present function on every bus
+pub fn scan() -> Vec<PciDevice> {
+    let mut found = Vec::new();
+    for bus in 0..=255u8 {
+        for device in 0..32u8 {
+            for function in 0..8u8 {
+                let id = read_u32(bus, device, function, 0x00);
+                if id & 0xFFFF == 0xFFFF {
+                    // Function 0 absent means the whole slot is empty
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                found.push(PciDevice {
+                    bus,
+                    device,
+                    function,
+                    vendor_id: id as u16,
+                    device_id: (id >> 16) as u16,
+                });
+                // Only multi-function devices implement functions 1-7
+                let header_type = (read_u32(bus, device, 0, 0x0C) >> 16) as u8;
+                if function == 0 && header_type & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// First function matching `vendor_id:device_id`
+pub fn find(vendor_id: u16, device_id: u16) -> Option<PciDevice> {
+    scan().into_iter().find(|d| d.vendor_id == vendor_id && d.device_id == device_id)
+}
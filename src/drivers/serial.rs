@@ -0,0 +1,49 @@
+//! 16550 UART (COM1) Driver
+//!
+//! Polled, lock-free output used by the panic path, so messages survive
+//! even if the logger or UEFI console is wedged.
+
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+
+/// Program COM1 for 38400 baud, 8N1, FIFO enabled
+pub fn init() {
+    unsafe {
+        Port::<u8>::new(COM1 + 1).write(0x00); // Disable interrupts
+        Port::<u8>::new(COM1 + 3).write(0x80); // Enable DLAB
+        Port::<u8>::new(COM1).write(0x03);     // Divisor lo (38400 baud)
+        Port::<u8>::new(COM1 + 1).write(0x00); // Divisor hi
+        Port::<u8>::new(COM1 + 3).write(0x03); // 8 bits, no parity, one stop bit
+        Port::<u8>::new(COM1 + 2).write(0xC7); // Enable FIFO, clear, 14-byte threshold
+        Port::<u8>::new(COM1 + 4).write(0x0B); // RTS/DSR set
+    }
+}
+
+pub fn write_byte(byte: u8) {
+    let mut line_status = Port::<u8>::new(COM1 + 5);
+    let mut data = Port::<u8>::new(COM1);
+    unsafe {
+        // Wait for the transmit holding register to empty
+        while line_status.read() & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+        data.write(byte);
+    }
+}
+
+/// Zero-sized writer; every instance talks to the same port
+pub struct SerialWriter;
+
+impl fmt::Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                write_byte(b'\r');
+            }
+            write_byte(byte);
+        }
+        Ok(())
+    }
+}
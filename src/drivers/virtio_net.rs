@@ -0,0 +1,295 @@
+//! virtio-net (legacy PCI transport)
+//!
+//! Drives QEMU's `-device virtio-net-pci` through its I/O port BAR with one
+//! receive and one transmit virtqueue. Each descriptor owns a fixed
+//! `BUF_SIZE` buffer holding the 10-byte virtio_net_hdr and the frame, so
+//! nothing is allocated per packet. Completion is polled; no interrupts.
+//!
+//! Only VIRTIO_NET_F_MAC and VIRTIO_F_ANY_LAYOUT are negotiated. Leaving
+//! out MRG_RXBUF keeps the short header and one buffer per received frame.
+//!
+//! RAM is identity-mapped, so heap addresses double as DMA addresses.
+
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::sync::atomic::{fence, Ordering};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use crate::drivers::pci;
+use crate::mm::PAGE_SIZE;
+use crate::net::EthernetDevice;
+
+const VENDOR_VIRTIO: u16 = 0x1AF4;
+/// Transitional virtio-net, which still has the legacy I/O interface
+const DEVICE_NET_LEGACY: u16 = 0x1000;
+
+// Legacy register offsets from BAR0
+const REG_DEVICE_FEATURES: u16 = 0x00;
+const REG_GUEST_FEATURES: u16 = 0x04;
+const REG_QUEUE_PFN: u16 = 0x08;
+const REG_QUEUE_SIZE: u16 = 0x0C;
+const REG_QUEUE_SELECT: u16 = 0x0E;
+const REG_QUEUE_NOTIFY: u16 = 0x10;
+const REG_STATUS: u16 = 0x12;
+/// Device-specific config: the MAC address comes first
+const REG_CONFIG: u16 = 0x14;
+
+// Device status bits
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FAILED: u8 = 0x80;
+
+const F_MAC: u32 = 1 << 5;
+const F_ANY_LAYOUT: u32 = 1 << 27;
+
+const QUEUE_RX: u16 = 0;
+const QUEUE_TX: u16 = 1;
+
+const DESC_F_WRITE: u16 = 2;
+
+/// struct virtio_net_hdr without num_buffers (no MRG_RXBUF)
+const NET_HDR_LEN: usize = 10;
+/// Header plus the largest Ethernet frame, rounded up
+const BUF_SIZE: usize = 2048;
+pub const MAX_FRAME: usize = 1514;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// A split virtqueue laid out as the legacy interface expects: descriptor
+/// table and avail ring, then the used ring on the next page boundary
+struct Virtqueue {
+    index: u16,
+    size: u16,
+    ring: *mut u8,
+    ring_layout: Layout,
+    used_offset: usize,
+    /// `size` buffers of BUF_SIZE; descriptor i always points at buffer i
+    buffers: *mut u8,
+    buffers_layout: Layout,
+    /// Descriptors not currently owned by the device
+    free: Vec<u16>,
+    last_used: u16,
+}
+
+impl Virtqueue {
+    fn new(io_base: u16, index: u16) -> Result<Self, &'static str> {
+        unsafe { Port::<u16>::new(io_base + REG_QUEUE_SELECT).write(index) };
+        let size = unsafe { Port::<u16>::new(io_base + REG_QUEUE_SIZE).read() };
+        if size == 0 {
+            return Err("virtqueue not available");
+        }
+        let n = size as usize;
+        let avail_end = 16 * n + 6 + 2 * n;
+        let used_offset = avail_end.next_multiple_of(PAGE_SIZE);
+        let total = used_offset + (6 + 8 * n).next_multiple_of(PAGE_SIZE);
+
+        let ring_layout = Layout::from_size_align(total, PAGE_SIZE).map_err(|_| "bad ring layout")?;
+        let buffers_layout = Layout::from_size_align(BUF_SIZE * n, PAGE_SIZE).map_err(|_| "bad buffer layout")?;
+        let ring = unsafe { alloc::alloc::alloc_zeroed(ring_layout) };
+        let buffers = unsafe { alloc::alloc::alloc_zeroed(buffers_layout) };
+        if ring.is_null() || buffers.is_null() {
+            return Err("out of memory for virtqueue");
+        }
+        let queue = Self {
+            index,
+            size,
+            ring,
+            ring_layout,
+            used_offset,
+            buffers,
+            buffers_layout,
+            free: (0..size).rev().collect(),
+            last_used: 0,
+        };
+        for i in 0..size {
+            let desc = queue.descriptor(i);
+            unsafe {
+                (*desc).addr = queue.buffer(i) as u64;
+                (*desc).len = BUF_SIZE as u32;
+            }
+        }
+        unsafe { Port::<u32>::new(io_base + REG_QUEUE_PFN).write((ring as usize / PAGE_SIZE) as u32) };
+        Ok(queue)
+    }
+
+    fn descriptor(&self, i: u16) -> *mut Descriptor {
+        unsafe { (self.ring as *mut Descriptor).add(i as usize) }
+    }
+
+    fn buffer(&self, i: u16) -> *mut u8 {
+        unsafe { self.buffers.add(i as usize * BUF_SIZE) }
+    }
+
+    fn avail(&self) -> *mut u16 {
+        unsafe { self.ring.add(16 * self.size as usize) as *mut u16 }
+    }
+
+    fn used(&self) -> *mut u8 {
+        unsafe { self.ring.add(self.used_offset) }
+    }
+
+    /// Hand descriptor `i`, `len` bytes long, to the device
+    fn push(&mut self, i: u16, len: usize, flags: u16) {
+        unsafe {
+            let desc = self.descriptor(i);
+            (*desc).len = len as u32;
+            (*desc).flags = flags;
+            let avail = self.avail();
+            let idx = avail.add(1).read_volatile();
+            avail.add(2 + (idx % self.size) as usize).write_volatile(i);
+            // The ring entry must be visible before the index that publishes it
+            fence(Ordering::SeqCst);
+            avail.add(1).write_volatile(idx.wrapping_add(1));
+        }
+    }
+
+    /// Next completed (descriptor, length) from the used ring
+    fn pop_used(&mut self) -> Option<(u16, usize)> {
+        let used = self.used();
+        let idx = unsafe { (used.add(2) as *const u16).read_volatile() };
+        if idx == self.last_used {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let elem = unsafe { used.add(4 + 8 * (self.last_used % self.size) as usize) as *const u32 };
+        let (id, len) = unsafe { (elem.read_volatile(), elem.add(1).read_volatile()) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some((id as u16, len as usize))
+    }
+}
+
+impl Drop for Virtqueue {
+    fn drop(&mut self) {
+        unsafe {
+            alloc::alloc::dealloc(self.ring, self.ring_layout);
+            alloc::alloc::dealloc(self.buffers, self.buffers_layout);
+        }
+    }
+}
+
+pub struct VirtioNet {
+    io_base: u16,
+    mac: [u8; 6],
+    rx: Virtqueue,
+    tx: Virtqueue,
+}
+
+// Queue memory is only touched through the owning Mutex
+unsafe impl Send for VirtioNet {}
+
+impl VirtioNet {
+    /// Reset and bring up the device behind `io_base`
+    fn new(io_base: u16) -> Result<Self, &'static str> {
+        let mut status = Port::<u8>::new(io_base + REG_STATUS);
+        unsafe {
+            status.write(0);
+            status.write(STATUS_ACKNOWLEDGE);
+            status.write(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+            let offered = Port::<u32>::new(io_base + REG_DEVICE_FEATURES).read();
+            if offered & F_MAC == 0 {
+                status.write(STATUS_FAILED);
+                return Err("device has no MAC address");
+            }
+            Port::<u32>::new(io_base + REG_GUEST_FEATURES).write(offered & (F_MAC | F_ANY_LAYOUT));
+        }
+
+        let mut mac = [0u8; 6];
+        for (i, byte) in mac.iter_mut().enumerate() {
+            *byte = unsafe { Port::<u8>::new(io_base + REG_CONFIG + i as u16).read() };
+        }
+
+        let queues = Virtqueue::new(io_base, QUEUE_RX).and_then(|rx| Ok((rx, Virtqueue::new(io_base, QUEUE_TX)?)));
+        let (rx, tx) = match queues {
+            Ok(q) => q,
+            Err(e) => {
+                unsafe { status.write(STATUS_FAILED) };
+                return Err(e);
+            }
+        };
+        let mut net = Self { io_base, mac, rx, tx };
+
+        // Give the device every receive buffer up front
+        while let Some(i) = net.rx.free.pop() {
+            net.rx.push(i, BUF_SIZE, DESC_F_WRITE);
+        }
+        unsafe { status.write(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK) };
+        net.notify(&net.rx);
+        Ok(net)
+    }
+
+    /// Tell the device a queue has new buffers
+    fn notify(&self, queue: &Virtqueue) {
+        unsafe { Port::<u16>::new(self.io_base + REG_QUEUE_NOTIFY).write(queue.index) };
+    }
+}
+
+impl EthernetDevice for VirtioNet {
+    fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), &'static str> {
+        if frame.len() > MAX_FRAME {
+            return Err("frame too large");
+        }
+        // Reclaim buffers the device has finished sending
+        while let Some((i, _)) = self.tx.pop_used() {
+            self.tx.free.push(i);
+        }
+        let i = self.tx.free.pop().ok_or("transmit queue full")?;
+        let buf = self.tx.buffer(i);
+        unsafe {
+            // Zeroed header: no checksum offload, no GSO
+            core::ptr::write_bytes(buf, 0, NET_HDR_LEN);
+            core::ptr::copy_nonoverlapping(frame.as_ptr(), buf.add(NET_HDR_LEN), frame.len());
+        }
+        self.tx.push(i, NET_HDR_LEN + frame.len(), 0);
+        self.notify(&self.tx);
+        Ok(())
+    }
+
+    fn recv_frame(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let (i, len) = self.rx.pop_used()?;
+        let frame_len = len.saturating_sub(NET_HDR_LEN).min(buf.len());
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.rx.buffer(i).add(NET_HDR_LEN), buf.as_mut_ptr(), frame_len);
+        }
+        // Recycle the buffer straight away
+        self.rx.push(i, BUF_SIZE, DESC_F_WRITE);
+        self.notify(&self.rx);
+        Some(frame_len)
+    }
+}
+
+/// The first virtio-net device, once `init` has found one
+pub static DEVICE: Mutex<Option<VirtioNet>> = Mutex::new(None);
+
+/// Probe PCI for a virtio-net device and bring it up
+pub fn init() {
+    let Some(pci_dev) = pci::find(VENDOR_VIRTIO, DEVICE_NET_LEGACY) else {
+        log::info!("[virtio-net] No device");
+        return;
+    };
+    let Some(io_base) = pci_dev.io_bar(0) else {
+        log::warn!("[virtio-net] BAR0 is not an I/O BAR");
+        return;
+    };
+    pci_dev.enable_io_and_dma();
+    match VirtioNet::new(io_base) {
+        Ok(net) => {
+            let [a, b, c, d, e, f] = net.mac;
+            log::info!("[virtio-net] {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} at I/O {:#x}",
+                       a, b, c, d, e, f, io_base);
+            *DEVICE.lock() = Some(net);
+        }
+        Err(e) => log::warn!("[virtio-net] Init failed: {}", e),
+    }
+}
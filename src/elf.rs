@@ -0,0 +1,371 @@
+//! ELF64 Parsing
+//!
+//! Typed views of an ELF image held in a byte slice: the file header,
+//! program headers, section headers, dynamic entries and symbols. Every
+//! read is checked against the slice, so the loader (syscall/elf.rs) and
+//! the dynamic linker (syscall/dynlink.rs) can hand it untrusted files.
+
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+/// ELF64 Header
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Header {
+    pub e_ident: [u8; 16],      // ELF identification
+    pub e_type: u16,            // Object file type
+    pub e_machine: u16,         // Machine type
+    pub e_version: u32,         // Object file version
+    pub e_entry: u64,           // Entry point address
+    pub e_phoff: u64,           // Program header offset
+    pub e_shoff: u64,           // Section header offset
+    pub e_flags: u32,           // Processor-specific flags
+    pub e_ehsize: u16,          // ELF header size
+    pub e_phentsize: u16,       // Size of program header entry
+    pub e_phnum: u16,           // Number of program header entries
+    pub e_shentsize: u16,       // Size of section header entry
+    pub e_shnum: u16,           // Number of section header entries
+    pub e_shstrndx: u16,        // Section name string table index
+}
+
+/// ELF64 Program Header
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Phdr {
+    pub p_type: u32,            // Segment type
+    pub p_flags: u32,           // Segment flags
+    pub p_offset: u64,          // Offset in file
+    pub p_vaddr: u64,           // Virtual address in memory
+    pub p_paddr: u64,           // Physical address (ignored)
+    pub p_filesz: u64,          // Size of segment in file
+    pub p_memsz: u64,           // Size of segment in memory
+    pub p_align: u64,           // Alignment
+}
+
+/// ELF64 Section Header
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Shdr {
+    pub sh_name: u32,           // Name offset in the section name table
+    pub sh_type: u32,           // Section type
+    pub sh_flags: u64,          // Section flags
+    pub sh_addr: u64,           // Virtual address in memory
+    pub sh_offset: u64,         // Offset in file
+    pub sh_size: u64,           // Size in bytes
+    pub sh_link: u32,           // Related section (a symbol table's strings)
+    pub sh_info: u32,           // Extra information
+    pub sh_addralign: u64,      // Alignment
+    pub sh_entsize: u64,        // Size of one entry, for tables
+}
+
+/// Dynamic section entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Dyn {
+    pub d_tag: i64,
+    pub d_val: u64,
+}
+
+/// Symbol table entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Sym {
+    pub st_name: u32,      // Symbol name offset in string table
+    pub st_info: u8,       // Symbol type and binding
+    pub st_other: u8,      // Reserved
+    pub st_shndx: u16,     // Section header index
+    pub st_value: u64,     // Symbol value (address)
+    pub st_size: u64,      // Symbol size
+}
+
+/// Relocation entry (with addend)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Elf64Rela {
+    pub r_offset: u64,     // Virtual address
+    pub r_info: u64,       // Relocation type and symbol index
+    pub r_addend: i64,     // Addend
+}
+
+impl Elf64Rela {
+    pub fn r_type(&self) -> u32 {
+        (self.r_info & 0xFFFFFFFF) as u32
+    }
+
+    pub fn r_sym(&self) -> usize {
+        (self.r_info >> 32) as usize
+    }
+}
+
+/// ELF structures made only of integers: any bytes are a valid value
+/// # Safety
+/// Implementors must be `repr(C)` with integer (or integer array) fields.
+pub unsafe trait Pod: Copy {}
+
+unsafe impl Pod for Elf64Header {}
+unsafe impl Pod for Elf64Phdr {}
+unsafe impl Pod for Elf64Shdr {}
+unsafe impl Pod for Elf64Dyn {}
+unsafe impl Pod for Elf64Sym {}
+unsafe impl Pod for Elf64Rela {}
+
+// ELF constants
+pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+pub const ELFCLASS64: u8 = 2;
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+// Segment types (p_type)
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_INTERP: u32 = 3;
+pub const PT_GNU_STACK: u32 = 0x6474e551;
+pub const PT_GNU_RELRO: u32 = 0x6474e552;
+
+// Segment permission flags (p_flags)
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+// Section types (sh_type)
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_DYNSYM: u32 = 11;
+
+/// Terminates the dynamic section
+pub const DT_NULL: i64 = 0;
+
+/// Read a `T` at byte `offset` of `data`, if it fits
+pub fn read<T: Pod>(data: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(size_of::<T>())?;
+    let bytes = data.get(offset..end)?;
+    // In bounds, any bit pattern is valid for T, and no alignment is assumed
+    Some(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+/// The NUL-terminated string at `offset` of a string table
+pub fn str_at(strtab: &[u8], offset: usize) -> Option<&str> {
+    let rest = strtab.get(offset..)?;
+    let len = rest.iter().position(|&b| b == 0)?;
+    core::str::from_utf8(&rest[..len]).ok()
+}
+
+/// `count` records of `T`, `stride` bytes apart, all inside the slice
+#[derive(Clone)]
+pub struct Table<'a, T> {
+    data: &'a [u8],
+    stride: usize,
+    count: usize,
+    next: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: Pod> Table<'a, T> {
+    /// The table at `offset`, or None if a record is too short or one
+    /// would run past the end of `data`
+    pub fn new(data: &'a [u8], offset: usize, stride: usize, count: usize) -> Option<Self> {
+        if count == 0 {
+            return Some(Self::packed(&[]));
+        }
+        if stride < size_of::<T>() {
+            return None;
+        }
+        let last = stride.checked_mul(count - 1)?.checked_add(size_of::<T>())?;
+        if offset.checked_add(last)? > data.len() {
+            return None;
+        }
+        Some(Self { data: &data[offset..], stride, count, next: 0, _marker: PhantomData })
+    }
+
+    /// As many whole records as `data` holds, packed
+    pub fn packed(data: &'a [u8]) -> Self {
+        let count = data.len() / size_of::<T>();
+        Self { data, stride: size_of::<T>(), count, next: 0, _marker: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Record `index`
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.count {
+            return None;
+        }
+        read(self.data, index * self.stride)
+    }
+}
+
+impl<T: Pod> Iterator for Table<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let item = self.get(self.next)?;
+        self.next += 1;
+        Some(item)
+    }
+}
+
+/// Dynamic entries up to (not including) DT_NULL
+pub struct DynamicEntries<'a>(Table<'a, Elf64Dyn>);
+
+impl<'a> DynamicEntries<'a> {
+    /// Entries packed from the start of `data` (a PT_DYNAMIC segment)
+    pub fn new(data: &'a [u8]) -> Self {
+        Self(Table::packed(data))
+    }
+}
+
+impl Iterator for DynamicEntries<'_> {
+    type Item = Elf64Dyn;
+
+    fn next(&mut self) -> Option<Elf64Dyn> {
+        match self.0.next() {
+            Some(d) if d.d_tag != DT_NULL => Some(d),
+            _ => {
+                self.0.next = self.0.count; // Nothing after DT_NULL counts
+                None
+            }
+        }
+    }
+}
+
+/// A symbol table and the string table its names point into
+pub struct SymbolTable<'a> {
+    pub symbols: Table<'a, Elf64Sym>,
+    pub strtab: &'a [u8],
+}
+
+impl<'a> SymbolTable<'a> {
+    /// Name of `sym`, if it has a valid one
+    pub fn name(&self, sym: &Elf64Sym) -> Option<&'a str> {
+        str_at(self.strtab, sym.st_name as usize)
+    }
+
+    /// Symbol `index` and its name ("" if unnamed or invalid)
+    pub fn get(&self, index: usize) -> Option<(&'a str, Elf64Sym)> {
+        let sym = self.symbols.get(index)?;
+        Some((self.name(&sym).unwrap_or(""), sym))
+    }
+
+    /// First defined symbol called `name`
+    pub fn lookup(&self, name: &str) -> Option<Elf64Sym> {
+        self.symbols.clone().find(|sym| sym.st_shndx != 0 && self.name(sym) == Some(name))
+    }
+}
+
+/// A validated ELF64 image
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    header: Elf64Header,
+}
+
+impl<'a> ElfFile<'a> {
+    /// Check the identification and that the header tables lie in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, &'static str> {
+        let header: Elf64Header = read(data, 0).ok_or("Data too small for ELF header")?;
+        if header.e_ident[0..4] != ELF_MAGIC {
+            return Err("Invalid ELF magic");
+        }
+        if header.e_ident[4] != ELFCLASS64 {
+            return Err("Not a 64-bit ELF");
+        }
+        let file = Self { data, header };
+        if header.e_phnum > 0 && (header.e_phentsize as usize) < size_of::<Elf64Phdr>() {
+            return Err("Program header entry too small");
+        }
+        file.try_program_headers().ok_or("Program header out of bounds")?;
+        if header.e_shnum > 0 && (header.e_shentsize as usize) < size_of::<Elf64Shdr>() {
+            return Err("Section header entry too small");
+        }
+        file.try_section_headers().ok_or("Section header out of bounds")?;
+        Ok(file)
+    }
+
+    pub fn header(&self) -> &Elf64Header {
+        &self.header
+    }
+
+    /// The whole image
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn try_program_headers(&self) -> Option<Table<'a, Elf64Phdr>> {
+        let h = &self.header;
+        Table::new(self.data, usize::try_from(h.e_phoff).ok()?, h.e_phentsize as usize, h.e_phnum as usize)
+    }
+
+    fn try_section_headers(&self) -> Option<Table<'a, Elf64Shdr>> {
+        let h = &self.header;
+        Table::new(self.data, usize::try_from(h.e_shoff).ok()?, h.e_shentsize as usize, h.e_shnum as usize)
+    }
+
+    pub fn program_headers(&self) -> Table<'a, Elf64Phdr> {
+        // Checked by parse
+        self.try_program_headers().unwrap_or(Table::packed(&[]))
+    }
+
+    pub fn section_headers(&self) -> Table<'a, Elf64Shdr> {
+        self.try_section_headers().unwrap_or(Table::packed(&[]))
+    }
+
+    /// File bytes backing a segment
+    pub fn segment_data(&self, phdr: &Elf64Phdr) -> Result<&'a [u8], &'static str> {
+        let end = phdr.p_offset.checked_add(phdr.p_filesz).ok_or("Segment file range overflows")?;
+        usize::try_from(phdr.p_offset)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .and_then(|(start, end)| self.data.get(start..end))
+            .ok_or("Segment extends past end of file")
+    }
+
+    /// File bytes of a section (empty for SHT_NOBITS)
+    pub fn section_data(&self, shdr: &Elf64Shdr) -> Result<&'a [u8], &'static str> {
+        if shdr.sh_type == SHT_NOBITS {
+            return Ok(&[]);
+        }
+        let end = shdr.sh_offset.checked_add(shdr.sh_size).ok_or("Section range overflows")?;
+        usize::try_from(shdr.sh_offset)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .and_then(|(start, end)| self.data.get(start..end))
+            .ok_or("Section extends past end of file")
+    }
+
+    /// Name of a section, from the section name string table
+    pub fn section_name(&self, shdr: &Elf64Shdr) -> Option<&'a str> {
+        let names = self.section_headers().get(self.header.e_shstrndx as usize)?;
+        str_at(self.section_data(&names).ok()?, shdr.sh_name as usize)
+    }
+
+    /// First section called `name`
+    pub fn section_by_name(&self, name: &str) -> Option<Elf64Shdr> {
+        self.section_headers().find(|s| self.section_name(s) == Some(name))
+    }
+
+    /// The symbols of a SHT_SYMTAB or SHT_DYNSYM section
+    pub fn symbol_table(&self, shdr: &Elf64Shdr) -> Result<SymbolTable<'a>, &'static str> {
+        if shdr.sh_type != SHT_SYMTAB && shdr.sh_type != SHT_DYNSYM {
+            return Err("Not a symbol table");
+        }
+        let strings = self.section_headers().get(shdr.sh_link as usize).ok_or("Bad string table index")?;
+        Ok(SymbolTable {
+            symbols: Table::packed(self.section_data(shdr)?),
+            strtab: self.section_data(&strings)?,
+        })
+    }
+
+    /// Entries of the PT_DYNAMIC segment, if there is one
+    pub fn dynamic(&self) -> Result<Option<DynamicEntries<'a>>, &'static str> {
+        match self.program_headers().find(|p| p.p_type == PT_DYNAMIC) {
+            Some(phdr) => Ok(Some(DynamicEntries::new(self.segment_data(&phdr)?))),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Device Nodes
+//!
+//! The fixed set of character devices reachable under /dev. There is no
+//! directory inode yet; `fs::open` hands "/dev/<name>" paths to `lookup`.
+
+use alloc::sync::Arc;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, StatFs};
+
+/// /dev/random and /dev/urandom
+/// Both read from the kernel generator and never block; writes are
+/// accepted and dropped (no entropy mixing yet).
+pub struct RandomDevice;
+
+impl Inode for RandomDevice {
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> usize {
+        crate::rand::fill_bytes(buf);
+        buf.len()
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> usize {
+        buf.len()
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ | FileMode::WRITE),
+            file_type: FileType::Device,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    /// Linux keeps /dev on a devtmpfs
+    fn statfs(&self) -> StatFs {
+        StatFs::pseudo(crate::fs::ramfs::TMPFS_MAGIC)
+    }
+}
+
+/// Device node called `name` (the part after "/dev/")
+pub fn lookup(name: &str) -> Result<Arc<dyn Inode>, FsError> {
+    match name {
+        "random" | "urandom" => Ok(Arc::new(RandomDevice)),
+        _ if name.starts_with("input/") => crate::drivers::input::lookup(&name["input/".len()..]),
+        _ => Err(FsError::NotFound),
+    }
+}
@@ -0,0 +1,138 @@
+//! epoll Instances
+//!
+//! An epoll fd is an inode holding an interest list keyed by fd. Entries
+//! keep weak references to the watched inodes, so once the last descriptor
+//! of a watched file is closed it silently drops out of the set, as on
+//! Linux. Readiness is level-triggered and comes from `Inode::readiness`.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use core::any::Any;
+use spin::Mutex;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, Inode, Metadata, POLLERR, POLLHUP, POLLIN};
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+
+pub const EPOLL_CLOEXEC: usize = 0o2000000;
+
+/// struct epoll_event (packed on x86_64)
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+struct Interest {
+    inode: Weak<dyn Inode>,
+    events: u32,
+    data: u64,
+}
+
+pub struct Epoll {
+    interest: Mutex<BTreeMap<usize, Interest>>,
+}
+
+impl Epoll {
+    pub fn new() -> Self {
+        Self { interest: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Watch `fd`; false if it is already in the set
+    pub fn add(&self, fd: usize, inode: &Arc<dyn Inode>, event: EpollEvent) -> bool {
+        let mut interest = self.interest.lock();
+        self.prune(&mut interest);
+        if interest.contains_key(&fd) {
+            return false;
+        }
+        interest.insert(fd, Interest {
+            inode: Arc::downgrade(inode),
+            events: event.events,
+            data: event.data,
+        });
+        true
+    }
+
+    /// Change the mask and data for `fd`; false if it is not in the set
+    pub fn modify(&self, fd: usize, event: EpollEvent) -> bool {
+        let mut interest = self.interest.lock();
+        self.prune(&mut interest);
+        match interest.get_mut(&fd) {
+            Some(entry) => {
+                entry.events = event.events;
+                entry.data = event.data;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop watching `fd`; false if it is not in the set
+    pub fn remove(&self, fd: usize) -> bool {
+        let mut interest = self.interest.lock();
+        self.prune(&mut interest);
+        interest.remove(&fd).is_some()
+    }
+
+    /// Fill `out` with ready events, returning how many were written
+    pub fn ready(&self, out: &mut [EpollEvent]) -> usize {
+        let mut interest = self.interest.lock();
+        self.prune(&mut interest);
+        let mut count = 0;
+        for entry in interest.values() {
+            if count == out.len() {
+                break;
+            }
+            let Some(inode) = entry.inode.upgrade() else { continue };
+            // EPOLLERR/EPOLLHUP are always reported
+            let events = inode.readiness() as u32 & (entry.events | (POLLERR | POLLHUP) as u32);
+            if events != 0 {
+                out[count] = EpollEvent { events, data: entry.data };
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Forget entries whose inode has been closed everywhere
+    fn prune(&self, interest: &mut BTreeMap<usize, Interest>) {
+        interest.retain(|_, entry| entry.inode.strong_count() > 0);
+    }
+}
+
+impl Inode for Epoll {
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ),
+            file_type: FileType::Device,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    /// Readable when any watched fd has an event pending
+    fn readiness(&self) -> u16 {
+        let mut probe = [EpollEvent { events: 0, data: 0 }];
+        if self.ready(&mut probe) != 0 {
+            POLLIN
+        } else {
+            0
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
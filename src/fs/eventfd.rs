@@ -0,0 +1,102 @@
+//! eventfd Counters
+//!
+//! An eventfd is a u64 counter used as a wakeup channel: writes add to
+//! it, reads drain it (or take one unit in semaphore mode). It is
+//! readable whenever the counter is nonzero.
+
+use core::any::Any;
+use spin::Mutex;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, POLLIN, POLLOUT};
+
+pub const EFD_SEMAPHORE: usize = 0o1;
+pub const EFD_NONBLOCK: usize = 0o4000;
+pub const EFD_CLOEXEC: usize = 0o2000000;
+
+/// Largest value the counter may hold
+const COUNTER_MAX: u64 = u64::MAX - 1;
+
+pub struct EventFd {
+    counter: Mutex<u64>,
+    semaphore: bool,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, flags: usize) -> Self {
+        Self {
+            counter: Mutex::new(initval),
+            semaphore: flags & EFD_SEMAPHORE != 0,
+        }
+    }
+}
+
+impl Inode for EventFd {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        self.read(offset, buf, true).unwrap_or(0)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> usize {
+        self.write(offset, buf, true).unwrap_or(0)
+    }
+
+    /// Tasks cannot block yet, so an empty counter is EAGAIN either way
+    fn read(&self, _offset: u64, buf: &mut [u8], _nonblock: bool) -> Result<usize, FsError> {
+        if buf.len() < 8 {
+            return Err(FsError::InvalidInput);
+        }
+        let mut counter = self.counter.lock();
+        if *counter == 0 {
+            return Err(FsError::WouldBlock);
+        }
+        let value = if self.semaphore { 1 } else { *counter };
+        *counter -= value;
+        buf[..8].copy_from_slice(&value.to_ne_bytes());
+        Ok(8)
+    }
+
+    fn write(&self, _offset: u64, buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        if buf.len() < 8 {
+            return Err(FsError::InvalidInput);
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&buf[..8]);
+        let value = u64::from_ne_bytes(bytes);
+        if value == u64::MAX {
+            return Err(FsError::InvalidInput);
+        }
+        let mut counter = self.counter.lock();
+        match counter.checked_add(value) {
+            Some(sum) if sum <= COUNTER_MAX => {
+                *counter = sum;
+                Ok(8)
+            }
+            _ => Err(FsError::WouldBlock),
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ | FileMode::WRITE),
+            file_type: FileType::Device,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn readiness(&self) -> u16 {
+        let counter = *self.counter.lock();
+        let mut events = 0;
+        if counter > 0 {
+            events |= POLLIN;
+        }
+        if counter < COUNTER_MAX {
+            events |= POLLOUT;
+        }
+        events
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -0,0 +1,381 @@
+//! ext2 (read-only)
+//!
+//! Reads revision 0 and 1 images with 1-4 KiB blocks: superblock, block
+//! group descriptors, inodes, direct and indirect block maps, and linear
+//! directories. Writes fail with EACCES-style `PermissionDenied`.
+//!
+//! Images using features we can't read (extents, 64-bit, compression, a
+//! journal needing recovery, ...) are refused at mount time.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::drivers::block::BlockDevice;
+use crate::fs::vfs::{FileMode, FileSystem, FileTimes, FileType, FsError, Inode, Metadata, StatFs};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT2_MAGIC: u16 = 0xEF53;
+/// Longest name a directory entry holds
+const NAME_MAX: u64 = 255;
+const ROOT_INO: u32 = 2;
+
+/// Incompatible features that don't change how we read: directory entry
+/// file types, and flexible block group placement
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+
+/// Direct block pointers in i_block; then single, double, triple indirect
+const DIRECT_BLOCKS: usize = 12;
+
+// i_mode file types
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+/// Geometry shared by every inode of a mounted image
+struct Volume {
+    device: Arc<dyn BlockDevice>,
+    block_size: usize,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    blocks_count: u32,
+    /// Free counts from the superblock; fixed, since we never write
+    free_blocks: u32,
+    free_inodes: u32,
+    inode_size: usize,
+    /// bg_inode_table of each block group
+    inode_tables: Vec<u32>,
+}
+
+/// A mounted ext2 image
+pub struct Ext2Fs {
+    root: Arc<Ext2Inode>,
+}
+
+impl Ext2Fs {
+    /// Mount the image on `device`
+    pub fn new(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        let volume = Volume::new(device)?;
+        let root = volume.load_inode(ROOT_INO)?;
+        if root.file_type() != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+        Ok(Self { root })
+    }
+}
+
+impl FileSystem for Ext2Fs {
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn statfs(&self) -> StatFs {
+        self.root.fs.statfs()
+    }
+}
+
+impl Volume {
+    /// Parse the superblock and group descriptors of the image on `device`
+    fn new(device: Arc<dyn BlockDevice>) -> Result<Arc<Self>, FsError> {
+        let mut sb = [0u8; 1024];
+        device.read_bytes(SUPERBLOCK_OFFSET, &mut sb).map_err(|_| FsError::IOError)?;
+        if le16(&sb, 56) != EXT2_MAGIC {
+            return Err(FsError::InvalidInput);
+        }
+        let log_block_size = le32(&sb, 24);
+        if log_block_size > 2 {
+            return Err(FsError::InvalidInput); // Larger than 4 KiB
+        }
+        let block_size = 1024 << log_block_size;
+        let rev_level = le32(&sb, 76);
+        let (inode_size, incompat) = if rev_level == 0 {
+            (128, 0)
+        } else {
+            (le16(&sb, 88) as usize, le32(&sb, 96))
+        };
+        if incompat & !INCOMPAT_SUPPORTED != 0 {
+            log::warn!("[ext2] Unsupported incompat features {:#x}", incompat & !INCOMPAT_SUPPORTED);
+            return Err(FsError::InvalidInput);
+        }
+
+        let inodes_count = le32(&sb, 0);
+        let blocks_count = le32(&sb, 4);
+        let first_data_block = le32(&sb, 20);
+        let blocks_per_group = le32(&sb, 32);
+        let inodes_per_group = le32(&sb, 40);
+        if blocks_per_group == 0 || inodes_per_group == 0 || inode_size < 128 {
+            return Err(FsError::InvalidInput);
+        }
+        let groups = blocks_count.saturating_sub(first_data_block).div_ceil(blocks_per_group) as usize;
+
+        // The descriptor table starts in the block after the superblock
+        let mut table = vec![0u8; groups * 32];
+        let table_offset = (first_data_block as u64 + 1) * block_size as u64;
+        device.read_bytes(table_offset, &mut table).map_err(|_| FsError::IOError)?;
+        let inode_tables = (0..groups).map(|g| le32(&table, g * 32 + 8)).collect();
+
+        log::info!("[ext2] {} blocks of {} bytes, {} inodes, {} groups",
+                   blocks_count, block_size, inodes_count, groups);
+        Ok(Arc::new(Self {
+            device,
+            block_size,
+            inodes_count,
+            inodes_per_group,
+            blocks_count,
+            free_blocks: le32(&sb, 12),
+            free_inodes: le32(&sb, 16),
+            inode_size,
+            inode_tables,
+        }))
+    }
+
+    fn statfs(&self) -> StatFs {
+        StatFs {
+            magic: EXT2_MAGIC as u64,
+            block_size: self.block_size as u64,
+            blocks: self.blocks_count as u64,
+            blocks_free: self.free_blocks as u64,
+            files: self.inodes_count as u64,
+            files_free: self.free_inodes as u64,
+            name_max: NAME_MAX,
+            read_only: true,
+        }
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        self.device.read_bytes(offset, buf).map_err(|_| FsError::IOError)
+    }
+
+    fn read_block(&self, block: u32, buf: &mut [u8]) -> Result<(), FsError> {
+        self.read(block as u64 * self.block_size as u64, &mut buf[..self.block_size])
+    }
+
+    /// Entry `index` of the block-pointer block `block`
+    fn pointer_at(&self, block: u32, index: usize) -> Result<u32, FsError> {
+        let mut raw = [0u8; 4];
+        self.read(block as u64 * self.block_size as u64 + index as u64 * 4, &mut raw)?;
+        Ok(u32::from_le_bytes(raw))
+    }
+
+    fn load_inode(self: &Arc<Self>, ino: u32) -> Result<Arc<Ext2Inode>, FsError> {
+        if ino == 0 || ino > self.inodes_count {
+            return Err(FsError::IOError);
+        }
+        let group = ((ino - 1) / self.inodes_per_group) as usize;
+        let index = ((ino - 1) % self.inodes_per_group) as u64;
+        let table = *self.inode_tables.get(group).ok_or(FsError::IOError)?;
+        let mut raw = [0u8; 128];
+        self.read(table as u64 * self.block_size as u64 + index * self.inode_size as u64, &mut raw)?;
+
+        let mode = le16(&raw, 0);
+        let mut size = le32(&raw, 4) as u64;
+        if mode & S_IFMT == S_IFREG {
+            // Revision 1 keeps the upper half of a regular file's size in i_dir_acl
+            size |= (le32(&raw, 108) as u64) << 32;
+        }
+        let mut block = [0u8; 60];
+        block.copy_from_slice(&raw[40..100]);
+        // On-disk times are whole seconds
+        let secs = |at| le32(&raw, at) as u64 * 1_000_000_000;
+        let times = FileTimes { atime: secs(8), ctime: secs(12), mtime: secs(16) };
+        // Linux keeps the upper 16 bits of the ids in osd2
+        let uid = le16(&raw, 2) as u32 | (le16(&raw, 120) as u32) << 16;
+        let gid = le16(&raw, 24) as u32 | (le16(&raw, 122) as u32) << 16;
+        Ok(Arc::new(Ext2Inode { fs: self.clone(), mode, size, times, uid, gid, block }))
+    }
+}
+
+pub struct Ext2Inode {
+    fs: Arc<Volume>,
+    mode: u16,
+    size: u64,
+    times: FileTimes,
+    uid: u32,
+    gid: u32,
+    /// Raw i_block: 15 block pointers, or the target of a fast symlink
+    block: [u8; 60],
+}
+
+impl Ext2Inode {
+    fn file_type(&self) -> FileType {
+        match self.mode & S_IFMT {
+            S_IFDIR => FileType::Directory,
+            S_IFLNK => FileType::Symlink,
+            S_IFREG => FileType::File,
+            _ => FileType::Device,
+        }
+    }
+
+    /// Short symlinks keep their target in i_block instead of a data block
+    fn is_fast_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK && self.size < 60
+    }
+
+    /// Device block holding logical block `n` of this file (0 = hole)
+    fn block_for(&self, n: usize) -> Result<u32, FsError> {
+        let per_block = self.fs.block_size / 4;
+        if n < DIRECT_BLOCKS {
+            return Ok(le32(&self.block, n * 4));
+        }
+        // Find which indirect tree holds `n`, then descend it; `stride` is
+        // how many data blocks sit under each pointer at the current level
+        let mut n = n - DIRECT_BLOCKS;
+        let mut span = per_block;
+        for level in 0..3 {
+            if n < span {
+                let mut block = le32(&self.block, (DIRECT_BLOCKS + level) * 4);
+                let mut stride = span / per_block;
+                loop {
+                    if block == 0 {
+                        return Ok(0);
+                    }
+                    block = self.fs.pointer_at(block, n / stride)?;
+                    if stride == 1 {
+                        return Ok(block);
+                    }
+                    n %= stride;
+                    stride /= per_block;
+                }
+            }
+            n -= span;
+            span *= per_block;
+        }
+        Err(FsError::IOError)
+    }
+
+    fn read_data(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let len = buf.len().min((self.size - offset) as usize);
+        if self.is_fast_symlink() {
+            let start = offset as usize;
+            buf[..len].copy_from_slice(&self.block[start..start + len]);
+            return Ok(len);
+        }
+        let bs = self.fs.block_size;
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let skip = (pos % bs as u64) as usize;
+            let chunk = (bs - skip).min(len - done);
+            match self.block_for((pos / bs as u64) as usize)? {
+                0 => buf[done..done + chunk].fill(0), // Sparse hole
+                block => self.fs.read(block as u64 * bs as u64 + skip as u64, &mut buf[done..done + chunk])?,
+            }
+            done += chunk;
+        }
+        Ok(len)
+    }
+
+    /// Every (name, inode) in this directory, "." and ".." included
+    fn entries(&self) -> Result<Vec<(String, u32)>, FsError> {
+        if self.file_type() != FileType::Directory {
+            return Err(FsError::NotADirectory);
+        }
+        let bs = self.fs.block_size;
+        let mut entries = Vec::new();
+        let mut block = vec![0u8; bs];
+        for n in 0..self.size.div_ceil(bs as u64) as usize {
+            match self.block_for(n)? {
+                0 => continue,
+                b => self.fs.read_block(b, &mut block)?,
+            }
+            let mut pos = 0;
+            while pos + 8 <= bs {
+                let ino = le32(&block, pos);
+                let rec_len = le16(&block, pos + 4) as usize;
+                let name_len = block[pos + 6] as usize;
+                if rec_len < 8 || pos + rec_len > bs || 8 + name_len > rec_len {
+                    return Err(FsError::IOError); // Corrupt entry
+                }
+                if ino != 0 {
+                    let name = String::from_utf8_lossy(&block[pos + 8..pos + 8 + name_len]).into_owned();
+                    entries.push((name, ino));
+                }
+                pos += rec_len;
+            }
+        }
+        Ok(entries)
+    }
+}
+
+impl Inode for Ext2Inode {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        self.read_data(offset, buf).unwrap_or(0)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8], _nonblock: bool) -> Result<usize, FsError> {
+        if self.file_type() == FileType::Directory {
+            return Err(FsError::IsADirectory);
+        }
+        self.read_data(offset, buf)
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self) -> Metadata {
+        // Permission bits minus write: the mount is read-only
+        let perm = self.mode as u32 & FileMode::MASK & !FileMode::all(FileMode::WRITE).0;
+        Metadata {
+            size: self.size,
+            mode: FileMode(perm),
+            file_type: self.file_type(),
+            times: self.times,
+            uid: self.uid,
+            gid: self.gid,
+        }
+    }
+
+    fn poll(&self) -> Result<Vec<(String, u64)>, FsError> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|(name, _)| name != "." && name != "..")
+            .map(|(name, ino)| (name, ino as u64))
+            .collect())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        let ino = self
+            .entries()?
+            .into_iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, ino)| ino)
+            .ok_or(FsError::NotFound)?;
+        Ok(self.fs.load_inode(ino)?)
+    }
+
+    fn create(&self, _name: &str, _mode: FileMode) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn statfs(&self) -> StatFs {
+        self.fs.statfs()
+    }
+
+    fn truncate(&self, _size: u64) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename(&self, _old_name: &str, _new_dir: &dyn Inode, _new_name: &str) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+}
@@ -2,15 +2,30 @@
 
 pub mod vfs;     // VFS abstraction
 pub mod ramfs;   // In-memory filesystem
+pub mod pipe;    // Anonymous pipes
+pub mod socket;  // Unix domain sockets
+pub mod epoll;   // epoll instances
+pub mod eventfd; // eventfd counters
+pub mod devfs;   // /dev device nodes
+pub mod procfs;  // /proc files
+pub mod ext2;    // Read-only ext2
 pub mod initrd;  // Initial RAM Disk loading (stub)
 
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use vfs::{FileSystem, Inode};
 use spin::RwLock;
 
 /// Global VFS Root
 pub static ROOT: RwLock<Option<Arc<dyn Inode>>> = RwLock::new(None);
 
+/// Size limit of the tmpfs mounted on /tmp
+pub const TMPFS_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Filesystems grafted onto directories, by normalized absolute path
+static MOUNTS: RwLock<Vec<(String, Arc<dyn Inode>)>> = RwLock::new(Vec::new());
+
 /// Initialize filesystem layer
 pub fn init() {
     log::info!("[VFS] Initializing Virtual Filesystem...");
@@ -20,32 +35,163 @@ pub fn init() {
     let init_data = initrd::load();
     ramfs.add_file("init", init_data);
     log::info!("[VFS] Added /init to RamFS");
+    ramfs.add_dir("tmp");
 
     let root = ramfs.root_inode();
     
     // Mount root
     *ROOT.write() = Some(root);
     log::info!("[VFS] Mounted ROOT (RamFS)");
+    
+    if let Err(e) = mount("/tmp", &ramfs::TmpFs::with_limit(TMPFS_SIZE)) {
+        log::warn!("[VFS] Failed to mount /tmp: {:?}", e);
+    }
 }
 
-/// Open a file by path
-pub fn open(path: &str, _flags: u32) -> Result<Arc<dyn Inode>, vfs::FsError> {
-    // TODO: Proper path resolution
-    // For now, only support root-level file lookup
-    let root_guard = ROOT.read();
-    let root = root_guard.as_ref().ok_or(vfs::FsError::NotFound)?;
+/// Mount `fs` over the existing directory `path`, hiding what was there
+pub fn mount(path: &str, fs: &dyn FileSystem) -> Result<(), vfs::FsError> {
+    use vfs::{FileType, FsError};
     
+    let path = normalize("/", path);
     if path == "/" {
-        return Ok(root.clone());
+        return Err(FsError::InvalidInput); // Replace ROOT instead
+    }
+    if open(&path, 0)?.metadata().file_type != FileType::Directory {
+        return Err(FsError::NotADirectory);
+    }
+    let mut mounts = MOUNTS.write();
+    if mounts.iter().any(|(at, _)| *at == path) {
+        return Err(FsError::AlreadyExists);
+    }
+    log::info!("[VFS] Mounted {}", path);
+    mounts.push((path, fs.root_inode()));
+    Ok(())
+}
+
+/// Root of the filesystem mounted at `path`, if any
+fn mounted_at(path: &str) -> Option<Arc<dyn Inode>> {
+    MOUNTS.read().iter().find(|(at, _)| at == path).map(|(_, root)| root.clone())
+}
+
+/// Make `path` absolute against `cwd`, folding ".", ".." and repeated slashes
+/// Purely lexical: ".." at the root stays at the root.
+pub fn normalize(cwd: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let base = if path.starts_with('/') { "" } else { cwd };
+    for part in base.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            name => parts.push(name),
+        }
+    }
+    let mut out = String::new();
+    for part in &parts {
+        out.push('/');
+        out.push_str(part);
+    }
+    if out.is_empty() {
+        out.push('/');
+    }
+    out
+}
+
+/// Walk to the directory holding the normalized path `path`, returning
+/// it and the final component ("" for "/")
+fn walk_parent(path: &str) -> Result<(Arc<dyn Inode>, &str), vfs::FsError> {
+    let root_guard = ROOT.read();
+    let mut inode = root_guard.as_ref().ok_or(vfs::FsError::NotFound)?.clone();
+    drop(root_guard);
+    let (parent, last) = path.rsplit_once('/').unwrap_or(("", path));
+    let mut walked = String::new();
+    for name in parent.split('/').filter(|p| !p.is_empty()) {
+        walked.push('/');
+        walked.push_str(name);
+        inode = enter(inode.lookup(name)?, &walked);
     }
+    Ok((inode, last))
+}
+
+/// Crossing a mount point swaps in the mounted filesystem's root
+fn enter(dir: Arc<dyn Inode>, at: &str) -> Arc<dyn Inode> {
+    mounted_at(at).unwrap_or(dir)
+}
+
+/// Open a file by path (relative paths are taken from the root)
+/// Honours O_CREAT, O_EXCL and O_TRUNC; other flags are the caller's business.
+/// Files it creates are readable and writable by everyone.
+pub fn open(path: &str, flags: u32) -> Result<Arc<dyn Inode>, vfs::FsError> {
+    use vfs::FileMode;
+    open_with_mode(path, flags, FileMode::all(FileMode::READ | FileMode::WRITE))
+}
+
+/// `open`, creating a missing file with permission bits `mode`
+pub fn open_with_mode(path: &str, flags: u32, mode: vfs::FileMode) -> Result<Arc<dyn Inode>, vfs::FsError> {
+    use vfs::{FileType, FsError, O_ACCMODE, O_CREAT, O_DIRECTORY, O_EXCL, O_NOFOLLOW, O_TRUNC};
     
-    // Simple lookup for "/filename"
-    let filename = if path.starts_with('/') {
-        &path[1..]
+    let path = normalize("/", path);
+    let file = if let Some(name) = path.strip_prefix("/dev/") {
+        devfs::lookup(name)?
+    } else if let Some(name) = path.strip_prefix("/proc/") {
+        procfs::lookup(name)?
     } else {
-        path
+        let (inode, last) = walk_parent(&path)?;
+        if last.is_empty() {
+            inode // "/"
+        } else {
+            match inode.lookup(last) {
+                Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(FsError::AlreadyExists),
+                Ok(file) => enter(file, &path),
+                Err(FsError::NotFound) if flags & O_CREAT != 0 => inode.create(last, mode)?,
+                Err(e) => return Err(e),
+            }
+        }
     };
+    let file_type = file.metadata().file_type;
+    if flags & O_NOFOLLOW != 0 && file_type == FileType::Symlink {
+        return Err(FsError::TooManyLinks);
+    }
+    if flags & O_DIRECTORY != 0 && file_type != FileType::Directory {
+        return Err(FsError::NotADirectory);
+    }
+    // Directories only open read-only
+    if flags & O_ACCMODE != 0 && file_type == FileType::Directory {
+        return Err(FsError::IsADirectory);
+    }
+    if flags & O_TRUNC != 0 && file_type == FileType::File {
+        file.truncate(0)?;
+    }
+    Ok(file)
+}
+
+/// Remove the file at `path` (not a directory)
+pub fn unlink(path: &str) -> Result<(), vfs::FsError> {
+    let path = normalize("/", path);
+    if path == "/" || mounted_at(&path).is_some() {
+        return Err(vfs::FsError::Busy);
+    }
+    let (dir, name) = walk_parent(&path)?;
+    dir.unlink(name)
+}
+
+/// Move `old` to `new`, replacing a file or empty directory already there
+pub fn rename(old: &str, new: &str) -> Result<(), vfs::FsError> {
+    use vfs::FsError;
     
-    // Lookup in root directory
-    root.lookup(filename)
+    let (old, new) = (normalize("/", old), normalize("/", new));
+    if old == "/" || new == "/" || mounted_at(&old).is_some() || mounted_at(&new).is_some() {
+        return Err(FsError::Busy);
+    }
+    // A directory can't move inside itself
+    if new.strip_prefix(old.as_str()).is_some_and(|rest| rest.starts_with('/')) {
+        return Err(FsError::InvalidInput);
+    }
+    let (src_dir, src_name) = walk_parent(&old)?;
+    if old == new {
+        return src_dir.lookup(src_name).map(|_| ());
+    }
+    let (dst_dir, dst_name) = walk_parent(&new)?;
+    src_dir.rename(src_name, &*dst_dir, dst_name)
 }
@@ -0,0 +1,238 @@
+//! Anonymous Pipes
+//!
+//! A pipe is a bounded byte queue shared by a read end and a write end.
+//! Each end is its own inode, so the number of open descriptors on a side
+//! is the strong count of its inode; when the last one goes away its
+//! `Drop` marks that side closed. Reads on an empty pipe block until data
+//! arrives or every writer is gone (EOF); writes to a full pipe block until
+//! a reader makes room, and fail with EPIPE once no reader is left.
+//!
+//! `PipeBuffer` carries all of that logic so other byte streams (Unix
+//! socket pairs) can be built from a pair of them.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use crate::sched::wait::WaitQueue;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, POLLERR, POLLHUP, POLLIN, POLLOUT};
+
+/// Bytes a pipe buffers before writers have to wait
+pub const PIPE_CAPACITY: usize = 4096;
+
+/// One direction of a byte stream
+pub struct PipeBuffer {
+    data: Mutex<VecDeque<u8>>,
+    read_open: AtomicBool,
+    write_open: AtomicBool,
+    /// Readers waiting for data, writers waiting for room
+    readers: WaitQueue,
+    writers: WaitQueue,
+}
+
+impl PipeBuffer {
+    pub fn new() -> Self {
+        Self {
+            data: Mutex::new(VecDeque::with_capacity(PIPE_CAPACITY)),
+            read_open: AtomicBool::new(true),
+            write_open: AtomicBool::new(true),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+        }
+    }
+
+    fn readable(&self) -> bool {
+        !self.data.lock().is_empty() || !self.write_open.load(Ordering::Acquire)
+    }
+
+    fn writable(&self) -> bool {
+        self.data.lock().len() < PIPE_CAPACITY || !self.read_open.load(Ordering::Acquire)
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.data.lock().len()
+    }
+
+    /// Take whatever is buffered, without waiting
+    pub fn drain_into(&self, buf: &mut [u8]) -> usize {
+        let mut data = self.data.lock();
+        let len = buf.len().min(data.len());
+        for (dst, src) in buf.iter_mut().zip(data.drain(..len)) {
+            *dst = src;
+        }
+        drop(data);
+        if len > 0 {
+            self.writers.wake_all();
+        }
+        len
+    }
+
+    /// Queue as much of `buf` as fits, without waiting
+    pub fn fill_from(&self, buf: &[u8]) -> usize {
+        let mut data = self.data.lock();
+        let len = buf.len().min(PIPE_CAPACITY - data.len());
+        data.extend(&buf[..len]);
+        drop(data);
+        if len > 0 {
+            self.readers.wake_all();
+        }
+        len
+    }
+
+    /// Read, blocking while empty unless `nonblock`; 0 means EOF
+    pub fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, FsError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = self.drain_into(buf);
+            if len > 0 || !self.write_open.load(Ordering::Acquire) {
+                return Ok(len);
+            }
+            if nonblock {
+                return Err(FsError::WouldBlock);
+            }
+            self.readers.wait_until(|| self.readable());
+        }
+    }
+
+    /// Blocking writes return only once all of `buf` is queued
+    pub fn write(&self, buf: &[u8], nonblock: bool) -> Result<usize, FsError> {
+        let mut written = 0;
+        loop {
+            if !self.read_open.load(Ordering::Acquire) {
+                return Err(FsError::BrokenPipe);
+            }
+            written += self.fill_from(&buf[written..]);
+            if written == buf.len() {
+                return Ok(written);
+            }
+            if nonblock {
+                return if written > 0 { Ok(written) } else { Err(FsError::WouldBlock) };
+            }
+            self.writers.wait_until(|| self.writable());
+        }
+    }
+
+    /// Non-blocking write that gives up once nobody reads
+    pub fn write_now(&self, buf: &[u8]) -> usize {
+        if !self.read_open.load(Ordering::Acquire) {
+            return 0;
+        }
+        self.fill_from(buf)
+    }
+
+    /// Poll bits seen by the reading side
+    pub fn read_events(&self) -> u16 {
+        let mut events = 0;
+        if !self.data.lock().is_empty() {
+            events |= POLLIN;
+        }
+        // No writers left: a read returns EOF immediately
+        if !self.write_open.load(Ordering::Acquire) {
+            events |= POLLIN | POLLHUP;
+        }
+        events
+    }
+
+    /// Poll bits seen by the writing side
+    pub fn write_events(&self) -> u16 {
+        if !self.read_open.load(Ordering::Acquire) {
+            return POLLERR;
+        }
+        if self.data.lock().len() < PIPE_CAPACITY {
+            POLLOUT
+        } else {
+            0
+        }
+    }
+
+    /// The last reader went away: blocked writers now fail with EPIPE
+    pub fn close_read(&self) {
+        self.read_open.store(false, Ordering::Release);
+        self.writers.wake_all();
+    }
+
+    /// The last writer went away: blocked readers now see EOF
+    pub fn close_write(&self) {
+        self.write_open.store(false, Ordering::Release);
+        self.readers.wake_all();
+    }
+}
+
+pub struct PipeReader(Arc<PipeBuffer>);
+pub struct PipeWriter(Arc<PipeBuffer>);
+
+/// Create a pipe, returning its (read, write) ends
+pub fn pipe() -> (Arc<dyn Inode>, Arc<dyn Inode>) {
+    let buffer = Arc::new(PipeBuffer::new());
+    (Arc::new(PipeReader(buffer.clone())), Arc::new(PipeWriter(buffer)))
+}
+
+fn pipe_metadata(buffer: &PipeBuffer, mode: u32) -> Metadata {
+    Metadata {
+        size: buffer.buffered() as u64,
+        mode: FileMode::all(mode),
+        file_type: FileType::Pipe,
+        times: FileTimes::default(),
+        uid: 0,
+        gid: 0,
+    }
+}
+
+impl Inode for PipeReader {
+    /// Pipes have no offset; an empty pipe reads as 0 bytes
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> usize {
+        self.0.drain_into(buf)
+    }
+
+    fn read(&self, _offset: u64, buf: &mut [u8], nonblock: bool) -> Result<usize, FsError> {
+        self.0.read(buf, nonblock)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn metadata(&self) -> Metadata {
+        pipe_metadata(&self.0, FileMode::READ)
+    }
+
+    fn readiness(&self) -> u16 {
+        self.0.read_events()
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.0.close_read();
+    }
+}
+
+impl Inode for PipeWriter {
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> usize {
+        self.0.write_now(buf)
+    }
+
+    fn write(&self, _offset: u64, buf: &[u8], nonblock: bool) -> Result<usize, FsError> {
+        self.0.write(buf, nonblock)
+    }
+
+    fn metadata(&self) -> Metadata {
+        pipe_metadata(&self.0, FileMode::WRITE)
+    }
+
+    fn readiness(&self) -> u16 {
+        self.0.write_events()
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.0.close_write();
+    }
+}
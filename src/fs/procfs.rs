@@ -0,0 +1,73 @@
+//! /proc Files
+//!
+//! Like /dev there is no directory inode; `fs::open` hands "/proc/<name>"
+//! paths to `lookup`. Each open renders the file's text once, so a reader
+//! sees one consistent snapshot however it splits its reads.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, StatFs};
+
+/// f_type of procfs
+pub const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+
+/// A read-only file holding text rendered at open time
+pub struct ProcFile {
+    text: String,
+}
+
+impl Inode for ProcFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let bytes = self.text.as_bytes();
+        let start = (offset as usize).min(bytes.len());
+        let len = buf.len().min(bytes.len() - start);
+        buf[..len].copy_from_slice(&bytes[start..start + len]);
+        len
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            // Linux reports 0 for generated files too
+            size: 0,
+            mode: FileMode(0o444),
+            file_type: FileType::File,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn statfs(&self) -> StatFs {
+        StatFs::pseudo(PROC_SUPER_MAGIC)
+    }
+}
+
+/// Context switches of the timer-driven scheduler and what they cost
+#[cfg(target_arch = "x86_64")]
+fn sched_stats() -> String {
+    use core::fmt::Write;
+    let stats = crate::multitasking::switch_stats();
+    let mut text = String::new();
+    let _ = writeln!(text, "switches {}", stats.count);
+    let _ = writeln!(text, "latency_cycles_min {}", stats.min);
+    let _ = writeln!(text, "latency_cycles_max {}", stats.max);
+    let _ = writeln!(text, "latency_cycles_avg {}", stats.avg());
+    text
+}
+
+/// /proc file called `name` (the part after "/proc/")
+pub fn lookup(name: &str) -> Result<Arc<dyn Inode>, FsError> {
+    match name {
+        #[cfg(target_arch = "x86_64")]
+        "sched_stats" => Ok(Arc::new(ProcFile { text: sched_stats() })),
+        _ => Err(FsError::NotFound),
+    }
+}
@@ -1,28 +1,115 @@
 //! Simple RAM Filesystem
+//!
+//! With a size limit this is tmpfs: file data is charged to a per-mount
+//! `Quota`, and writes or creates that would exceed it fail with NoSpace
+//! (ENOSPC). An unlimited RamFS still keeps count, for statfs.
 
 use alloc::sync::Arc;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
-use spin::RwLock;
+use core::any::Any;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::{Mutex, RwLock};
 use alloc::vec::Vec;
-use crate::fs::vfs::{self, FileSystem, Inode, Metadata, FileType, FileMode, FsError};
+use crate::fs::vfs::{self, FileSystem, Inode, Metadata, FileTimes, FileType, FileMode, FsError, StatFs};
+use crate::mm::PAGE_SIZE;
+
+pub const RAMFS_MAGIC: u64 = 0x8584_58f6;
+pub const TMPFS_MAGIC: u64 = 0x0102_1994;
+
+/// Bytes of file data a filesystem may hold
+struct Quota {
+    limit: u64,
+    used: AtomicU64,
+    /// Live files and directories
+    nodes: AtomicU64,
+}
+
+impl Quota {
+    /// Unlimited RamFS reports zeroes like Linux ramfs; tmpfs reports its
+    /// limit in pages, and as many free inodes as free pages
+    fn statfs(&self) -> StatFs {
+        if self.limit == u64::MAX {
+            return StatFs::pseudo(RAMFS_MAGIC);
+        }
+        let page = PAGE_SIZE as u64;
+        let blocks = self.limit / page;
+        let blocks_free = blocks.saturating_sub(self.used.load(Ordering::Relaxed).div_ceil(page));
+        StatFs {
+            magic: TMPFS_MAGIC,
+            block_size: page,
+            blocks,
+            blocks_free,
+            files: self.nodes.load(Ordering::Relaxed) + blocks_free,
+            files_free: blocks_free,
+            name_max: 255,
+            read_only: false,
+        }
+    }
+
+    /// Reserve up to `want` bytes, returning how many fit
+    fn charge(&self, want: u64) -> u64 {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let grant = want.min(self.limit.saturating_sub(used));
+            match self.used.compare_exchange_weak(used, used + grant, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return grant,
+                Err(now) => used = now,
+            }
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    fn full(&self) -> bool {
+        self.used.load(Ordering::Relaxed) >= self.limit
+    }
+}
 
 /// RamFS structure
 pub struct RamFS {
     root: Arc<RamNode>,
+    quota: Arc<Quota>,
 }
 
+/// A size-limited RamFS
+pub type TmpFs = RamFS;
+
 impl RamFS {
     pub fn new() -> Self {
+        Self::with_limit(u64::MAX)
+    }
+    
+    /// A filesystem holding at most `limit` bytes of file data
+    pub fn with_limit(limit: u64) -> Self {
+        let quota = Arc::new(Quota { limit, used: AtomicU64::new(0), nodes: AtomicU64::new(0) });
         Self {
-            root: Arc::new(RamNode::new_dir()),
+            root: Arc::new(RamNode::new_dir(quota.clone())),
+            quota,
         }
     }
     
+    /// Bytes of file data stored, and the limit (u64::MAX = unlimited)
+    pub fn usage(&self) -> (u64, u64) {
+        (self.quota.used.load(Ordering::Relaxed), self.quota.limit)
+    }
+    
+    /// Add a file to the root directory; the initrd is not held to the limit
     pub fn add_file(&self, name: &str, content: Vec<u8>) {
          let mut guard = self.root.data.write();
          if let RamNodeData::Directory { children } = &mut *guard {
-             children.insert(String::from(name), Arc::new(RamNode::new_file(content)));
+             self.quota.used.fetch_add(content.len() as u64, Ordering::Relaxed);
+             children.insert(String::from(name), Arc::new(RamNode::new_file(content, FileMode::all(FileMode::READ | FileMode::WRITE), self.quota.clone())));
+         }
+    }
+    
+    /// Add an empty directory to the root directory
+    pub fn add_dir(&self, name: &str) {
+         let mut guard = self.root.data.write();
+         if let RamNodeData::Directory { children } = &mut *guard {
+             children.insert(String::from(name), Arc::new(RamNode::new_dir(self.quota.clone())));
          }
     }
 }
@@ -31,11 +118,43 @@ impl FileSystem for RamFS {
     fn root_inode(&self) -> Arc<dyn Inode> {
         self.root.clone()
     }
+
+    fn statfs(&self) -> StatFs {
+        self.quota.statfs()
+    }
 }
 
 /// Node in RamFS (File or Directory)
 struct RamNode {
     data: RwLock<RamNodeData>,
+    quota: Arc<Quota>,
+    times: Mutex<FileTimes>,
+    attrs: Mutex<Attrs>,
+}
+
+/// What chmod(2) and chown(2) change
+#[derive(Clone, Copy)]
+struct Attrs {
+    mode: FileMode,
+    uid: u32,
+    gid: u32,
+}
+
+impl Attrs {
+    /// Owned by root; `rwx` for everyone
+    fn new(rwx: u32) -> Mutex<Self> {
+        Mutex::new(Self { mode: FileMode::all(rwx), uid: 0, gid: 0 })
+    }
+}
+
+/// Timestamps come from the tick clock, like CLOCK_REALTIME
+fn now() -> u64 {
+    crate::sched::clock::uptime_ns()
+}
+
+fn created_now() -> Mutex<FileTimes> {
+    let t = now();
+    Mutex::new(FileTimes { atime: t, mtime: t, ctime: t })
 }
 
 enum RamNodeData {
@@ -48,17 +167,111 @@ enum RamNodeData {
 }
 
 impl RamNode {
-    fn new_dir() -> Self {
+    fn new_dir(quota: Arc<Quota>) -> Self {
+        quota.nodes.fetch_add(1, Ordering::Relaxed);
         Self {
             data: RwLock::new(RamNodeData::Directory {
                 children: BTreeMap::new(),
             }),
+            quota,
+            times: created_now(),
+            attrs: Attrs::new(FileMode::READ | FileMode::WRITE | FileMode::EXEC),
         }
     }
     
-    fn new_file(content: Vec<u8>) -> Self {
+    fn new_file(content: Vec<u8>, mode: FileMode, quota: Arc<Quota>) -> Self {
+        quota.nodes.fetch_add(1, Ordering::Relaxed);
         Self {
             data: RwLock::new(RamNodeData::File { content }),
+            quota,
+            times: created_now(),
+            attrs: Mutex::new(Attrs { mode: FileMode(mode.0 & FileMode::MASK), uid: 0, gid: 0 }),
+        }
+    }
+    
+    /// Contents changed: new mtime and ctime
+    fn touch_modified(&self) {
+        let mut times = self.times.lock();
+        times.mtime = now();
+        times.ctime = times.mtime;
+    }
+    
+    /// Attributes changed: new ctime
+    fn touch_changed(&self) {
+        self.times.lock().ctime = now();
+    }
+}
+
+impl RamNode {
+    /// Write what fits under the quota; NoSpace only if nothing did
+    fn write_data(&self, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        let mut guard = self.data.write();
+        match &mut *guard {
+            RamNodeData::File { content } => {
+                let off = offset as usize;
+                let mut end = match off.checked_add(buf.len()) {
+                    Some(end) => end,
+                    None => return Ok(0), // Offset past the address space
+                };
+                if end > content.len() {
+                    let want = (end - content.len()) as u64;
+                    let grant = self.quota.charge(want);
+                    end -= (want - grant) as usize;
+                    if end <= off && !buf.is_empty() {
+                        self.quota.release(grant);
+                        return Err(FsError::NoSpace);
+                    }
+                    content.resize(end, 0);
+                }
+                content[off..end].copy_from_slice(&buf[..end - off]);
+                self.touch_modified();
+                Ok(end - off)
+            }
+            RamNodeData::Directory { .. } => Ok(0), // Cannot write to dir directly
+        }
+    }
+}
+
+impl RamNode {
+    fn is_dir(&self) -> bool {
+        matches!(*self.data.read(), RamNodeData::Directory { .. })
+    }
+
+    /// Whether `node`, from this directory, may take the place of
+    /// `existing` under rename(2) rules; false when there is nothing to do
+    fn may_replace(&self, node: &Arc<RamNode>, existing: Option<&Arc<RamNode>>) -> Result<bool, FsError> {
+        let Some(existing) = existing else {
+            return Ok(true);
+        };
+        if Arc::ptr_eq(existing, node) {
+            return Ok(false); // Already there
+        }
+        // Replacing our own (locked) directory: it holds `node`, so it isn't empty
+        if core::ptr::eq(Arc::as_ptr(existing), self) {
+            return Err(if node.is_dir() { FsError::NotEmpty } else { FsError::IsADirectory });
+        }
+        match (node.is_dir(), &*existing.data.read()) {
+            (true, RamNodeData::Directory { children }) if !children.is_empty() => Err(FsError::NotEmpty),
+            (true, RamNodeData::File { .. }) => Err(FsError::NotADirectory),
+            (false, RamNodeData::Directory { .. }) => Err(FsError::IsADirectory),
+            _ => Ok(true),
+        }
+    }
+}
+
+/// Entries of a directory node
+fn children_mut(data: &mut RamNodeData) -> Result<&mut BTreeMap<String, Arc<RamNode>>, FsError> {
+    match data {
+        RamNodeData::Directory { children } => Ok(children),
+        RamNodeData::File { .. } => Err(FsError::NotADirectory),
+    }
+}
+
+impl Drop for RamNode {
+    fn drop(&mut self) {
+        self.quota.nodes.fetch_sub(1, Ordering::Relaxed);
+        if let RamNodeData::File { content } = self.data.get_mut() {
+            self.quota.release(content.len() as u64);
         }
     }
 }
@@ -74,6 +287,7 @@ impl Inode for RamNode {
                 }
                 let len = core::cmp::min(buf.len(), content.len() - off);
                 buf[..len].copy_from_slice(&content[off..off + len]);
+                self.times.lock().atime = now();
                 len
             }
             RamNodeData::Directory { .. } => 0, // Cannot read dir as file
@@ -81,34 +295,27 @@ impl Inode for RamNode {
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> usize {
-        let mut guard = self.data.write();
-        match &mut *guard {
-            RamNodeData::File { content } => {
-                let off = offset as usize;
-                let end = off + buf.len();
-                if end > content.len() {
-                    content.resize(end, 0);
-                }
-                content[off..end].copy_from_slice(buf);
-                buf.len()
-            }
-            RamNodeData::Directory { .. } => 0, // Cannot write to dir directly
-        }
+        self.write_data(offset, buf).unwrap_or(0)
+    }
+
+    fn write(&self, offset: u64, buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        self.write_data(offset, buf)
     }
 
     fn metadata(&self) -> Metadata {
         let guard = self.data.read();
-        match &*guard {
-            RamNodeData::File { content } => Metadata {
-                size: content.len() as u64,
-                mode: FileMode(FileMode::READ | FileMode::WRITE),
-                file_type: FileType::File,
-            },
-            RamNodeData::Directory { .. } => Metadata {
-                size: 0,
-                mode: FileMode(FileMode::READ | FileMode::WRITE | FileMode::EXEC),
-                file_type: FileType::Directory,
-            },
+        let (size, file_type) = match &*guard {
+            RamNodeData::File { content } => (content.len() as u64, FileType::File),
+            RamNodeData::Directory { .. } => (0, FileType::Directory),
+        };
+        let attrs = *self.attrs.lock();
+        Metadata {
+            size,
+            mode: attrs.mode,
+            file_type,
+            times: *self.times.lock(),
+            uid: attrs.uid,
+            gid: attrs.gid,
         }
     }
     
@@ -117,9 +324,9 @@ impl Inode for RamNode {
         match &*guard {
             RamNodeData::Directory { children } => {
                 let mut entries = Vec::new();
-                for (name, _) in children.iter() {
-                    // TODO: Return actual inode number if we tracked it
-                    entries.push((name.clone(), 0)); 
+                for (name, node) in children.iter() {
+                    // Nodes don't move while referenced, so the address is a stable inode number
+                    entries.push((name.clone(), Arc::as_ptr(node) as u64));
                 }
                 Ok(entries)
             }
@@ -127,6 +334,148 @@ impl Inode for RamNode {
         }
     }
     
+    fn create(&self, name: &str, mode: FileMode) -> Result<Arc<dyn Inode>, FsError> {
+        let mut guard = self.data.write();
+        match &mut *guard {
+            RamNodeData::Directory { children } => {
+                if children.contains_key(name) {
+                    return Err(FsError::AlreadyExists);
+                }
+                if self.quota.full() {
+                    return Err(FsError::NoSpace);
+                }
+                let node = Arc::new(RamNode::new_file(Vec::new(), mode, self.quota.clone()));
+                children.insert(String::from(name), node.clone());
+                self.touch_modified();
+                Ok(node)
+            }
+            _ => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FsError> {
+        let mut guard = self.data.write();
+        let children = children_mut(&mut guard)?;
+        let node = children.get(name).ok_or(FsError::NotFound)?;
+        if node.is_dir() {
+            return Err(FsError::IsADirectory);
+        }
+        if let Some(node) = children.remove(name) {
+            node.touch_changed();
+        }
+        self.touch_modified();
+        Ok(())
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), FsError> {
+        let mut guard = self.data.write();
+        match &mut *guard {
+            RamNodeData::File { content } => {
+                let old = content.len() as u64;
+                if size > old {
+                    let grant = self.quota.charge(size - old);
+                    if grant != size - old {
+                        self.quota.release(grant);
+                        return Err(FsError::NoSpace);
+                    }
+                } else {
+                    self.quota.release(old - size);
+                }
+                content.resize(size as usize, 0);
+                self.touch_modified();
+                Ok(())
+            }
+            RamNodeData::Directory { .. } => Err(FsError::IsADirectory),
+        }
+    }
+    
+    fn statfs(&self) -> StatFs {
+        self.quota.statfs()
+    }
+    
+    fn rename(&self, old_name: &str, new_dir: &dyn Inode, new_name: &str) -> Result<(), FsError> {
+        let target = new_dir
+            .as_any()
+            .and_then(|a| a.downcast_ref::<RamNode>())
+            .filter(|t| Arc::ptr_eq(&t.quota, &self.quota))
+            .ok_or(FsError::CrossDevice)?;
+        
+        // Take both directory locks in address order so concurrent renames
+        // in opposite directions can't deadlock
+        let same_dir = core::ptr::eq(self, target);
+        let (mut src_guard, mut dst_guard) = if same_dir {
+            (self.data.write(), None)
+        } else if (self as *const RamNode) < (target as *const RamNode) {
+            let src = self.data.write();
+            (src, Some(target.data.write()))
+        } else {
+            let dst = target.data.write();
+            (self.data.write(), Some(dst))
+        };
+        let node = children_mut(&mut src_guard)?.get(old_name).ok_or(FsError::NotFound)?.clone();
+        match dst_guard.as_deref_mut() {
+            None => {
+                let dir = children_mut(&mut src_guard)?;
+                if self.may_replace(&node, dir.get(new_name))? {
+                    dir.remove(old_name);
+                    dir.insert(String::from(new_name), node.clone());
+                    self.touch_modified();
+                    node.touch_changed();
+                }
+            }
+            Some(dst_data) => {
+                // Moving a directory below itself would orphan it
+                if core::ptr::eq(Arc::as_ptr(&node), target) {
+                    return Err(FsError::InvalidInput);
+                }
+                let dst = children_mut(dst_data)?;
+                if self.may_replace(&node, dst.get(new_name))? {
+                    dst.insert(String::from(new_name), node.clone());
+                    children_mut(&mut src_guard)?.remove(old_name);
+                    self.touch_modified();
+                    target.touch_modified();
+                    node.touch_changed();
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    fn set_times(&self, atime: Option<u64>, mtime: Option<u64>) -> Result<(), FsError> {
+        let mut times = self.times.lock();
+        if let Some(atime) = atime {
+            times.atime = atime;
+        }
+        if let Some(mtime) = mtime {
+            times.mtime = mtime;
+        }
+        times.ctime = now();
+        Ok(())
+    }
+    
+    fn set_mode(&self, mode: FileMode) -> Result<(), FsError> {
+        self.attrs.lock().mode = FileMode(mode.0 & FileMode::MASK);
+        self.touch_changed();
+        Ok(())
+    }
+    
+    fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> Result<(), FsError> {
+        let mut attrs = self.attrs.lock();
+        if let Some(uid) = uid {
+            attrs.uid = uid;
+        }
+        if let Some(gid) = gid {
+            attrs.gid = gid;
+        }
+        drop(attrs);
+        self.touch_changed();
+        Ok(())
+    }
+    
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+    
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
         let guard = self.data.read();
         match &*guard {
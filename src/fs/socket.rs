@@ -0,0 +1,69 @@
+//! Unix Domain Sockets
+//!
+//! Only connected stream pairs from socketpair(2) for now. Each endpoint
+//! reads from one `PipeBuffer` and writes to the other, so blocking, EOF
+//! and EPIPE behave exactly as for pipes, in both directions.
+
+use alloc::sync::Arc;
+use crate::fs::pipe::PipeBuffer;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata};
+
+pub const AF_UNIX: usize = 1;
+pub const SOCK_STREAM: usize = 1;
+pub const SOCK_NONBLOCK: usize = 0o4000;
+pub const SOCK_CLOEXEC: usize = 0o2000000;
+
+/// One end of a connected AF_UNIX stream
+pub struct UnixStream {
+    rx: Arc<PipeBuffer>,
+    tx: Arc<PipeBuffer>,
+}
+
+/// Create a connected pair of stream sockets
+pub fn socketpair() -> (Arc<dyn Inode>, Arc<dyn Inode>) {
+    let a_to_b = Arc::new(PipeBuffer::new());
+    let b_to_a = Arc::new(PipeBuffer::new());
+    let a = UnixStream { rx: b_to_a.clone(), tx: a_to_b.clone() };
+    let b = UnixStream { rx: a_to_b, tx: b_to_a };
+    (Arc::new(a), Arc::new(b))
+}
+
+impl Inode for UnixStream {
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> usize {
+        self.rx.drain_into(buf)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> usize {
+        self.tx.write_now(buf)
+    }
+
+    fn read(&self, _offset: u64, buf: &mut [u8], nonblock: bool) -> Result<usize, FsError> {
+        self.rx.read(buf, nonblock)
+    }
+
+    fn write(&self, _offset: u64, buf: &[u8], nonblock: bool) -> Result<usize, FsError> {
+        self.tx.write(buf, nonblock)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ | FileMode::WRITE),
+            file_type: FileType::Socket,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn readiness(&self) -> u16 {
+        self.rx.read_events() | self.tx.write_events()
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        self.rx.close_read();
+        self.tx.close_write();
+    }
+}
@@ -3,6 +3,7 @@
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::sync::Arc;
+use core::any::Any;
 use core::fmt;
 
 /// File types
@@ -13,9 +14,11 @@ pub enum FileType {
     Device,
     Pipe,
     Symlink,
+    Socket,
 }
 
-/// File permission/mode flags
+/// Permission bits of st_mode: rwx for owner, group and others, plus
+/// setuid/setgid/sticky
 #[derive(Debug, Clone, Copy)]
 pub struct FileMode(pub u32);
 
@@ -23,6 +26,37 @@ impl FileMode {
     pub const READ: u32 = 0o4;
     pub const WRITE: u32 = 0o2;
     pub const EXEC: u32 = 0o1;
+    /// Every bit chmod(2) can change
+    pub const MASK: u32 = 0o7777;
+
+    /// The same `READ`/`WRITE`/`EXEC` bits for owner, group and others
+    pub const fn all(rwx: u32) -> Self {
+        Self(rwx << 6 | rwx << 3 | rwx)
+    }
+}
+
+/// poll(2) event bits, as reported by `Inode::readiness`
+pub const POLLIN: u16 = 0x001;
+pub const POLLOUT: u16 = 0x004;
+pub const POLLERR: u16 = 0x008;
+pub const POLLHUP: u16 = 0x010;
+pub const POLLNVAL: u16 = 0x020;
+
+/// open(2) flags the VFS itself acts on
+pub const O_ACCMODE: u32 = 0o3;   // Nonzero = opened for writing
+pub const O_CREAT: u32 = 0o100;
+pub const O_EXCL: u32 = 0o200;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_DIRECTORY: u32 = 0o200000; // Fail unless it is a directory
+pub const O_NOFOLLOW: u32 = 0o400000;  // Fail if the last component is a symlink
+
+/// Access, modification and status-change times, in nanoseconds of
+/// CLOCK_REALTIME
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileTimes {
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
 }
 
 /// Metadata for a file/inode
@@ -30,6 +64,42 @@ pub struct Metadata {
     pub size: u64,
     pub mode: FileMode,
     pub file_type: FileType,
+    pub times: FileTimes,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Filesystem-wide figures reported by statfs(2)
+#[derive(Debug, Clone, Copy)]
+pub struct StatFs {
+    /// f_type magic (TMPFS_MAGIC, EXT2_SUPER_MAGIC, ...)
+    pub magic: u64,
+    pub block_size: u64,
+    pub blocks: u64,
+    pub blocks_free: u64,
+    pub files: u64,
+    pub files_free: u64,
+    pub name_max: u64,
+    pub read_only: bool,
+}
+
+/// anon_inodefs: where pipes, sockets, eventfds and epoll sets live
+pub const ANON_INODE_FS_MAGIC: u64 = 0x0904_1934;
+
+impl StatFs {
+    /// An empty pseudo filesystem with the given magic
+    pub const fn pseudo(magic: u64) -> Self {
+        Self {
+            magic,
+            block_size: 4096,
+            blocks: 0,
+            blocks_free: 0,
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+            read_only: false,
+        }
+    }
 }
 
 /// Inode trait - represents an object in the filesystem (file or dir)
@@ -40,6 +110,17 @@ pub trait Inode: Send + Sync {
     /// Write data to file at offset
     fn write_at(&self, offset: u64, buf: &[u8]) -> usize;
     
+    /// Read through a descriptor. Defaults to `read_at`; inodes with
+    /// errors to report (empty eventfd, ...) override this.
+    fn read(&self, offset: u64, buf: &mut [u8], _nonblock: bool) -> Result<usize, FsError> {
+        Ok(self.read_at(offset, buf))
+    }
+    
+    /// Write through a descriptor. Defaults to `write_at`.
+    fn write(&self, offset: u64, buf: &[u8], _nonblock: bool) -> Result<usize, FsError> {
+        Ok(self.write_at(offset, buf))
+    }
+    
     /// Get file metadata
     fn metadata(&self) -> Metadata;
     
@@ -52,12 +133,86 @@ pub trait Inode: Send + Sync {
     fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FsError> {
         Err(FsError::NotADirectory)
     }
+
+    /// Create an empty regular file called `name` in this directory, with
+    /// permission bits `mode` (the caller has applied the umask)
+    fn create(&self, _name: &str, _mode: FileMode) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// Remove the non-directory entry `name` from this directory; open
+    /// handles keep the file itself alive
+    fn unlink(&self, _name: &str) -> Result<(), FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// Set the access and/or modification time (None leaves it alone);
+    /// the change time becomes now
+    fn set_times(&self, _atime: Option<u64>, _mtime: Option<u64>) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    /// Replace the permission bits (`FileMode::MASK`); the change time becomes now
+    fn set_mode(&self, _mode: FileMode) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    /// Change the owner and/or group (None leaves it alone); the change
+    /// time becomes now
+    fn set_owner(&self, _uid: Option<u32>, _gid: Option<u32>) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    /// Move entry `old_name` of this directory to `new_name` in `new_dir`
+    /// (possibly this directory), replacing what is there
+    fn rename(&self, _old_name: &str, _new_dir: &dyn Inode, _new_name: &str) -> Result<(), FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// Cut or zero-extend a regular file to `size` bytes
+    fn truncate(&self, _size: u64) -> Result<(), FsError> {
+        Err(FsError::InvalidInput)
+    }
+
+    /// Events that would not block right now (POLLIN/POLLOUT/...)
+    /// Regular files never block, so they are always readable and writable.
+    fn readiness(&self) -> u16 {
+        POLLIN | POLLOUT
+    }
+
+    /// Figures for the filesystem holding this inode
+    fn statfs(&self) -> StatFs {
+        StatFs::pseudo(ANON_INODE_FS_MAGIC)
+    }
+
+    /// Concrete type, for syscalls that only apply to one kind of inode
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+}
+
+/// Read until `buf` is full or EOF, returning the total read
+/// `read_at` may return short (pipes, devices), so callers that need
+/// the whole range should use this instead.
+pub fn read_exact_at(inode: &dyn Inode, offset: u64, buf: &mut [u8]) -> usize {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = inode.read_at(offset + total as u64, &mut buf[total..]);
+        if n == 0 {
+            break;
+        }
+        total += n;
+    }
+    total
 }
 
 /// FileSystem trait
 pub trait FileSystem: Send + Sync {
     /// Get the root inode
     fn root_inode(&self) -> Arc<dyn Inode>;
+
+    /// Size and usage figures for statfs(2)
+    fn statfs(&self) -> StatFs;
 }
 
 /// VFS Errors
@@ -68,6 +223,23 @@ pub enum FsError {
     NotADirectory,
     IsADirectory,
     IOError,
+    /// The operation would block (EAGAIN)
+    WouldBlock,
+    InvalidInput,
+    /// Write to a pipe with no readers (EPIPE)
+    BrokenPipe,
+    /// O_CREAT|O_EXCL on an existing name (EEXIST)
+    AlreadyExists,
+    /// The filesystem's size limit is reached (ENOSPC)
+    NoSpace,
+    /// Replacing a directory that still has entries (ENOTEMPTY)
+    NotEmpty,
+    /// Rename between two filesystems (EXDEV)
+    CrossDevice,
+    /// The path is a mount point or the root (EBUSY)
+    Busy,
+    /// A symlink where none may be (O_NOFOLLOW), or too many of them (ELOOP)
+    TooManyLinks,
 }
 
 impl fmt::Display for FsError {
@@ -1,10 +1,14 @@
 use spin::Mutex;
 use aether_core::scheduler::Scheduler;
 use lazy_static::lazy_static;
+use core::sync::atomic::AtomicUsize;
 
 lazy_static! {
     pub static ref SCHEDULER: Mutex<Option<Scheduler>> = Mutex::new(None);
 }
 
 // Storage for the Idle/Boot thread's stack pointer
-pub static mut IDLE_STACK_POINTER: usize = 0;
+pub static IDLE_STACK_POINTER: AtomicUsize = AtomicUsize::new(0);
+
+// Where the last context of a Terminated process is saved (never loaded)
+pub static DEAD_STACK_POINTER: AtomicUsize = AtomicUsize::new(0);
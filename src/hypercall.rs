@@ -0,0 +1,79 @@
+//! Guest Hypercalls
+//!
+//! Host side of the `mmio::HYPERCALL_*` doorbell. There is no
+//! virtualization, so a guest's doorbell write can't trap: the backend
+//! polls it (on every scheduler pass, and around each step) and answers
+//! here, the way `syscall::dispatch` answers POSIX tasks.
+
+use alloc::string::String;
+use aether_abi::errno;
+use aether_abi::mmio::{
+    DOORBELL_DONE, DOORBELL_RING, HYPERCALL_ARGS, HYPERCALL_ARG_COUNT, HYPERCALL_DOORBELL,
+    HYPERCALL_NR, HYPERCALL_RET,
+};
+use aether_abi::HyperCall;
+use core::sync::atomic::{fence, Ordering};
+use crate::backend::UefiBackend;
+
+/// Longest string a single Print takes
+const PRINT_MAX: u64 = 4096;
+
+/// Answer the guest's hypercall, if its doorbell is ringing
+pub fn service(backend: &UefiBackend) {
+    let regs = backend.base_address() as *mut u8;
+    unsafe {
+        let doorbell = regs.add(HYPERCALL_DOORBELL) as *mut u32;
+        if doorbell.read_volatile() != DOORBELL_RING {
+            return;
+        }
+        // Arguments were written before the doorbell
+        fence(Ordering::Acquire);
+        let nr = (regs.add(HYPERCALL_NR) as *const u64).read_volatile();
+        let mut args = [0u64; HYPERCALL_ARG_COUNT];
+        for (i, arg) in args.iter_mut().enumerate() {
+            *arg = (regs.add(HYPERCALL_ARGS) as *const u64).add(i).read_volatile();
+        }
+        
+        let ret = dispatch(backend, nr, args);
+        
+        (regs.add(HYPERCALL_RET) as *mut i64).write_volatile(ret);
+        // The result must be visible before the guest sees DONE
+        fence(Ordering::Release);
+        doorbell.write_volatile(DOORBELL_DONE);
+    }
+}
+
+/// Hypercall dispatcher
+pub fn dispatch(backend: &UefiBackend, nr: u64, args: [u64; HYPERCALL_ARG_COUNT]) -> i64 {
+    match HyperCall::from_u64(nr) {
+        Some(HyperCall::Print) => hc_print(backend, args[0], args[1]),
+        Some(HyperCall::Exit) => hc_exit(backend, args[0] as i32),
+        Some(HyperCall::Time) => hc_time(),
+        None => {
+            log::warn!("[hypercall] Unknown hypercall {}", nr);
+            -errno::ENOSYS as i64
+        }
+    }
+}
+
+fn hc_print(backend: &UefiBackend, offset: u64, len: u64) -> i64 {
+    if len > PRINT_MAX {
+        return -errno::EINVAL as i64;
+    }
+    match backend.guest_bytes(offset as usize, len as usize) {
+        Some(bytes) => {
+            log::info!("[guest] {}", String::from_utf8_lossy(bytes).trim_end());
+            len as i64
+        }
+        None => -errno::EFAULT as i64,
+    }
+}
+
+fn hc_exit(backend: &UefiBackend, status: i32) -> i64 {
+    backend.finish(status);
+    0
+}
+
+fn hc_time() -> i64 {
+    crate::sched::clock::uptime_ns() as i64
+}
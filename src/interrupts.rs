@@ -1,12 +1,26 @@
 use lazy_static::lazy_static;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
 use pic8259::ChainedPics;
 use spin::Mutex;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use log::{info, error};
+use crate::arch::x86_64::idt::TrapFrameExt;
+use crate::arch::x86_64::percpu::KernelGs;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
+// Signals reported for user tasks killed by a CPU exception
+const SIGILL: i32 = 4;
+const SIGFPE: i32 = 8;
+const SIGSEGV: i32 = 11;
+
+/// A tick found SCHEDULER locked and could not switch; the next one will
+static SWITCH_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Ticks the running process has had since the last switch
+static SLICE_TICKS: AtomicU64 = AtomicU64::new(0);
+
 pub static PICS: Mutex<ChainedPics> =
     Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
@@ -28,12 +42,22 @@ impl InterruptIndex {
     }
 }
 
+/// Acknowledge `irq` at the PIC; each handler does this exactly once
+fn end_of_interrupt(irq: InterruptIndex) {
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(irq.as_u8());
+    }
+}
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
+        idt.divide_error.set_handler_fn(divide_error_handler);
         idt.breakpoint.set_handler_fn(breakpoint_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
         idt.double_fault.set_handler_fn(double_fault_handler);
         idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+        idt.page_fault.set_handler_fn(page_fault_handler);
         
         // Timer Interrupt
         idt[InterruptIndex::Timer.as_usize()]
@@ -45,21 +69,43 @@ lazy_static! {
     };
 }
 
+/// PIT input clock
+const PIT_BASE_HZ: u64 = 1_193_182;
+
+/// Rates `set_timer_hz` accepts; 18Hz is about the slowest the 16-bit
+/// divisor reaches
+pub const TIMER_HZ_MIN: u64 = 18;
+pub const TIMER_HZ_MAX: u64 = 1000;
+
 // PIT defaults to 18.2Hz if untouched, but we want faster checks for UI.
-// Let's set it to ~100Hz.
 pub fn init_pit() {
-    let mut command_port = x86_64::instructions::port::Port::<u8>::new(0x43);
-    let mut data_port = x86_64::instructions::port::Port::<u8>::new(0x40);
+    set_timer_hz(crate::sched::clock::DEFAULT_HZ);
+}
+
+/// Reprogram PIT channel 0 to fire at about `hz` (clamped to
+/// `TIMER_HZ_MIN..=TIMER_HZ_MAX`) and return the rate actually set
+/// Safe to call at any time; the tick clock follows the new period.
+pub fn set_timer_hz(hz: u64) -> u64 {
+    let hz = hz.clamp(TIMER_HZ_MIN, TIMER_HZ_MAX);
+    // 1193182 / 100 Hz = 11931
+    let divisor = (PIT_BASE_HZ / hz).min(u16::MAX as u64) as u16;
     
-    // 0x34: Channel 0, Lo/Hi Byte, Rate Generator (Mode 2), Binary
-    unsafe { command_port.write(0x34) };
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut command_port = x86_64::instructions::port::Port::<u8>::new(0x43);
+        let mut data_port = x86_64::instructions::port::Port::<u8>::new(0x40);
+        
+        // 0x34: Channel 0, Lo/Hi Byte, Rate Generator (Mode 2), Binary
+        unsafe {
+            command_port.write(0x34);
+            data_port.write((divisor & 0xFF) as u8);
+            data_port.write((divisor >> 8) as u8);
+        }
+        crate::sched::clock::set_tick_ns(divisor as u64 * 1_000_000_000 / PIT_BASE_HZ);
+    });
     
-    // 1193182 / 100 Hz = 11931
-    let divisor = 11931u16;
-    unsafe {
-        data_port.write((divisor & 0xFF) as u8);
-        data_port.write((divisor >> 8) as u8);
-    }
+    let actual = PIT_BASE_HZ / divisor as u64;
+    info!("[Aether::Interrupts] Timer at {}Hz", actual);
+    actual
 }
 
 pub fn init_idt() {
@@ -73,34 +119,126 @@ pub fn init_idt() {
 extern "x86-interrupt" fn breakpoint_handler(
     stack_frame: InterruptStackFrame)
 {
+    let _gs = KernelGs::enter(&stack_frame);
     info!("[EXCEPTION] BREAKPOINT\n{:#?}", stack_frame);
 }
 
+extern "x86-interrupt" fn divide_error_handler(
+    stack_frame: InterruptStackFrame)
+{
+    let _gs = KernelGs::enter(&stack_frame);
+    if stack_frame.is_user() {
+        user_oops("DIVIDE ERROR", SIGFPE, &stack_frame);
+    }
+    
+    error!("[EXCEPTION] DIVIDE ERROR\n{:#?}", stack_frame);
+    crate::panic::record_fault(stack_frame.instruction_pointer.as_u64(), stack_frame.stack_pointer.as_u64());
+    panic!("Divide error");
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(
+    stack_frame: InterruptStackFrame)
+{
+    let _gs = KernelGs::enter(&stack_frame);
+    if stack_frame.is_user() {
+        user_oops("INVALID OPCODE", SIGILL, &stack_frame);
+    }
+    
+    error!("[EXCEPTION] INVALID OPCODE\n{:#?}", stack_frame);
+    crate::panic::record_fault(stack_frame.instruction_pointer.as_u64(), stack_frame.stack_pointer.as_u64());
+    panic!("Invalid opcode");
+}
+
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame, _error_code: u64) -> !
 {
+    crate::panic::record_fault(stack_frame.instruction_pointer.as_u64(), stack_frame.stack_pointer.as_u64());
     panic!("[EXCEPTION] DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn general_protection_fault_handler(
     stack_frame: InterruptStackFrame, error_code: u64)
 {
+    let _gs = KernelGs::enter(&stack_frame);
+    if stack_frame.is_user() {
+        user_oops("GENERAL PROTECTION FAULT", SIGSEGV, &stack_frame);
+    }
+    
     error!("[EXCEPTION] GENERAL PROTECTION FAULT\nError Code: {}\n{:#?}", error_code, stack_frame);
+    crate::panic::record_fault(stack_frame.instruction_pointer.as_u64(), stack_frame.stack_pointer.as_u64());
     panic!("GPF");
 }
 
+extern "x86-interrupt" fn page_fault_handler(
+    stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode)
+{
+    use x86_64::registers::control::Cr2;
+    let _gs = KernelGs::enter(&stack_frame);
+    
+    let addr = Cr2::read();
+    if stack_frame.is_user() {
+        let write = error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE);
+        let exec = error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH);
+        match crate::mm::fault::handle_user_fault(addr.as_u64() as usize, write, exec) {
+            Ok(()) => {
+                crate::sched::account_fault(false);
+                return;
+            }
+            Err(reason) => error!("[OOPS] Page fault at {:?} ({:?}): {}", addr, error_code, reason),
+        }
+        user_oops("PAGE FAULT", SIGSEGV, &stack_frame);
+    }
+    
+    // The kernel touching user memory for a syscall (read() into a fresh
+    // mmap, say) backs the page just as the program's own access would
+    let write = error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE);
+    match crate::mm::fault::handle_kernel_fault(addr.as_u64() as usize, write) {
+        Ok(()) => {
+            crate::sched::account_fault(false);
+            return;
+        }
+        Err(reason) => error!("[EXCEPTION] Kernel page fault not resolved: {}", reason),
+    }
+    
+    error!("[EXCEPTION] PAGE FAULT\nAddress: {:?}\nError Code: {:?}\n{:#?}", addr, error_code, stack_frame);
+    crate::panic::record_fault(stack_frame.instruction_pointer.as_u64(), stack_frame.stack_pointer.as_u64());
+    panic!("Page fault");
+}
+
+/// A Ring 3 program faulted: log an oops and kill only that task
+fn user_oops(what: &str, signal: i32, stack_frame: &InterruptStackFrame) -> ! {
+    error!(
+        "[OOPS] {} in user mode at RIP=0x{:x} RSP=0x{:x}, killing task",
+        what,
+        stack_frame.instruction_pointer.as_u64(),
+        stack_frame.stack_pointer.as_u64()
+    );
+    // wait(2) status for "killed by signal" is just the signal number
+    crate::sched::exit_current(signal)
+}
+
 extern "x86-interrupt" fn keyboard_interrupt_handler(
-    _stack_frame: InterruptStackFrame)
+    stack_frame: InterruptStackFrame)
 {
     use x86_64::instructions::port::Port;
+    let _gs = KernelGs::enter(&stack_frame);
     
     // 1. Read Scancode
     let mut port = Port::new(0x60);
     let scancode: u8 = unsafe { port.read() };
     
-    // 2. Process Scancode
+    // 2. Structured events for /dev/input/event0
+    if let Some((code, pressed)) = crate::keyboard::linux_key(scancode) {
+        crate::drivers::input::report_key(code, pressed);
+    }
+    
+    // 3. Process Scancode
     if let Some(key) = crate::keyboard::process_scancode(scancode) {
-        // 3. Inject into Guests (Multi-Cast)
+        // 4. Console stdin, and whoever polls it
+        crate::drivers::console::push_input(key);
+        crate::syscall::wake_pollers();
+        
+        // 5. Inject into Guests (Multi-Cast)
         if let Some(mut sched_lock) = crate::globals::SCHEDULER.try_lock() {
             if let Some(sched) = (*sched_lock).as_mut() {
                 // Broadcast input to all processes!
@@ -112,61 +250,94 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(
         }
     }
 
-    // Safety: we must notify EOI
-    unsafe {
-        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
-    }
+    end_of_interrupt(InterruptIndex::Keyboard);
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(
-    _stack_frame: InterruptStackFrame) 
+    stack_frame: InterruptStackFrame) 
 {
-    // Blit Shadow Buffer to Screen
-    crate::video::blit();
+    let _gs = KernelGs::enter(&stack_frame);
+    crate::sched::clock::tick();
+    crate::sched::wait::expire_timeouts();
+    crate::drivers::input::retry_wakeup();
+    crate::syscall::retry_wake_pollers();
+    crate::sched::account_tick(stack_frame.is_user());
+    #[cfg(feature = "watchdog")]
+    crate::sched::watchdog::check(&stack_frame);
+
+    // Blit Shadow Buffer to Screen (throttled to video::PRESENT_HZ)
+    crate::video::present_tick();
 
     // Preemptive Multitasking
-    // Try to lock scheduler
-    if let Some(mut sched_lock) = crate::globals::SCHEDULER.try_lock() {
-        if let Some(sched) = (*sched_lock).as_mut() {
-            let prev_pid = sched.current_pid;
-            
-            // Check if we need to switch
-            // Ensure we don't switch if we are in the middle of crucial kernel things?
-            // "schedule()" handles the decision.
-            if let Some(next_pid) = sched.schedule() {
+    //
+    // Interrupt-safety invariants:
+    // - IF stays clear for the whole handler and there is one CPU, so
+    //   between taking the snapshot below and `switch_context` nothing
+    //   else can touch the scheduler: the save slot and the new SP can't
+    //   go stale, and the outgoing process can't be reaped.
+    // - The SCHEDULER lock is dropped before switching. The incoming
+    //   context resumes in its own handler (or the trampoline) and would
+    //   never release a guard held by ours.
+    // - EOI goes out once, before switching, on every path: the incoming
+    //   context never comes back through this handler's tail (a fresh one
+    //   starts in the trampoline), so an EOI after the switch would be
+    //   skipped and the timer would stop firing. Nothing on the resume path
+    //   acknowledges again, so there is no double EOI either.
+    // - If the lock is busy (we interrupted its holder), spinning would
+    //   deadlock; the switch is marked pending and done on the next tick.
+    // - Switches happen once per `clock::QUANTUM_MS`, however many ticks
+    //   that is at the current rate.
+    let slice = SLICE_TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    let due = slice >= crate::sched::clock::quantum_ticks() || SWITCH_PENDING.load(Ordering::Relaxed);
+    let switch = match due.then(|| crate::globals::SCHEDULER.try_lock()) {
+        None => None,
+        Some(Some(mut sched_lock)) => {
+            SWITCH_PENDING.store(false, Ordering::Relaxed);
+            SLICE_TICKS.store(0, Ordering::Relaxed);
+            sched_lock.as_mut().and_then(|sched| {
+                let prev_pid = sched.current_pid;
+                let next_pid = sched.schedule()?;
                 
                 // 1. Resolve Old Stack Pointer location
-                // If prev_pid is None or invalid, we save to IDLE/BOOT stack.
+                // No previous process: we are on the IDLE/BOOT stack. A
+                // Terminated one is never resumed, so its context goes to a
+                // scratch slot instead of into state about to be reaped.
                 let old_sp_ptr = match prev_pid {
-                    Some(pid) => {
-                        if let Some(p) = sched.get_process_mut(pid) {
-                            &mut p.stack_pointer as *mut usize
-                        } else {
-                             unsafe { &mut crate::globals::IDLE_STACK_POINTER as *mut usize }
-                        }
-                    },
-                    None => unsafe { &mut crate::globals::IDLE_STACK_POINTER as *mut usize }
+                    Some(pid) => sched.save_slot(pid)
+                        .unwrap_or(crate::globals::DEAD_STACK_POINTER.as_ptr()),
+                    None => crate::globals::IDLE_STACK_POINTER.as_ptr()
                 };
                 
                 // 2. Resolve New Stack Pointer
-                // Unwrap is safe because schedule() returned valid PID
+                // Unwrap is safe: schedule() only returns a live, Ready process
                 let new_sp = sched.get_process_mut(next_pid).unwrap().stack_pointer;
                 
                 log::trace!("[Timer] Switching {:?} -> {}", prev_pid, next_pid);
-
-                // Release lock before switch!
-                drop(sched_lock);
-                
-                // 3. Switch Context
-                unsafe {
-                    crate::multitasking::switch_context(new_sp, old_sp_ptr);
-                }
+                Some((old_sp_ptr, new_sp))
+            })
+            // Lock released here, before the switch
+        }
+        Some(None) => {
+            if !SWITCH_PENDING.swap(true, Ordering::Relaxed) {
+                log::trace!("[Timer] Scheduler busy, switch deferred to the next tick");
             }
+            None
         }
-    }
+    };
 
-    // Safety: we must notify EOI or system hangs
-    unsafe {
-        PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+    end_of_interrupt(InterruptIndex::Timer);
+
+    // 3. Switch Context
+    if let Some((old_sp_ptr, new_sp)) = switch {
+        #[cfg(feature = "watchdog")]
+        crate::sched::watchdog::progress();
+        crate::multitasking::switch_started();
+        unsafe {
+            crate::multitasking::switch_context(new_sp, old_sp_ptr);
+        }
+        crate::multitasking::switch_finished();
     }
+
+    // End the time slice of a guest driven through Backend::step
+    crate::backend::preempt_stepped_guest();
 }
@@ -1,6 +1,7 @@
 use spin::Mutex;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, Keyboard, ScancodeSet1};
 use lazy_static::lazy_static;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 lazy_static! {
     static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
@@ -13,9 +14,61 @@ pub fn process_scancode(scancode: u8) -> Option<char> {
         if let Some(key) = keyboard.process_keyevent(key_event) {
             match key {
                 DecodedKey::Unicode(character) => return Some(character),
+                // Shift+PageUp/PageDown scroll the console and are not delivered
+                DecodedKey::RawKey(KeyCode::PageUp) if keyboard.get_modifiers().is_shifted() => {
+                    crate::drivers::console::page_up();
+                }
+                DecodedKey::RawKey(KeyCode::PageDown) if keyboard.get_modifiers().is_shifted() => {
+                    crate::drivers::console::page_down();
+                }
                 DecodedKey::RawKey(_) => {},
             }
         }
     }
     None
 }
+
+/// The last scancode byte was the 0xE0 extended prefix
+static EXTENDED: AtomicBool = AtomicBool::new(false);
+
+/// Translate a set 1 scancode byte into a Linux KEY_* code and whether the
+/// key went down; None for prefixes and keys without a code
+pub fn linux_key(scancode: u8) -> Option<(u16, bool)> {
+    if scancode == 0xE0 {
+        EXTENDED.store(true, Ordering::Relaxed);
+        return None;
+    }
+    let extended = EXTENDED.swap(false, Ordering::Relaxed);
+    let pressed = scancode & 0x80 == 0;
+    let make = scancode & 0x7F;
+    
+    let code = if !extended {
+        // Linux numbers the base keys after their set 1 make codes
+        match make {
+            0x01..=0x58 => make as u16,
+            _ => return None,
+        }
+    } else {
+        match make {
+            0x1C => 96,  // KEY_KPENTER
+            0x1D => 97,  // KEY_RIGHTCTRL
+            0x35 => 98,  // KEY_KPSLASH
+            0x38 => 100, // KEY_RIGHTALT
+            0x47 => 102, // KEY_HOME
+            0x48 => 103, // KEY_UP
+            0x49 => 104, // KEY_PAGEUP
+            0x4B => 105, // KEY_LEFT
+            0x4D => 106, // KEY_RIGHT
+            0x4F => 107, // KEY_END
+            0x50 => 108, // KEY_DOWN
+            0x51 => 109, // KEY_PAGEDOWN
+            0x52 => 110, // KEY_INSERT
+            0x53 => 111, // KEY_DELETE
+            0x5B => 125, // KEY_LEFTMETA
+            0x5C => 126, // KEY_RIGHTMETA
+            // Fake shifts around PrintScreen and friends
+            _ => return None,
+        }
+    };
+    Some((code, pressed))
+}
@@ -1,6 +1,9 @@
-#![no_std]
-#![no_main]
+// Host unit tests (`cargo test`) link std and only exercise pure-logic modules
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(test, allow(dead_code))]
 #![cfg_attr(target_arch = "x86_64", feature(abi_x86_interrupt))]
+#![feature(allocator_api)]
 
 extern crate alloc;
 
@@ -10,11 +13,19 @@ mod sched;
 mod fs;
 mod drivers;
 mod syscall;
+mod panic;
+mod rand;
+mod net;
+mod elf;
+#[cfg(all(feature = "symbols", target_arch = "x86_64"))]
+mod symbols;
 
 // Legacy modules - x86 only, to be refactored/removed
 #[cfg(target_arch = "x86_64")]
 mod backend;
 #[cfg(target_arch = "x86_64")]
+mod hypercall;
+#[cfg(target_arch = "x86_64")]
 mod interrupts;
 #[cfg(target_arch = "x86_64")]
 mod video;
@@ -52,21 +63,39 @@ fn main(_image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     // 4. Initialize Filesystem
     log::info!("[Kernel] Initializing Filesystem...");
     fs::init();
+    #[cfg(feature = "selftest")]
+    test_tmpfs();
+    #[cfg(feature = "selftest")]
+    test_chmod();
+    #[cfg(feature = "selftest")]
+    test_open_flags();
     
     // 5. Initialize Scheduler
     log::info!("[Kernel] Initializing Scheduler...");
     sched::init();
+    #[cfg(feature = "selftest")]
+    test_wait_queue();
+    #[cfg(feature = "selftest")]
+    test_fork_credentials();
+    #[cfg(feature = "selftest")]
+    test_dup3();
+    #[cfg(all(feature = "selftest", target_arch = "x86_64"))]
+    test_init_stack();
+    #[cfg(all(feature = "selftest", target_arch = "x86_64"))]
+    test_stepped_guest();
     
     // 6. Initialize Drivers
     log::info!("[Kernel] Initializing Drivers...");
     drivers::init();
+    #[cfg(feature = "selftest")]
+    test_input_events();
     
     // 7. Load Init Process
     log::info!("[Kernel] Loading /init...");
     if let Ok(inode) = fs::open("/init", 0) {
         // Allocate buffer for init (64KB max for now)
         let mut buffer = alloc::vec![0u8; 65536];
-        let len = inode.read_at(0, &mut buffer);
+        let len = fs::vfs::read_exact_at(&*inode, 0, &mut buffer);
         log::info!("[Kernel] Read init: {} bytes", len);
         
         if len > 0 {
@@ -86,10 +115,12 @@ fn main(_image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
             
             log::info!("[Kernel] Entering Userspace (Ring 3)...");
             
+            // argv/envp/auxv go at the top of the stack
+            let user_sp = syscall::setup_init_stack(stack_addr + stack_size as u64, b"/init");
+            
             // Jump to Ring 3
-            // Stack grows down, so pointer is top of stack region
             unsafe {
-                arch::enter_usermode(code_addr, stack_addr + stack_size as u64);
+                arch::enter_usermode(code_addr, user_sp);
             }
         }
     } else {
@@ -100,6 +131,10 @@ fn main(_image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     
     // Halt Loop
     loop {
+        // Guests reaped by the timer are freed here, outside the interrupt
+        if let Some(sched) = globals::SCHEDULER.lock().as_mut() {
+            sched.free_reaped();
+        }
         #[cfg(target_arch = "x86_64")]
         x86_64::instructions::hlt();
         #[cfg(target_arch = "aarch64")]
@@ -129,14 +164,226 @@ fn init_video(st: &SystemTable<Boot>) {
 fn test_syscalls() {
     log::info!("[Test] Testing POSIX syscalls internally...");
     
-    // Test open (should fail as file doesn't exist yet, or succeed if we stubbed it)
-    let ret = syscall::dispatch(syscall::numbers::SYS_OPEN, 0, 0, 0); // filename=NULL
+    // A bad name pointer and a missing file fail differently
+    let ret = syscall::dispatch(syscall::numbers::SYS_OPEN, 0, 0, 0, 0, 0, 0); // filename=NULL
     log::info!("[Test] open(NULL) = {}", ret);
+    assert_eq!(ret, -syscall::errno::EFAULT);
+    let missing = b"/no-such-file\0";
+    let ret = syscall::dispatch(syscall::numbers::SYS_OPEN, missing.as_ptr() as usize, 0, 0, 0, 0, 0);
+    log::info!("[Test] open(missing) = {}", ret);
+    assert_eq!(ret, -syscall::errno::ENOENT);
     
     // Test write to stdout (fd=1)
     let msg = "Hello from Internal Syscall!\n";
     let ptr = msg.as_ptr() as usize;
     let len = msg.len();
-    let ret = syscall::dispatch(syscall::numbers::SYS_WRITE, 1, ptr, len);
+    let ret = syscall::dispatch(syscall::numbers::SYS_WRITE, 1, ptr, len, 0, 0, 0);
     log::info!("[Test] write(1, ...) = {}", ret);
 }
+
+/// Fill a small tmpfs and check that it stops at its limit
+#[cfg(feature = "selftest")]
+fn test_tmpfs() {
+    use fs::vfs::{FileMode, FileSystem, FsError};
+    
+    let tmpfs = fs::ramfs::TmpFs::with_limit(8192);
+    let root = tmpfs.root_inode();
+    let file = root.create("fill", FileMode(0o644)).expect("tmpfs create");
+    let chunk = [0xA5u8; 3000];
+    let mut written = 0;
+    let err = loop {
+        match file.write(written, &chunk, false) {
+            Ok(n) => written += n as u64,
+            Err(e) => break e,
+        }
+    };
+    assert!(matches!(err, FsError::NoSpace));
+    assert_eq!(written, 8192);
+    assert_eq!(tmpfs.usage(), (8192, 8192));
+    assert!(matches!(root.create("more", FileMode(0o644)), Err(FsError::NoSpace)));
+    assert!(file.truncate(4096).is_ok());
+    assert_eq!(tmpfs.usage().0, 4096);
+    assert!(root.create("more", FileMode(0o644)).is_ok());
+    log::info!("[Test] tmpfs ENOSPC at {} bytes: ok", written);
+}
+
+/// chmod and chown a file on a private tmpfs and read the result back
+#[cfg(feature = "selftest")]
+fn test_chmod() {
+    use fs::vfs::{FileMode, FileSystem};
+    
+    let tmpfs = fs::ramfs::TmpFs::new();
+    let file = tmpfs.root_inode().create("chmod-test", FileMode(0o644)).expect("create chmod-test");
+    assert!(file.set_mode(FileMode(0o4750)).is_ok());
+    assert!(file.set_owner(Some(1000), None).is_ok());
+    let meta = file.metadata();
+    assert_eq!((meta.mode.0, meta.uid, meta.gid), (0o4750, 1000, 0));
+    assert!(file.set_mode(FileMode(0o644)).is_ok());
+    assert_eq!(file.metadata().mode.0, 0o644);
+    log::info!("[Test] chmod round-trip: ok");
+}
+
+/// O_DIRECTORY and write access check the type of what they open
+#[cfg(feature = "selftest")]
+fn test_open_flags() {
+    use fs::vfs::{FsError, O_CREAT, O_DIRECTORY};
+    
+    assert!(fs::open("/tmp/open-flags-test", O_CREAT).is_ok());
+    assert!(matches!(fs::open("/tmp/open-flags-test", O_DIRECTORY), Err(FsError::NotADirectory)));
+    assert!(fs::open("/tmp", O_DIRECTORY).is_ok());
+    assert!(matches!(fs::open("/tmp", 2), Err(FsError::IsADirectory))); // O_RDWR
+    // Leave /tmp as we found it
+    assert!(fs::unlink("/tmp/open-flags-test").is_ok());
+    assert!(matches!(fs::open("/tmp/open-flags-test", 0), Err(FsError::NotFound)));
+    log::info!("[Test] open O_DIRECTORY/EISDIR: ok");
+}
+
+/// Parked tasks are woken in FIFO order, each exactly once
+#[cfg(feature = "selftest")]
+fn test_wait_queue() {
+    use sched::queue::{new_task_ref, TaskRef};
+    use sched::task::{Task, TaskState};
+    use sched::wait::WaitQueue;
+    
+    let queue = WaitQueue::new();
+    let tasks: alloc::vec::Vec<TaskRef> = (0..3).map(|_| new_task_ref(Task::with_pid(0, 0))).collect();
+    for task in &tasks {
+        task.lock().set_state(TaskState::Running);
+        queue.park(task.clone());
+    }
+    let states = || tasks.iter().map(|t| t.lock().state).collect::<alloc::vec::Vec<_>>();
+    assert_eq!(states(), [TaskState::Blocked; 3]);
+    assert!(queue.wake_one());
+    assert_eq!(states(), [TaskState::Ready, TaskState::Blocked, TaskState::Blocked]);
+    assert_eq!(queue.wake_all(), 2);
+    assert_eq!(states(), [TaskState::Ready; 3]);
+    assert!(queue.is_empty() && !queue.wake_one());
+    log::info!("[Test] wait queue park/wake: ok");
+}
+
+/// A forked child starts with its parent's uid and gid
+#[cfg(feature = "selftest")]
+fn test_fork_credentials() {
+    let task = sched::queue::current_task();
+    let mut parent = task.lock();
+    let saved = parent.creds;
+    assert!(parent.creds.set_gid(100).is_ok() && parent.creds.set_uid(1000).is_ok());
+    // Pid 0: a throwaway copy that takes no pid and needs no reaping
+    let child = parent.fork_with_pid(0, 0, 0);
+    assert_eq!(child.creds, parent.creds);
+    assert_eq!((child.creds.uid, child.creds.egid), (1000, 100));
+    parent.creds = saved;
+    log::info!("[Test] fork keeps credentials: ok");
+}
+
+/// dup3 with O_CLOEXEC gives a close-on-exec copy that exec drops, and
+/// rejects oldfd == newfd
+#[cfg(feature = "selftest")]
+fn test_dup3() {
+    use syscall::numbers::{SYS_CLOSE, SYS_DUP3};
+    use syscall::O_CLOEXEC;
+    
+    let inode = fs::open("/tmp/dup3-test", fs::vfs::O_CREAT).expect("create /tmp/dup3-test");
+    let task = sched::queue::current_task();
+    let fd = task.lock().add_file(sched::task::FileDescriptor { inode, offset: 0, flags: 0 });
+    let newfd = fd + 10;
+    
+    assert_eq!(syscall::dispatch(SYS_DUP3, fd, newfd, O_CLOEXEC as usize, 0, 0, 0), newfd as isize);
+    assert_eq!(syscall::dispatch(SYS_DUP3, fd, fd, 0, 0, 0, 0), -syscall::errno::EINVAL);
+    
+    let mut locked = task.lock();
+    assert!(locked.get_file(newfd).is_some_and(|f| f.flags & O_CLOEXEC != 0));
+    assert!(locked.get_file(fd).is_some_and(|f| f.flags & O_CLOEXEC == 0));
+    // What execve does to the table: the copy goes, the original stays
+    syscall::close_on_exec(&mut locked);
+    assert!(locked.get_file(newfd).is_none() && locked.get_file(fd).is_some());
+    drop(locked);
+    
+    // Close the original (exec took the copy) and leave /tmp as we found it
+    assert_eq!(syscall::dispatch(SYS_CLOSE, fd, 0, 0, 0, 0, 0), 0);
+    assert!(fs::unlink("/tmp/dup3-test").is_ok());
+    log::info!("[Test] dup3 O_CLOEXEC: ok");
+}
+
+/// init_stack refuses a buffer too small for its frame, and otherwise
+/// builds the frame switch_context pops right below the aligned top, with
+/// the entry point and argument where trampoline looks for them and RSP
+/// at 8 (mod 16) once its `ret` lands in trampoline
+#[cfg(all(feature = "selftest", target_arch = "x86_64"))]
+fn test_init_stack() {
+    use multitasking::{init_stack, trampoline, INIT_FRAME_SIZE, MIN_STACK_SIZE};
+    
+    let mut small = [0u8; MIN_STACK_SIZE - 1];
+    assert!(init_stack(&mut small, 0x1234, 0x5678).is_err());
+    
+    let mut stack = alloc::vec![0xAAu8; 4096];
+    let base = stack.as_ptr() as usize;
+    let top = (base + stack.len()) & !0xF;
+    let sp = init_stack(&mut stack, 0x1234, 0x5678).expect("init_stack");
+    assert_eq!(top - sp, INIT_FRAME_SIZE);
+    // What trampoline starts with: just past the return address
+    assert_eq!((sp + 7 * 8) % 16, 8);
+    
+    // Popped in this order: R15, R14, R13, R12, RBP, RBX, then the return
+    // address; the padding word stays
+    let frame: alloc::vec::Vec<u64> = stack[sp - base..top - base]
+        .chunks_exact(8)
+        .map(|b| u64::from_ne_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(frame[6], trampoline as *const () as u64);
+    assert_eq!([frame[0], frame[1], frame[4], frame[5], frame[7]], [0; 5]);
+    // trampoline finds entry in R12 and its argument in R13
+    assert_eq!((frame[3], frame[2]), (0x1234, 0x5678));
+    log::info!("[Test] init_stack frame layout: ok");
+}
+
+/// A stepped guest runs until its entry point returns, and `step` hands
+/// back control with interrupts as the host had them, even after the
+/// timer preempted the guest
+#[cfg(all(feature = "selftest", target_arch = "x86_64"))]
+fn test_stepped_guest() {
+    use aether_core::backend::{Backend, ExitReason};
+    use backend::{ExecMode, UefiBackend};
+    
+    // The whole guest image is a `ret`
+    let guest = UefiBackend::with_mode(alloc::vec![0xC3], ExecMode::Stepped).expect("stepped backend");
+    let host_irqs = x86_64::instructions::interrupts::are_enabled();
+    let mut reason = ExitReason::Yield;
+    for _ in 0..16 {
+        reason = guest.step();
+        assert_eq!(x86_64::instructions::interrupts::are_enabled(), host_irqs);
+        if reason != ExitReason::Yield {
+            break;
+        }
+    }
+    assert_eq!(reason, ExitReason::Exited(0));
+    assert_eq!(guest.step(), ExitReason::Exited(0));
+    log::info!("[Test] stepped guest: ok");
+}
+
+/// A press, an autorepeat and a release read back from an event device
+/// (a private queue, so keys typed during boot stay in /dev/input/event0)
+#[cfg(feature = "selftest")]
+fn test_input_events() {
+    use drivers::input::{EventDevice, EventQueue, EVENT_SIZE, EV_KEY};
+    use fs::vfs::{FsError, Inode};
+    
+    static QUEUE: EventQueue = EventQueue::new();
+    let dev = EventDevice::new(&QUEUE);
+    let mut buf = [0u8; 6 * EVENT_SIZE];
+    
+    const KEY_A: u16 = 30;
+    QUEUE.report_key(KEY_A, true);
+    QUEUE.report_key(KEY_A, true);
+    QUEUE.report_key(KEY_A, false);
+    let len = dev.read(0, &mut buf, true).expect("read queued events");
+    let keys: alloc::vec::Vec<(u16, u16, i32)> = buf[..len].chunks_exact(EVENT_SIZE).map(|e| (
+        u16::from_ne_bytes([e[16], e[17]]),
+        u16::from_ne_bytes([e[18], e[19]]),
+        i32::from_ne_bytes([e[20], e[21], e[22], e[23]]),
+    )).filter(|e| e.0 == EV_KEY).collect();
+    assert_eq!(keys, [(EV_KEY, KEY_A, 1), (EV_KEY, KEY_A, 2), (EV_KEY, KEY_A, 0)]);
+    assert!(matches!(dev.read(0, &mut buf, true), Err(FsError::WouldBlock)));
+    assert!(fs::open("/dev/input/event0", 0).is_ok());
+    log::info!("[Test] input key events: ok");
+}
@@ -0,0 +1,50 @@
+//! User Page Fault Resolution
+//!
+//! Backs anonymous mappings on first touch, whether the first touch comes
+//! from the program itself or from the kernel copying to or from user
+//! memory on its behalf (a `read()` into a fresh mapping).
+
+use crate::mm::vma::{PROT_EXEC, PROT_WRITE};
+use crate::mm::PAGE_SIZE;
+use crate::sched::queue::{current_task, try_current_task};
+use crate::sched::task::{Task, USER_SPACE_END};
+
+/// Resolve a fault from user mode at `addr`
+/// On error the caller should kill the task with SIGSEGV.
+pub fn handle_user_fault(addr: usize, write: bool, exec: bool) -> Result<(), &'static str> {
+    let task_arc = current_task();
+    let task = task_arc.lock();
+    resolve(&task, addr, write, exec)
+}
+
+/// Resolve a fault the kernel took on a user address
+/// Only faults inside one of the current task's areas are resolved; the
+/// task lock is not waited for, since the faulting code may hold it.
+/// On error the fault is a kernel bug.
+pub fn handle_kernel_fault(addr: usize, write: bool) -> Result<(), &'static str> {
+    if addr >= USER_SPACE_END {
+        return Err("kernel address");
+    }
+    let task_arc = try_current_task().ok_or("no current task")?;
+    let task = task_arc.try_lock().ok_or("task locked by the faulting code")?;
+    resolve(&task, addr, write, false)
+}
+
+fn resolve(task: &Task, addr: usize, write: bool, exec: bool) -> Result<(), &'static str> {
+    let vma = *task.vmas.find(addr).ok_or("address not mapped")?;
+    if write && vma.prot & PROT_WRITE == 0 {
+        return Err("write to read-only mapping");
+    }
+    if exec && vma.prot & PROT_EXEC == 0 {
+        return Err("execute from non-executable mapping");
+    }
+    if vma.prot == 0 {
+        return Err("access to PROT_NONE mapping");
+    }
+    
+    let page = addr & !(PAGE_SIZE - 1);
+    if crate::mm::paging::map_anon_page(page as u64, vma.prot)? {
+        task.rss_map(1);
+    }
+    Ok(())
+}
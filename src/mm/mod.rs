@@ -4,8 +4,15 @@ pub mod pmm;     // Physical Memory Manager
 pub mod vmm;     // Virtual Memory Manager
 pub mod heap;    // Kernel Heap Allocator
 pub mod paging;  // Page Table Helpers
+pub mod slab;    // Object Caches
+pub mod vma;     // Virtual Memory Areas
+pub mod fault;   // Demand Paging
+
+/// Size of a page mapping
+pub const PAGE_SIZE: usize = 4096;
 
 /// Initialize memory management
 pub fn init() {
+    pmm::init();
     // TODO: Setup page tables, heap
 }
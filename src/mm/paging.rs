@@ -8,6 +8,48 @@ mod x86_64_paging {
         PageTable, OffsetPageTable, Page, PhysFrame, Mapper, FrameAllocator, Size4KiB, PageTableFlags
     };
     use x86_64::{PhysAddr, VirtAddr};
+    use crate::mm::pmm;
+    
+    /// Software PTE bit marking frames that came from the PMM
+    /// (as opposed to firmware identity mappings, which must never be freed)
+    const PMM_OWNED: PageTableFlags = PageTableFlags::BIT_9;
+    
+    /// Software PTE bit marking user pages hidden by PROT_NONE
+    const USER_REVOKED: PageTableFlags = PageTableFlags::BIT_10;
+    
+    /// Leaf flags for a user page with the given PROT_* bits
+    fn prot_flags(prot: u32) -> PageTableFlags {
+        use crate::mm::vma::{PROT_EXEC, PROT_NONE, PROT_WRITE};
+        use x86_64::registers::model_specific::{Efer, EferFlags};
+        
+        if prot == PROT_NONE {
+            return PageTableFlags::PRESENT | USER_REVOKED;
+        }
+        let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        if prot & PROT_WRITE != 0 {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        // NX is a reserved bit unless EFER.NXE is on
+        if prot & PROT_EXEC == 0 && Efer::read().contains(EferFlags::NO_EXECUTE_ENABLE) {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+        flags
+    }
+    
+    /// Whether a leaf PTE belongs to user space (as opposed to an untouched
+    /// firmware identity page)
+    fn is_user_page(flags: PageTableFlags) -> bool {
+        flags.intersects(PageTableFlags::USER_ACCESSIBLE | PMM_OWNED | USER_REVOKED)
+    }
+    
+    /// Feeds page-table and data frames to the mapper from the PMM
+    struct PmmFrameAllocator;
+    
+    unsafe impl FrameAllocator<Size4KiB> for PmmFrameAllocator {
+        fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+            pmm::alloc_frame().map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+        }
+    }
     
     /// Initialize and return the active page table mapper
     /// unsafe: Assumes identity mapping (offset 0)
@@ -40,6 +82,107 @@ mod x86_64_paging {
             }
         }
     }
+    
+    /// Back one user page with a fresh frame, mapped according to `prot`
+    /// Returns whether a frame was allocated. A firmware identity mapping of
+    /// the page is replaced, carrying over anything the kernel already wrote
+    /// through it; otherwise the frame starts zeroed.
+    pub fn map_anon_page(addr: u64, prot: u32) -> Result<bool, &'static str> {
+        use x86_64::structures::paging::mapper::{Translate, TranslateResult};
+        
+        let mut mapper = unsafe { active_mapper() };
+        let mut frames = PmmFrameAllocator;
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+        let flags = prot_flags(prot) | PMM_OWNED;
+        
+        // Already ours: only the permissions need fixing
+        if let TranslateResult::Mapped { flags: old, .. } = mapper.translate(page.start_address()) {
+            if is_user_page(old) {
+                let flags = (flags - PMM_OWNED) | (old & PMM_OWNED);
+                if let Ok(flush) = unsafe { mapper.update_flags(page, flags) } {
+                    flush.flush();
+                }
+                return Ok(false);
+            }
+        }
+        
+        let frame = frames.allocate_frame().ok_or("out of memory")?;
+        if let Ok((old, flush)) = mapper.unmap(page) {
+            flush.flush();
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    old.start_address().as_u64() as *const u8,
+                    frame.start_address().as_u64() as *mut u8,
+                    4096,
+                );
+            }
+        }
+        
+        match unsafe { mapper.map_to(page, frame, flags, &mut frames) } {
+            Ok(flush) => {
+                flush.flush();
+                Ok(true)
+            }
+            Err(_) => {
+                pmm::free_frame(frame.start_address().as_u64());
+                Err("cannot map page")
+            }
+        }
+    }
+    
+    /// Re-apply `prot` to the user pages already present in a range
+    /// Untouched pages pick up the VMA's protection when they fault in.
+    pub fn protect_user_range(start_addr: u64, len: u64, prot: u32) {
+        use x86_64::structures::paging::mapper::{Translate, TranslateResult};
+        
+        let mut mapper = unsafe { active_mapper() };
+        if len == 0 {
+            return;
+        }
+        let start_page = Page::<Size4KiB>::containing_address(VirtAddr::new(start_addr));
+        let end_page = Page::<Size4KiB>::containing_address(VirtAddr::new(start_addr + len - 1));
+        
+        for page in Page::range_inclusive(start_page, end_page) {
+            if let TranslateResult::Mapped { flags: old, .. } = mapper.translate(page.start_address()) {
+                if !is_user_page(old) {
+                    continue;
+                }
+                let flags = prot_flags(prot) | (old & PMM_OWNED);
+                if let Ok(flush) = unsafe { mapper.update_flags(page, flags) } {
+                    flush.flush();
+                }
+            }
+        }
+    }
+    
+    /// Drop the frames behind a user range, returning how many were freed
+    /// Only PMM-backed pages are touched; firmware identity mappings are left alone.
+    pub fn unmap_user_range(start_addr: u64, len: u64) -> usize {
+        let mut mapper = unsafe { active_mapper() };
+        if len == 0 {
+            return 0;
+        }
+        let start_page = Page::<Size4KiB>::containing_address(VirtAddr::new(start_addr));
+        let end_page = Page::<Size4KiB>::containing_address(VirtAddr::new(start_addr + len - 1));
+        
+        let mut freed = 0;
+        for page in Page::range_inclusive(start_page, end_page) {
+            use x86_64::structures::paging::mapper::{Translate, TranslateResult};
+            let owned = matches!(
+                mapper.translate(page.start_address()),
+                TranslateResult::Mapped { flags, .. } if flags.contains(PMM_OWNED)
+            );
+            if !owned {
+                continue;
+            }
+            if let Ok((frame, flush)) = mapper.unmap(page) {
+                flush.flush();
+                pmm::free_frame(frame.start_address().as_u64());
+                freed += 1;
+            }
+        }
+        freed
+    }
 }
 
 #[cfg(target_arch = "aarch64")]
@@ -56,6 +199,21 @@ mod aarch64_paging {
         // UEFI gives us identity mapping, which we use for now.
         // TODO: Walk page tables and set AP bits for user access
     }
+    
+    /// Back one user page (identity-mapped, so nothing to allocate yet)
+    pub fn map_anon_page(addr: u64, _prot: u32) -> Result<bool, &'static str> {
+        make_user_accessible(addr & !0xFFF, 4096);
+        Ok(false)
+    }
+    
+    /// Re-apply protections to a user range
+    /// TODO: Set AP/XN bits once ARM64 page tables are managed
+    pub fn protect_user_range(_start_addr: u64, _len: u64, _prot: u32) {}
+    
+    /// Drop the frames behind a user range (identity-mapped, nothing to free)
+    pub fn unmap_user_range(_start_addr: u64, _len: u64) -> usize {
+        0
+    }
 }
 
 // Re-export the correct implementation
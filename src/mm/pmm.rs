@@ -1,2 +1,75 @@
-// Physical Memory Manager Stub
-pub fn init() {}
+//! Physical Memory Manager
+//!
+//! Hands out 4 KiB frames carved from the firmware heap. UEFI identity-maps
+//! RAM, so a frame's address is valid both physically and virtually.
+//! Frames are capped at `MAX_FRAMES` so running out is an ordinary,
+//! recoverable condition rather than a firmware allocation failure.
+
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use super::PAGE_SIZE;
+
+/// Frame budget for user mappings and page tables (16 MiB)
+pub const MAX_FRAMES: usize = 4096;
+
+/// Log a warning when free frames drop to this level
+pub const LOW_MEMORY_FRAMES: usize = MAX_FRAMES / 16;
+
+/// Frames returned by `free_frame`, reused before asking the heap for more
+static FREE_LIST: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+/// Frames currently handed out
+static USED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init() {
+    log::info!("[PMM] {} frames ({} KiB) available", MAX_FRAMES, MAX_FRAMES * PAGE_SIZE / 1024);
+}
+
+/// Allocate a zeroed frame, or `None` when the budget is exhausted
+pub fn alloc_frame() -> Option<u64> {
+    let used = USED_FRAMES.fetch_add(1, Ordering::Relaxed);
+    if used >= MAX_FRAMES {
+        USED_FRAMES.fetch_sub(1, Ordering::Relaxed);
+        log::warn!("[PMM] Out of memory");
+        return None;
+    }
+    if MAX_FRAMES - used - 1 == LOW_MEMORY_FRAMES {
+        log::warn!("[PMM] Low memory: {} frames left", LOW_MEMORY_FRAMES);
+    }
+    
+    let recycled = FREE_LIST.lock().pop();
+    let frame = match recycled {
+        Some(addr) => {
+            unsafe { core::ptr::write_bytes(addr as *mut u8, 0, PAGE_SIZE); }
+            addr
+        }
+        None => {
+            let ptr = unsafe { alloc::alloc::alloc_zeroed(frame_layout()) };
+            if ptr.is_null() {
+                USED_FRAMES.fetch_sub(1, Ordering::Relaxed);
+                log::warn!("[PMM] Heap exhausted");
+                return None;
+            }
+            ptr as u64
+        }
+    };
+    Some(frame)
+}
+
+/// Return a frame obtained from `alloc_frame`
+pub fn free_frame(addr: u64) {
+    FREE_LIST.lock().push(addr);
+    USED_FRAMES.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Frames still available
+pub fn free_frames() -> usize {
+    MAX_FRAMES - USED_FRAMES.load(Ordering::Relaxed)
+}
+
+fn frame_layout() -> Layout {
+    // PAGE_SIZE is a non-zero power of two
+    unsafe { Layout::from_size_align_unchecked(PAGE_SIZE, PAGE_SIZE) }
+}
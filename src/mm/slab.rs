@@ -0,0 +1,165 @@
+//! Slab Allocator
+//!
+//! Object caches for small, frequently churned kernel objects. A cache
+//! carves fixed-size slots out of page-sized slabs and keeps freed slots on
+//! an intrusive free list, so a warm cache never touches the general heap.
+//! Slabs are kept for the lifetime of the cache.
+
+use alloc::alloc::{alloc, Layout};
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::NonNull;
+use spin::Mutex;
+use super::PAGE_SIZE;
+
+/// Free-list link, stored inside a slot while it is free
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>,
+}
+
+struct SlabState {
+    free: Option<NonNull<FreeSlot>>,
+    slabs: usize,
+    in_use: usize,
+}
+
+// Only reachable through the cache's mutex
+unsafe impl Send for SlabState {}
+
+/// Cache of `T`-sized slots
+pub struct SlabCache<T> {
+    state: Mutex<SlabState>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SlabCache<T> {}
+unsafe impl<T: Send> Sync for SlabCache<T> {}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+impl<T> SlabCache<T> {
+    const SLOT_ALIGN: usize = max(align_of::<T>(), align_of::<FreeSlot>());
+    const SLOT_SIZE: usize =
+        (max(size_of::<T>(), size_of::<FreeSlot>()) + Self::SLOT_ALIGN - 1) & !(Self::SLOT_ALIGN - 1);
+    /// One page, or enough pages for at least 8 slots
+    const SLAB_SIZE: usize = max(PAGE_SIZE, (Self::SLOT_SIZE * 8 + PAGE_SIZE - 1) & !(PAGE_SIZE - 1));
+    
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(SlabState { free: None, slabs: 0, in_use: 0 }),
+            _marker: PhantomData,
+        }
+    }
+    
+    /// Move `value` into a slot
+    pub fn alloc(&self, value: T) -> Option<NonNull<T>> {
+        let slot = self.alloc_slot()?;
+        unsafe { slot.as_ptr().write(value); }
+        Some(slot)
+    }
+    
+    /// Drop the object and recycle its slot
+    /// unsafe: `obj` must come from `alloc` on this cache and not be used again
+    pub unsafe fn free(&self, obj: NonNull<T>) {
+        core::ptr::drop_in_place(obj.as_ptr());
+        self.free_slot(obj);
+    }
+    
+    /// Take an uninitialized slot
+    pub fn alloc_slot(&self) -> Option<NonNull<T>> {
+        let mut state = self.state.lock();
+        if state.free.is_none() && !Self::grow(&mut state) {
+            return None;
+        }
+        let slot = state.free?;
+        state.free = unsafe { slot.as_ref().next };
+        state.in_use += 1;
+        Some(slot.cast())
+    }
+    
+    /// Return a slot without dropping its contents
+    /// unsafe: `slot` must come from `alloc_slot` on this cache
+    pub unsafe fn free_slot(&self, slot: NonNull<T>) {
+        let mut state = self.state.lock();
+        let link = slot.cast::<FreeSlot>();
+        link.as_ptr().write(FreeSlot { next: state.free });
+        state.free = Some(link);
+        state.in_use -= 1;
+    }
+    
+    /// Slots currently handed out
+    pub fn in_use(&self) -> usize {
+        self.state.lock().in_use
+    }
+    
+    /// Slabs allocated so far
+    pub fn slabs(&self) -> usize {
+        self.state.lock().slabs
+    }
+    
+    /// Carve a fresh slab into slots and thread them onto the free list
+    fn grow(state: &mut SlabState) -> bool {
+        let layout = match Layout::from_size_align(Self::SLAB_SIZE, max(Self::SLOT_ALIGN, PAGE_SIZE)) {
+            Ok(l) => l,
+            Err(_) => return false,
+        };
+        let base = unsafe { alloc(layout) };
+        if base.is_null() {
+            return false;
+        }
+        
+        let count = Self::SLAB_SIZE / Self::SLOT_SIZE;
+        for i in (0..count).rev() {
+            let slot = unsafe { base.add(i * Self::SLOT_SIZE) } as *mut FreeSlot;
+            unsafe { slot.write(FreeSlot { next: state.free }); }
+            state.free = NonNull::new(slot);
+        }
+        state.slabs += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_one_slab_per_eight_slots() {
+        let cache: SlabCache<[u64; 64]> = SlabCache::new(); // 8 per 4K slab
+        let objs: alloc::vec::Vec<_> = (0..9).filter_map(|i| cache.alloc([i; 64])).collect();
+        assert_eq!(objs.len(), 9);
+        assert_eq!(cache.in_use(), 9);
+        assert_eq!(cache.slabs(), 2);
+        for obj in objs {
+            unsafe { cache.free(obj) };
+        }
+        assert_eq!(cache.in_use(), 0);
+        assert_eq!(cache.slabs(), 2);
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let cache: SlabCache<[u64; 64]> = SlabCache::new();
+        let first = cache.alloc([1; 64]).unwrap();
+        let second = cache.alloc([2; 64]).unwrap();
+        // The slot freed last is the next one handed out
+        unsafe { cache.free(second) };
+        let again = cache.alloc([3; 64]).unwrap();
+        assert_eq!(again, second);
+        assert_eq!(unsafe { again.as_ref()[0] }, 3);
+        unsafe {
+            cache.free(again);
+            cache.free(first);
+        }
+        
+        let refilled: alloc::vec::Vec<_> = (0..16).filter_map(|i| cache.alloc([i; 64])).collect();
+        assert_eq!(refilled.len(), 16);
+        assert_eq!(cache.slabs(), 2);
+        for obj in refilled {
+            unsafe { cache.free(obj) };
+        }
+        assert_eq!(cache.in_use(), 0);
+    }
+}
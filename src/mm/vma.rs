@@ -0,0 +1,196 @@
+//! Virtual Memory Areas
+//!
+//! Per-task list of user address ranges with their protections, mapping
+//! flags and backing. Anonymous pages are allocated lazily: the page-fault
+//! handler consults the list to decide whether a fault is valid and which
+//! permissions to map with. A fault outside every area is a segfault.
+
+use alloc::vec::Vec;
+
+pub const PROT_NONE: u32 = 0;
+pub const PROT_READ: u32 = 1;
+pub const PROT_WRITE: u32 = 2;
+pub const PROT_EXEC: u32 = 4;
+
+pub const MAP_SHARED: u32 = 0x01;
+pub const MAP_PRIVATE: u32 = 0x02;
+pub const MAP_FIXED: u32 = 0x10;
+pub const MAP_ANONYMOUS: u32 = 0x20;
+
+/// What provides the pages of an area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backing {
+    /// Zero-filled on first touch
+    Anonymous,
+    /// Populated up front by the ELF loader / execve (firmware identity pages)
+    Image,
+}
+
+/// A page-aligned user range [start, end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vma {
+    pub start: usize,
+    pub end: usize,
+    pub prot: u32,
+    pub flags: u32,
+    pub backing: Backing,
+}
+
+impl Vma {
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end
+    }
+    
+    /// Same attributes, so the two can live in one area when adjacent
+    fn compatible(&self, other: &Vma) -> bool {
+        self.prot == other.prot && self.flags == other.flags && self.backing == other.backing
+    }
+}
+
+/// Areas of one task, sorted by start address and non-overlapping
+/// Adjacent areas with identical attributes are always merged.
+#[derive(Clone, Default)]
+pub struct VmaList {
+    areas: Vec<Vma>,
+}
+
+impl VmaList {
+    pub const fn new() -> Self {
+        Self { areas: Vec::new() }
+    }
+    
+    /// Area covering `addr`
+    pub fn find(&self, addr: usize) -> Option<&Vma> {
+        let idx = self.areas.partition_point(|v| v.end <= addr);
+        self.areas.get(idx).filter(|v| v.contains(addr))
+    }
+    
+    /// Whether every byte of [start, end) lies in some area
+    pub fn covers(&self, start: usize, end: usize) -> bool {
+        let mut pos = start;
+        for v in self.areas.iter().skip_while(|v| v.end <= start) {
+            if v.start > pos {
+                return false;
+            }
+            pos = v.end;
+            if pos >= end {
+                return true;
+            }
+        }
+        pos >= end
+    }
+    
+    /// Lowest address of a `len`-byte hole inside [lo, hi)
+    pub fn find_free(&self, lo: usize, hi: usize, len: usize) -> Option<usize> {
+        let mut pos = lo;
+        for v in self.areas.iter().skip_while(|v| v.end <= lo) {
+            if v.start >= hi {
+                break;
+            }
+            if v.start >= pos && v.start - pos >= len {
+                return Some(pos);
+            }
+            pos = pos.max(v.end);
+        }
+        (pos <= hi && hi - pos >= len).then_some(pos)
+    }
+    
+    /// Map [start, end), replacing whatever was there (MAP_FIXED semantics)
+    pub fn insert(&mut self, start: usize, end: usize, prot: u32, flags: u32, backing: Backing) {
+        if start >= end {
+            return;
+        }
+        self.remove(start, end);
+        let idx = self.areas.partition_point(|v| v.start < start);
+        self.areas.insert(idx, Vma { start, end, prot, flags, backing });
+        self.merge();
+    }
+    
+    /// Unmap [start, end), trimming or splitting areas that straddle it
+    pub fn remove(&mut self, start: usize, end: usize) {
+        self.split_at(start);
+        self.split_at(end);
+        self.areas.retain(|v| v.end <= start || v.start >= end);
+    }
+    
+    /// Change the protection of [start, end)
+    /// Fails without changing anything unless the range is fully mapped.
+    pub fn protect(&mut self, start: usize, end: usize, prot: u32) -> Result<(), &'static str> {
+        if !self.covers(start, end) {
+            return Err("range not mapped");
+        }
+        self.split_at(start);
+        self.split_at(end);
+        for v in self.areas.iter_mut().filter(|v| v.start >= start && v.end <= end) {
+            v.prot = prot;
+        }
+        self.merge();
+        Ok(())
+    }
+    
+    /// Drop every area (execve)
+    pub fn clear(&mut self) {
+        self.areas.clear();
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = &Vma> {
+        self.areas.iter()
+    }
+    
+    /// Cut the area containing `addr` in two so that `addr` is a boundary
+    fn split_at(&mut self, addr: usize) {
+        let idx = self.areas.partition_point(|v| v.end <= addr);
+        if let Some(v) = self.areas.get(idx).copied() {
+            if v.start < addr && addr < v.end {
+                self.areas[idx].end = addr;
+                self.areas.insert(idx + 1, Vma { start: addr, ..v });
+            }
+        }
+    }
+    
+    /// Coalesce adjacent areas with identical attributes
+    fn merge(&mut self) {
+        let mut merged: Vec<Vma> = Vec::with_capacity(self.areas.len());
+        for v in self.areas.drain(..) {
+            match merged.last_mut() {
+                Some(prev) if prev.end == v.start && prev.compatible(&v) => prev.end = v.end,
+                _ => merged.push(v),
+            }
+        }
+        self.areas = merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RW: u32 = PROT_READ | PROT_WRITE;
+    const FLAGS: u32 = MAP_PRIVATE | MAP_ANONYMOUS;
+
+    fn areas(list: &VmaList) -> Vec<(usize, usize, u32)> {
+        list.iter().map(|v| (v.start, v.end, v.prot)).collect()
+    }
+
+    #[test]
+    fn protect_splits_and_merges() {
+        let mut vmas = VmaList::new();
+        vmas.insert(0x10000, 0x14000, RW, FLAGS, Backing::Anonymous);
+        assert!(vmas.protect(0x11000, 0x12000, PROT_READ).is_ok());
+        assert_eq!(areas(&vmas), [(0x10000, 0x11000, RW), (0x11000, 0x12000, PROT_READ), (0x12000, 0x14000, RW)]);
+        assert!(vmas.protect(0x11000, 0x12000, RW).is_ok());
+        assert_eq!(areas(&vmas), [(0x10000, 0x14000, RW)]);
+    }
+
+    #[test]
+    fn remove_punches_a_hole() {
+        let mut vmas = VmaList::new();
+        vmas.insert(0x10000, 0x14000, RW, FLAGS, Backing::Anonymous);
+        vmas.remove(0x11000, 0x13000);
+        assert_eq!(areas(&vmas), [(0x10000, 0x11000, RW), (0x13000, 0x14000, RW)]);
+        assert!(vmas.find(0x12000).is_none());
+        assert!(!vmas.covers(0x10000, 0x14000));
+        assert!(vmas.protect(0x10000, 0x14000, PROT_READ).is_err());
+        assert_eq!(vmas.find_free(0x10000, 0x14000, 0x2000), Some(0x11000));
+    }
+}
@@ -1,2 +1,15 @@
-// Virtual Memory Manager Stub
+// Virtual Memory Manager
+use crate::sched::queue::current_task;
+
 pub fn init() {}
+
+/// Change the protection of [addr, addr + len) in the current task
+/// Updates the VMAs, then the PTEs of pages already present.
+pub fn protect_current(addr: usize, len: usize, prot: u32) -> Result<(), &'static str> {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    
+    task.vmas.protect(addr, addr + len, prot)?;
+    crate::mm::paging::protect_user_range(addr as u64, len as u64, prot);
+    Ok(())
+}
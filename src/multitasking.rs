@@ -1,4 +1,5 @@
 use core::arch::global_asm;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 // Assembly for Context Switching
 // arguments: rdi = new_stack_pointer, rsi = old_stack_pointer_ptr
@@ -20,6 +21,7 @@ switch_context:
     mov rsp, rdi
     
     // 4. Restore Callee-Saved Registers from new stack
+    //    (init_stack builds fresh frames in exactly this order)
     pop r15
     pop r14
     pop r13
@@ -35,76 +37,165 @@ extern "C" {
     pub fn switch_context(new_sp: usize, old_sp_ptr: *mut usize);
 }
 
-// Function that newly created threads "return" to.
-// This function mimics the end of an interrupt handler.
-// It pops the TrapFrame and Irets.
-// But wait, if we are just starting, we can just call the function?
-// In strict preemption, we want `iretq` to enable interrupts atomically with jump.
-// So we should construct a TrapFrame.
+/// TSC when the timer handler began its switch, 0 if none is in flight
+static SWITCH_START: AtomicU64 = AtomicU64::new(0);
+static SWITCH_COUNT: AtomicU64 = AtomicU64::new(0);
+static SWITCH_CYCLES: AtomicU64 = AtomicU64::new(0);
+static SWITCH_MIN: AtomicU64 = AtomicU64::new(u64::MAX);
+static SWITCH_MAX: AtomicU64 = AtomicU64::new(0);
 
-use x86_64::structures::idt::InterruptStackFrameValue;
-use core::mem::size_of;
+/// Context-switch latency in TSC cycles, from the timer handler calling
+/// `switch_context` to the incoming context running again
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchStats {
+    pub count: u64,
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl SwitchStats {
+    pub fn avg(&self) -> u64 {
+        self.total.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Mark the start of a measured switch (just before `switch_context`)
+pub fn switch_started() {
+    SWITCH_START.store(rdtsc(), Ordering::Relaxed);
+}
+
+/// The incoming context is running: account the switch, if one was measured
+/// Called right after `switch_context` returns and first thing in a fresh
+/// context, so only switches started with `switch_started` count.
+pub fn switch_finished() {
+    let start = SWITCH_START.swap(0, Ordering::Relaxed);
+    if start == 0 {
+        return;
+    }
+    let cycles = rdtsc().wrapping_sub(start);
+    SWITCH_COUNT.fetch_add(1, Ordering::Relaxed);
+    SWITCH_CYCLES.fetch_add(cycles, Ordering::Relaxed);
+    SWITCH_MIN.fetch_min(cycles, Ordering::Relaxed);
+    SWITCH_MAX.fetch_max(cycles, Ordering::Relaxed);
+}
+
+/// Latency figures so far (min is 0 until the first switch)
+pub fn switch_stats() -> SwitchStats {
+    let count = SWITCH_COUNT.load(Ordering::Relaxed);
+    SwitchStats {
+        count,
+        total: SWITCH_CYCLES.load(Ordering::Relaxed),
+        min: if count == 0 { 0 } else { SWITCH_MIN.load(Ordering::Relaxed) },
+        max: SWITCH_MAX.load(Ordering::Relaxed),
+    }
+}
+
+/// Size of the initial frame built by `init_stack`:
+/// an alignment padding word, the trampoline return address and 6
+/// callee-saved registers
+pub const INIT_FRAME_SIZE: usize = 8 * 8;
+pub const MIN_STACK_SIZE: usize = INIT_FRAME_SIZE + 16;
+
+/// Builds a downward-growing stack frame inside a borrowed slice.
+///
+/// The cursor always points at the last value pushed (like RSP), and never
+/// moves below the start of the slice.
+pub struct StackBuilder<'a> {
+    bottom: *mut u8,
+    cursor: *mut u8,
+    _stack: core::marker::PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> StackBuilder<'a> {
+    /// Start building at the 16-byte aligned top of `stack`
+    pub fn new(stack: &'a mut [u8]) -> Self {
+        let bottom = stack.as_mut_ptr();
+        let top = bottom as usize + stack.len();
+        let aligned = top & !0xF;
+        Self {
+            bottom,
+            // Stay inside the slice's provenance: offset from `bottom`
+            cursor: unsafe { bottom.add(aligned - bottom as usize) },
+            _stack: core::marker::PhantomData,
+        }
+    }
+
+    /// Bytes still available below the cursor
+    pub fn remaining(&self) -> usize {
+        self.cursor as usize - self.bottom as usize
+    }
+
+    pub fn push_u64(&mut self, val: u64) -> Result<(), &'static str> {
+        if self.remaining() < 8 {
+            return Err("Stack overflow while building frame");
+        }
+        unsafe {
+            self.cursor = self.cursor.sub(8);
+            self.cursor.cast::<u64>().write_unaligned(val);
+        }
+        Ok(())
+    }
+
+    pub fn push_usize(&mut self, val: usize) -> Result<(), &'static str> {
+        self.push_u64(val as u64)
+    }
+
+    /// Final stack pointer (address of the last pushed value)
+    pub fn finish(self) -> usize {
+        self.cursor as usize
+    }
+}
 
 /// Initialize a process stack
-pub fn init_stack(stack: &mut [u8], entry_point: usize, arg0: usize) -> usize {
-    let stack_top = stack.as_ptr() as usize + stack.len();
+/// Returns the initial stack pointer to hand to `switch_context`.
+///
+/// New threads never had a TrapFrame, so instead of an `iretq` frame we build
+/// exactly what `switch_context` expects to pop, and let its `ret` land in
+/// `trampoline`, which calls `entry(arg)`.
+///
+/// Layout (high -> low), mirroring `switch_context`'s push order:
+/// ```text
+/// [padding]     <- 16-byte aligned top
+/// [trampoline]  <- popped by `ret`, leaving RSP = 8 (mod 16) as at any
+///                  function entry
+/// [RBX]
+/// [RBP]
+/// [R12]         = entry_point
+/// [R13]         = arg0
+/// [R14]
+/// [R15]         <- returned SP, popped first
+/// ```
+pub fn init_stack(stack: &mut [u8], entry_point: usize, arg0: usize) -> Result<usize, &'static str> {
+    if stack.len() < MIN_STACK_SIZE {
+        return Err("Stack too small for initial frame");
+    }
     
-    // We need to align stack to 16 bytes
-    let mut sp = stack_top & !0xF;
+    let mut builder = StackBuilder::new(stack);
     
-    unsafe {
-        let ptr = sp as *mut u8;
-        
-        // 1. Trap Context (setup for iretq)
-        // Layout: SS, RSP, RFLAGS, CS, RIP
-        sp -= 8; *ptr.add(sp - stack.as_ptr() as usize).cast::<u64>() = 0x0; // SS (0 on x86_64 mostly ignored or handled by iret?) 
-                                                                             // Actually in 64-bit mode, SS/CS are specific selectors.
-                                                                             // We need valid Selectors. UEFI CS=0x38?
-        // This is getting tricky because we need valid CS/SS from GDT.
-        // UEFI GDT is dynamic.
-        // Alternative: New threads start by `call function`.
-        // Only *interrupted* threads have TrapFrame.
-        // So `switch_context` returns to `trampoline` which calls `entry(arg)`.
-        
-        // Let's use the Trampoline approach for simplicity.
-        // Stack: [Trampoline Addr, RBX, RBP, R12, R13, R14, R15]
-        // When switch_context pops everything and rets, it rets to Trampoline.
-        // Trampoline calls `entry(arg)`.
-        
-        // Push Switch Context
-        sp -= 8; *ptr.add(sp - stack.as_ptr() as usize).cast::<usize>() = trampoline as usize; // Return Address (RIP)
-        
-        // Push R15..RBX (6 registers)
-        sp -= 8 * 6;
-        
-        // We can pass `entry_point` and `arg0` via registers R12, R13?
-        // System V ABI: rbx, rbp, r12-r15 are callee saved.
-        // We can use them to store initial state.
-        
-        // Let's store `entry_point` in R12, `arg0` in R13.
-        let regs_ptr = ptr.add(sp - stack.as_ptr() as usize).cast::<usize>();
-        // Stack order: RBX, RBP, R12, R13, R14, R15 (Pushed order)
-        // Wait, pop order is reverse: R15, R14, R13, R12, RBP, RBX.
-        // switch_context pops R15 first.
-        // So memory order (low to high): R15 ... RBX.
-        // No, PUSH decrements SP.
-        // Pushing: RBX (high), ..., R15 (low).
-        // Popping: R15 (low), ..., RBX (high).
-        
-        // So at `sp`: R15
-        *regs_ptr.add(0) = 0; // R15
-        *regs_ptr.add(1) = 0; // R14
-        *regs_ptr.add(2) = arg0; // R13
-        *regs_ptr.add(3) = entry_point; // R12
-        *regs_ptr.add(4) = 0; // RBP
-        *regs_ptr.add(5) = 0; // RBX
-    }
+    // Keeps trampoline's RSP at 8 (mod 16), as if it had been `call`ed
+    builder.push_u64(0)?;
+    
+    // Return Address (RIP)
+    builder.push_usize(trampoline as *const () as usize)?;
+    
+    // Callee-saved registers, in the same order switch_context pushes them
+    builder.push_u64(0)?;                   // RBX
+    builder.push_u64(0)?;                   // RBP
+    builder.push_usize(entry_point)?;       // R12
+    builder.push_usize(arg0)?;              // R13
+    builder.push_u64(0)?;                   // R14
+    builder.push_u64(0)?;                   // R15
     
-    sp
+    Ok(builder.finish())
 }
 
 #[no_mangle]
-extern "C" fn trampoline() -> ! {
+pub extern "C" fn trampoline() -> ! {
     // We are now running on the new stack!
     // Recover arguments from R12, R13 (which were restored by switch_context)
     let entry: extern "C" fn(usize) -> !;
@@ -117,9 +208,13 @@ extern "C" fn trampoline() -> ! {
             out(reg) entry,
             out(reg) arg
         );
+        switch_finished();
         
-        // Create a proper stack frame for backtraces?
-        // x86_64::instructions::interrupts::enable();
+        // We got here through switch_context from the timer handler, which
+        // runs with IF clear and already sent its EOI. There is no iretq on
+        // this path to restore IF, so turn interrupts back on ourselves or
+        // the timer never fires again.
+        x86_64::instructions::interrupts::enable();
         
         entry(arg);
     }
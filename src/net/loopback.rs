@@ -0,0 +1,36 @@
+//! Loopback Interface
+//!
+//! Transmitting queues a segment; `poll` delivers queued segments to TCP
+//! until the queue is empty. Replies generated during delivery are queued
+//! and handled by the same loop, so callers only need to poll once they
+//! have dropped their socket locks.
+
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use super::tcp::{self, Segment};
+
+static QUEUE: Mutex<VecDeque<Segment>> = Mutex::new(VecDeque::new());
+
+/// Set while some caller is draining the queue
+static POLLING: AtomicBool = AtomicBool::new(false);
+
+/// Queue a segment for delivery
+pub fn transmit(segment: Segment) {
+    QUEUE.lock().push_back(segment);
+}
+
+/// Deliver everything queued (a nested call leaves it to the outer loop)
+pub fn poll() {
+    if POLLING.swap(true, Ordering::Acquire) {
+        return;
+    }
+    loop {
+        let next = QUEUE.lock().pop_front();
+        match next {
+            Some(segment) => tcp::input(segment),
+            None => break,
+        }
+    }
+    POLLING.store(false, Ordering::Release);
+}
@@ -0,0 +1,66 @@
+//! Networking
+//!
+//! A loopback-only IPv4 stack. TCP segments are passed around as structs
+//! rather than wire-format packets, and the loopback interface is the only
+//! device, so every address must be in 127.0.0.0/8.
+
+pub mod loopback; // 127.0.0.1 interface
+pub mod tcp;      // TCP state machine and sockets
+
+use core::fmt;
+
+pub const AF_INET: usize = 2;
+
+/// The loopback interface's address
+pub const LOOPBACK: [u8; 4] = [127, 0, 0, 1];
+
+/// An IPv4 endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketAddr {
+    pub ip: [u8; 4],
+    pub port: u16,
+}
+
+impl SocketAddr {
+    pub const fn new(ip: [u8; 4], port: u16) -> Self {
+        Self { ip, port }
+    }
+
+    /// Reachable through the loopback interface (INADDR_ANY binds there too)
+    pub fn is_local(&self) -> bool {
+        self.ip[0] == 127 || self.ip == [0; 4]
+    }
+}
+
+impl fmt::Display for SocketAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d] = self.ip;
+        write!(f, "{}.{}.{}.{}:{}", a, b, c, d, self.port)
+    }
+}
+
+/// Socket operation failures, mapped to errno by the syscall layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError {
+    AddrInUse,
+    AddrNotAvailable,
+    NetworkUnreachable,
+    ConnectionRefused,
+    ConnectionReset,
+    AlreadyConnected,
+    NotConnected,
+    /// Wrong state for the call (listen on a connected socket, ...)
+    InvalidState,
+    WouldBlock,
+}
+
+/// A network card that moves raw Ethernet frames
+pub trait EthernetDevice: Send {
+    fn mac(&self) -> [u8; 6];
+
+    /// Queue a frame (without FCS) for transmission
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), &'static str>;
+
+    /// Copy out the next received frame, if any, returning its length
+    fn recv_frame(&mut self, buf: &mut [u8]) -> Option<usize>;
+}
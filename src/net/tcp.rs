@@ -0,0 +1,575 @@
+//! TCP
+//!
+//! Just enough of RFC 793 for local clients and servers: the three-way
+//! handshake, in-order data with window-based flow control, FIN for EOF
+//! and RST for refused or dead connections. Loopback never loses or
+//! reorders segments, so there are no retransmission timers; segments
+//! that don't line up with `rcv_nxt` are simply dropped.
+//!
+//! Closing a socket sends FIN and forgets the connection at once (no
+//! FIN_WAIT/TIME_WAIT); anything the peer sends afterwards draws a RST.
+//!
+//! Lock order: a socket's `tcb` before `BINDINGS`.
+
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use spin::Mutex;
+use crate::fs::vfs::{FileMode, FileTimes, FileType, FsError, Inode, Metadata, POLLERR, POLLHUP, POLLIN, POLLOUT};
+use crate::sched::wait::WaitQueue;
+use super::{loopback, NetError, SocketAddr, LOOPBACK};
+
+pub const FIN: u8 = 0x01;
+pub const SYN: u8 = 0x02;
+pub const RST: u8 = 0x04;
+pub const ACK: u8 = 0x10;
+
+/// Receive buffer size, and so the largest window we advertise
+const RX_CAPACITY: usize = 16 * 1024;
+/// Largest payload per segment
+const MSS: usize = 1460;
+/// Most connections a listener queues for accept()
+const BACKLOG_MAX: usize = 128;
+/// First port handed out to sockets that connect without binding
+const EPHEMERAL_START: u16 = 49152;
+
+/// A TCP segment on the loopback wire
+pub struct Segment {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub seq: u32,
+    pub ack: u32,
+    pub flags: u8,
+    pub window: u16,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    /// The peer has sent FIN; we may still send
+    CloseWait,
+}
+
+/// Transmission control block
+struct Tcb {
+    state: State,
+    local: Option<SocketAddr>,
+    remote: Option<SocketAddr>,
+    /// Oldest unacknowledged and next sequence number to send
+    snd_una: u32,
+    snd_nxt: u32,
+    /// Peer's advertised window
+    snd_wnd: u32,
+    /// Next sequence number expected from the peer
+    rcv_nxt: u32,
+    rx: VecDeque<u8>,
+    /// Peer sent FIN: reads hit EOF once `rx` drains
+    rx_closed: bool,
+    /// Torn down by a RST
+    reset: bool,
+    /// Listener: connections waiting for accept(), half-open ones included
+    backlog: VecDeque<Arc<Socket>>,
+    backlog_max: usize,
+    /// Half-open connection: the listener to wake once established
+    listener: Weak<Socket>,
+}
+
+impl Tcb {
+    const fn new() -> Self {
+        Self {
+            state: State::Closed,
+            local: None,
+            remote: None,
+            snd_una: 0,
+            snd_nxt: 0,
+            snd_wnd: 0,
+            rcv_nxt: 0,
+            rx: VecDeque::new(),
+            rx_closed: false,
+            reset: false,
+            backlog: VecDeque::new(),
+            backlog_max: 0,
+            listener: Weak::new(),
+        }
+    }
+
+    fn rx_window(&self) -> u16 {
+        (RX_CAPACITY - self.rx.len()).min(u16::MAX as usize) as u16
+    }
+
+    /// Room the peer has left for unacknowledged data
+    fn tx_window(&self) -> usize {
+        self.snd_wnd.saturating_sub(self.snd_nxt.wrapping_sub(self.snd_una)) as usize
+    }
+
+    /// Queue a segment to the peer from the current sequence state
+    fn send(&mut self, flags: u8, payload: Vec<u8>) {
+        let (Some(src), Some(dst)) = (self.local, self.remote) else { return };
+        let len = payload.len() as u32 + u32::from(flags & (SYN | FIN) != 0);
+        loopback::transmit(Segment {
+            src,
+            dst,
+            seq: self.snd_nxt,
+            ack: self.rcv_nxt,
+            flags,
+            window: self.rx_window(),
+            payload,
+        });
+        self.snd_nxt = self.snd_nxt.wrapping_add(len);
+    }
+}
+
+/// A bound address, for demultiplexing and port allocation
+struct Binding {
+    local: SocketAddr,
+    /// None for listeners and unconnected sockets
+    remote: Option<SocketAddr>,
+    socket: Weak<Socket>,
+}
+
+static BINDINGS: Mutex<Vec<Binding>> = Mutex::new(Vec::new());
+
+/// Connection state shared by the fd and the stack
+struct Socket {
+    tcb: Mutex<Tcb>,
+    /// Woken on every state, data or window change
+    wait: WaitQueue,
+}
+
+impl Socket {
+    fn new(tcb: Tcb) -> Arc<Self> {
+        Arc::new(Self { tcb: Mutex::new(tcb), wait: WaitQueue::new() })
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let tcb = self.tcb.get_mut();
+        if matches!(tcb.state, State::SynReceived | State::Established | State::CloseWait) {
+            tcb.send(FIN | ACK, Vec::new());
+        }
+        // Our own Weak is dead now, so this drops our binding too
+        BINDINGS.lock().retain(|b| b.socket.strong_count() > 0);
+        loopback::poll();
+    }
+}
+
+fn initial_sequence() -> u32 {
+    crate::rand::next_u64() as u32
+}
+
+/// Claim `addr` (port 0 = pick an ephemeral port) for `socket`
+fn bind_addr(socket: &Arc<Socket>, mut addr: SocketAddr) -> Result<SocketAddr, NetError> {
+    let mut bindings = BINDINGS.lock();
+    bindings.retain(|b| b.socket.strong_count() > 0);
+    let in_use = |port: u16| bindings.iter().any(|b| b.local.port == port);
+    if addr.port == 0 {
+        addr.port = (EPHEMERAL_START..=u16::MAX)
+            .find(|&p| !in_use(p))
+            .ok_or(NetError::AddrInUse)?;
+    } else if in_use(addr.port) {
+        return Err(NetError::AddrInUse);
+    }
+    bindings.push(Binding { local: addr, remote: None, socket: Arc::downgrade(socket) });
+    Ok(addr)
+}
+
+/// Record the peer of a connecting socket's binding
+fn set_remote(socket: &Arc<Socket>, remote: SocketAddr) {
+    let mut bindings = BINDINGS.lock();
+    if let Some(b) = bindings.iter_mut().find(|b| b.socket.as_ptr() == Arc::as_ptr(socket)) {
+        b.remote = Some(remote);
+    }
+}
+
+/// Socket a segment belongs to: its connection, else a listener on the port
+fn demux(segment: &Segment) -> Option<Arc<Socket>> {
+    let bindings = BINDINGS.lock();
+    let matches_local = |b: &Binding| {
+        b.local.port == segment.dst.port && (b.local.ip == segment.dst.ip || b.local.ip == [0; 4])
+    };
+    bindings
+        .iter()
+        .find(|b| matches_local(b) && b.remote == Some(segment.src))
+        .or_else(|| bindings.iter().find(|b| matches_local(b) && b.remote.is_none()))
+        .and_then(|b| b.socket.upgrade())
+}
+
+/// Handle a segment arriving on the loopback interface
+pub fn input(segment: Segment) {
+    match demux(&segment) {
+        Some(socket) => socket.input(segment),
+        None if segment.flags & RST == 0 => reset_reply(&segment),
+        None => {}
+    }
+}
+
+/// RST for a segment nobody wants
+fn reset_reply(segment: &Segment) {
+    let len = segment.payload.len() as u32 + u32::from(segment.flags & (SYN | FIN) != 0);
+    let (seq, ack, flags) = if segment.flags & ACK != 0 {
+        (segment.ack, 0, RST)
+    } else {
+        (0, segment.seq.wrapping_add(len), RST | ACK)
+    };
+    loopback::transmit(Segment {
+        src: segment.dst,
+        dst: segment.src,
+        seq,
+        ack,
+        flags,
+        window: 0,
+        payload: Vec::new(),
+    });
+}
+
+impl Socket {
+    fn input(self: &Arc<Self>, segment: Segment) {
+        let mut tcb = self.tcb.lock();
+
+        if segment.flags & RST != 0 {
+            if tcb.state != State::Listen {
+                tcb.state = State::Closed;
+                tcb.reset = true;
+            }
+            drop(tcb);
+            self.wait.wake_all();
+            return;
+        }
+
+        match tcb.state {
+            State::Listen => {
+                if segment.flags & SYN != 0 && tcb.backlog.len() < tcb.backlog_max {
+                    drop(tcb);
+                    self.open_child(&segment);
+                }
+                return;
+            }
+            State::SynSent => {
+                if segment.flags & (SYN | ACK) != SYN | ACK || segment.ack != tcb.snd_nxt {
+                    return;
+                }
+                tcb.snd_una = segment.ack;
+                tcb.snd_wnd = segment.window as u32;
+                tcb.rcv_nxt = segment.seq.wrapping_add(1);
+                tcb.state = State::Established;
+                tcb.send(ACK, Vec::new());
+                drop(tcb);
+                self.wait.wake_all();
+                return;
+            }
+            State::SynReceived => {
+                if segment.flags & ACK == 0 || segment.ack != tcb.snd_nxt {
+                    return;
+                }
+                tcb.state = State::Established;
+                // Already on the listener's backlog; let accept() take it now
+                if let Some(listener) = tcb.listener.upgrade() {
+                    listener.wait.wake_all();
+                }
+            }
+            State::Established | State::CloseWait => {}
+            State::Closed => return,
+        }
+
+        if segment.flags & ACK != 0 {
+            tcb.snd_una = segment.ack;
+            tcb.snd_wnd = segment.window as u32;
+        }
+
+        let mut need_ack = false;
+        if segment.seq == tcb.rcv_nxt {
+            if !segment.payload.is_empty() && tcb.state == State::Established {
+                // The sender honours our window, so this always fits
+                let take = segment.payload.len().min(RX_CAPACITY - tcb.rx.len());
+                tcb.rx.extend(&segment.payload[..take]);
+                tcb.rcv_nxt = tcb.rcv_nxt.wrapping_add(take as u32);
+                need_ack = true;
+            }
+            if segment.flags & FIN != 0 && tcb.state == State::Established {
+                tcb.rcv_nxt = tcb.rcv_nxt.wrapping_add(1);
+                tcb.rx_closed = true;
+                tcb.state = State::CloseWait;
+                need_ack = true;
+            }
+        } else if !segment.payload.is_empty() {
+            // Out of order: re-announce what we expect
+            need_ack = true;
+        }
+        if need_ack {
+            tcb.send(ACK, Vec::new());
+        }
+        drop(tcb);
+        self.wait.wake_all();
+    }
+
+    /// A SYN hit this listener: start a half-open connection and answer it
+    fn open_child(self: &Arc<Self>, syn: &Segment) {
+        let iss = initial_sequence();
+        let mut tcb = Tcb::new();
+        tcb.state = State::SynReceived;
+        tcb.local = Some(syn.dst);
+        tcb.remote = Some(syn.src);
+        tcb.snd_una = iss;
+        tcb.snd_nxt = iss;
+        tcb.snd_wnd = syn.window as u32;
+        tcb.rcv_nxt = syn.seq.wrapping_add(1);
+        tcb.listener = Arc::downgrade(self);
+        tcb.send(SYN | ACK, Vec::new());
+        let child = Socket::new(tcb);
+        BINDINGS.lock().push(Binding {
+            local: syn.dst,
+            remote: Some(syn.src),
+            socket: Arc::downgrade(&child),
+        });
+        // The backlog owns half-open children too; accept() skips them
+        // until their handshake completes
+        self.tcb.lock().backlog.push_back(child);
+    }
+}
+
+/// A TCP socket descriptor
+pub struct TcpSocket(Arc<Socket>);
+
+impl TcpSocket {
+    pub fn new() -> Self {
+        Self(Socket::new(Tcb::new()))
+    }
+
+    fn state(&self) -> State {
+        self.0.tcb.lock().state
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.0.tcb.lock().local
+    }
+
+    pub fn bind(&self, addr: SocketAddr) -> Result<(), NetError> {
+        if !addr.is_local() {
+            return Err(NetError::AddrNotAvailable);
+        }
+        let mut tcb = self.0.tcb.lock();
+        if tcb.local.is_some() {
+            return Err(NetError::InvalidState);
+        }
+        tcb.local = Some(bind_addr(&self.0, addr)?);
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: usize) -> Result<(), NetError> {
+        let mut tcb = self.0.tcb.lock();
+        match tcb.state {
+            State::Closed | State::Listen => {}
+            _ => return Err(NetError::InvalidState),
+        }
+        if tcb.local.is_none() {
+            tcb.local = Some(bind_addr(&self.0, SocketAddr::new([0; 4], 0))?);
+        }
+        tcb.state = State::Listen;
+        tcb.backlog_max = backlog.clamp(1, BACKLOG_MAX);
+        Ok(())
+    }
+
+    /// Take the next established connection, returning it and its peer
+    pub fn accept(&self, nonblock: bool) -> Result<(TcpSocket, SocketAddr), NetError> {
+        loop {
+            {
+                let mut tcb = self.0.tcb.lock();
+                if tcb.state != State::Listen {
+                    return Err(NetError::InvalidState);
+                }
+                let ready = tcb.backlog.iter().position(|c| c.tcb.lock().state != State::SynReceived);
+                if let Some(i) = ready {
+                    let child = tcb.backlog.remove(i).unwrap();
+                    let peer = child.tcb.lock().remote.unwrap_or(SocketAddr::new([0; 4], 0));
+                    return Ok((TcpSocket(child), peer));
+                }
+            }
+            if nonblock {
+                return Err(NetError::WouldBlock);
+            }
+            self.0.wait.wait_until(|| self.readiness() & POLLIN != 0);
+        }
+    }
+
+    pub fn connect(&self, addr: SocketAddr) -> Result<(), NetError> {
+        if !addr.is_local() {
+            return Err(NetError::NetworkUnreachable);
+        }
+        // Connecting to 0.0.0.0 means this host
+        let addr = if addr.ip == [0; 4] { SocketAddr::new(LOOPBACK, addr.port) } else { addr };
+        {
+            let mut tcb = self.0.tcb.lock();
+            match tcb.state {
+                State::Closed if !tcb.reset => {}
+                State::Closed | State::Listen => return Err(NetError::InvalidState),
+                _ => return Err(NetError::AlreadyConnected),
+            }
+            let local = match tcb.local {
+                Some(local) => local,
+                None => bind_addr(&self.0, SocketAddr::new(LOOPBACK, 0))?,
+            };
+            // An INADDR_ANY binding talks from the loopback address
+            let local = if local.ip == [0; 4] { SocketAddr::new(LOOPBACK, local.port) } else { local };
+            tcb.local = Some(local);
+            tcb.remote = Some(addr);
+            set_remote(&self.0, addr);
+            let iss = initial_sequence();
+            tcb.snd_una = iss;
+            tcb.snd_nxt = iss;
+            tcb.state = State::SynSent;
+            tcb.send(SYN, Vec::new());
+        }
+        loopback::poll();
+
+        self.0.wait.wait_until(|| self.state() != State::SynSent);
+        let tcb = self.0.tcb.lock();
+        match tcb.state {
+            State::Established | State::CloseWait => Ok(()),
+            _ => Err(NetError::ConnectionRefused),
+        }
+    }
+
+    /// Send all of `buf` (blocking) or what the window allows (nonblock)
+    pub fn send(&self, buf: &[u8], nonblock: bool) -> Result<usize, NetError> {
+        let mut sent = 0;
+        loop {
+            {
+                let mut tcb = self.0.tcb.lock();
+                if tcb.reset {
+                    return Err(NetError::ConnectionReset);
+                }
+                if !matches!(tcb.state, State::Established | State::CloseWait) {
+                    return Err(NetError::NotConnected);
+                }
+                while sent < buf.len() && tcb.tx_window() > 0 {
+                    let n = (buf.len() - sent).min(MSS).min(tcb.tx_window());
+                    tcb.send(ACK, buf[sent..sent + n].to_vec());
+                    sent += n;
+                }
+            }
+            loopback::poll();
+            if sent == buf.len() {
+                return Ok(sent);
+            }
+            if nonblock {
+                return if sent > 0 { Ok(sent) } else { Err(NetError::WouldBlock) };
+            }
+            self.0.wait.wait_until(|| self.readiness() & (POLLOUT | POLLERR) != 0);
+        }
+    }
+
+    /// Receive what is buffered, blocking while empty unless `nonblock`; 0 = EOF
+    pub fn recv(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, NetError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            {
+                let mut tcb = self.0.tcb.lock();
+                if !tcb.rx.is_empty() {
+                    let was_full = (tcb.rx_window() as usize) < MSS;
+                    let n = buf.len().min(tcb.rx.len());
+                    for (dst, src) in buf.iter_mut().zip(tcb.rx.drain(..n)) {
+                        *dst = src;
+                    }
+                    // Let a sender stalled on our window carry on
+                    if was_full {
+                        tcb.send(ACK, Vec::new());
+                    }
+                    drop(tcb);
+                    loopback::poll();
+                    return Ok(n);
+                }
+                if tcb.rx_closed {
+                    return Ok(0);
+                }
+                if tcb.reset {
+                    return Err(NetError::ConnectionReset);
+                }
+                if !matches!(tcb.state, State::Established | State::SynSent | State::SynReceived) {
+                    return Err(NetError::NotConnected);
+                }
+            }
+            if nonblock {
+                return Err(NetError::WouldBlock);
+            }
+            self.0.wait.wait_until(|| self.readiness() & (POLLIN | POLLHUP) != 0);
+        }
+    }
+}
+
+fn fs_error(err: NetError) -> FsError {
+    match err {
+        NetError::WouldBlock => FsError::WouldBlock,
+        NetError::ConnectionReset => FsError::BrokenPipe,
+        NetError::NotConnected | NetError::InvalidState => FsError::InvalidInput,
+        _ => FsError::IOError,
+    }
+}
+
+impl Inode for TcpSocket {
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> usize {
+        self.recv(buf, true).unwrap_or(0)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> usize {
+        self.send(buf, true).unwrap_or(0)
+    }
+
+    fn read(&self, _offset: u64, buf: &mut [u8], nonblock: bool) -> Result<usize, FsError> {
+        self.recv(buf, nonblock).map_err(fs_error)
+    }
+
+    fn write(&self, _offset: u64, buf: &[u8], nonblock: bool) -> Result<usize, FsError> {
+        self.send(buf, nonblock).map_err(fs_error)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ | FileMode::WRITE),
+            file_type: FileType::Socket,
+            times: FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn readiness(&self) -> u16 {
+        let tcb = self.0.tcb.lock();
+        if tcb.reset {
+            return POLLERR | POLLHUP;
+        }
+        match tcb.state {
+            State::Listen => {
+                let ready = tcb.backlog.iter().any(|c| c.tcb.lock().state != State::SynReceived);
+                if ready { POLLIN } else { 0 }
+            }
+            State::Established | State::CloseWait => {
+                let mut events = 0;
+                if !tcb.rx.is_empty() || tcb.rx_closed {
+                    events |= POLLIN;
+                }
+                if tcb.rx_closed {
+                    events |= POLLHUP;
+                }
+                if tcb.tx_window() > 0 {
+                    events |= POLLOUT;
+                }
+                events
+            }
+            _ => 0,
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
@@ -0,0 +1,76 @@
+//! Kernel Panic Handler
+//!
+//! Replaces the uefi_services handler. On x86_64 it writes the message, the
+//! faulting RIP/RSP (when an exception handler recorded them) and a
+//! frame-pointer backtrace straight to COM1, bypassing the logger.
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+// Set by exception handlers just before they panic
+static FAULT_RIP: AtomicU64 = AtomicU64::new(0);
+static FAULT_RSP: AtomicU64 = AtomicU64::new(0);
+
+/// Remember where a CPU exception happened so the panic dump can show it
+pub fn record_fault(rip: u64, rsp: u64) {
+    FAULT_RIP.store(rip, Ordering::Relaxed);
+    FAULT_RSP.store(rsp, Ordering::Relaxed);
+}
+
+#[cfg_attr(not(test), panic_handler)]
+fn panic(info: &PanicInfo) -> ! {
+    #[cfg(target_arch = "x86_64")]
+    x86_64::instructions::interrupts::disable();
+    
+    // A panic while dumping: don't recurse, just stop
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        #[cfg(target_arch = "x86_64")]
+        dump(info);
+        
+        #[cfg(target_arch = "aarch64")]
+        log::error!("[PANIC] {}", info);
+    }
+    
+    loop {
+        #[cfg(target_arch = "x86_64")]
+        x86_64::instructions::hlt();
+        #[cfg(target_arch = "aarch64")]
+        unsafe { core::arch::asm!("wfi"); }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn dump(info: &PanicInfo) {
+    use core::fmt::Write;
+    use crate::arch::x86_64::backtrace;
+    use crate::drivers::serial::SerialWriter;
+    
+    let mut out = SerialWriter;
+    let _ = writeln!(out, "\n[PANIC] {}", info);
+    
+    let fault_rip = FAULT_RIP.load(Ordering::Relaxed);
+    if fault_rip != 0 {
+        let _ = writeln!(out, "  fault: RIP=0x{:016x} RSP=0x{:016x}",
+            fault_rip, FAULT_RSP.load(Ordering::Relaxed));
+    }
+    
+    let rbp = backtrace::current_rbp();
+    let _ = writeln!(out, "  panic: RSP=0x{:016x} RBP=0x{:016x}", backtrace::current_rsp(), rbp);
+    
+    // Without the `symbols` feature, print RVAs so they can be fed to
+    // llvm-symbolizer/addr2line against aether.efi (add its PE ImageBase).
+    let base = backtrace::image_base();
+    let _ = writeln!(out, "  backtrace (image base 0x{:x}):", base);
+    unsafe {
+        backtrace::walk(rbp, |depth, addr| {
+            let _ = write!(out, "    #{:<2} 0x{:016x} (rva 0x{:x})", depth, addr, addr.wrapping_sub(base));
+            #[cfg(feature = "symbols")]
+            if let Some((name, offset)) = crate::symbols::resolve(addr) {
+                let _ = write!(out, " {}+0x{:x}", name, offset);
+            }
+            let _ = writeln!(out);
+        });
+    }
+}
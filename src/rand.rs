@@ -0,0 +1,64 @@
+//! Kernel Random Numbers
+//!
+//! A xorshift64* generator seeded from RDRAND when the CPU has it, and
+//! otherwise from the cycle counter. Every call also folds in the current
+//! cycle count, so output depends on timing as well as the seed. This is
+//! good enough for seeds and ASLR-style jitter, not for cryptography.
+
+use spin::Mutex;
+
+/// Generator state; 0 means not seeded yet
+static STATE: Mutex<u64> = Mutex::new(0);
+
+/// Free-running cycle counter (TSC / CNTVCT_EL0)
+fn cycles() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::x86_64::_rdtsc()
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let val: u64;
+        unsafe { core::arch::asm!("mrs {}, cntvct_el0", out(reg) val) };
+        val
+    }
+}
+
+/// A hardware random value, if the CPU provides one
+fn hardware_seed() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // RDRAND may transiently fail; Intel recommends 10 retries
+        let rdrand = x86_64::instructions::random::RdRand::new()?;
+        (0..10).find_map(|_| rdrand.get_u64())
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        None
+    }
+}
+
+/// Next 64 random bits
+pub fn next_u64() -> u64 {
+    let mut state = STATE.lock();
+    if *state == 0 {
+        *state = hardware_seed().unwrap_or_else(cycles) | 1;
+    }
+    let mut x = *state ^ cycles().rotate_left(32);
+    if x == 0 {
+        x = 0x9E37_79B9_7F4A_7C15;
+    }
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+}
+
+/// Fill `buf` with random bytes
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = next_u64().to_ne_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
@@ -0,0 +1,72 @@
+//! Kernel Tick Clock
+//!
+//! Monotonic tick counter driven by the timer interrupt. The tick rate
+//! can change at runtime (`interrupts::set_timer_hz`), so time is kept in
+//! nanoseconds alongside the count, each tick adding the period in force.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Timer interrupt frequency until something reprograms it
+pub const DEFAULT_HZ: u64 = 100;
+
+/// Rate of the clock_t values user space sees (times(2)), whatever the
+/// timer runs at
+pub const USER_HZ: u64 = 100;
+
+/// Length of a scheduler time slice
+pub const QUANTUM_MS: u64 = 10;
+
+/// Ticks since the timer was started
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Nanoseconds since the timer was started
+static NOW_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Nanoseconds per tick at the current rate
+static TICK_NS: AtomicU64 = AtomicU64::new(1_000_000_000 / DEFAULT_HZ);
+
+/// Advance the clock by one tick (called from the timer interrupt)
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    NOW_NS.fetch_add(tick_ns(), Ordering::Relaxed);
+}
+
+/// Record the period the timer now fires at
+pub fn set_tick_ns(ns: u64) {
+    TICK_NS.store(ns.max(1), Ordering::Relaxed);
+}
+
+/// Nanoseconds per tick
+pub fn tick_ns() -> u64 {
+    TICK_NS.load(Ordering::Relaxed)
+}
+
+/// Current tick rate
+pub fn hz() -> u64 {
+    1_000_000_000 / tick_ns()
+}
+
+/// Ticks since boot
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Convert nanoseconds to ticks, rounding up so sleeps are never short
+pub fn ns_to_ticks(ns: u64) -> u64 {
+    ns.div_ceil(tick_ns())
+}
+
+/// Ticks in a time slice; at least one, whatever the rate
+pub fn quantum_ticks() -> u64 {
+    ns_to_ticks(QUANTUM_MS * 1_000_000).max(1)
+}
+
+/// Nanoseconds to clock_t units (1/USER_HZ seconds)
+pub fn ns_to_clock_t(ns: u64) -> u64 {
+    ns / (1_000_000_000 / USER_HZ)
+}
+
+/// Time since boot in nanoseconds
+pub fn uptime_ns() -> u64 {
+    NOW_NS.load(Ordering::Relaxed)
+}
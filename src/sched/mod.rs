@@ -2,11 +2,17 @@
 
 pub mod task;    // Task/Process struct
 pub mod queue;   // Run queue
+pub mod clock;   // Tick counter
+pub mod wait;    // Wait queues
+#[cfg(all(feature = "watchdog", target_arch = "x86_64"))]
+pub mod watchdog; // Stall detector
 
-use alloc::sync::Arc;
-use spin::Mutex;
-use task::Task;
-use queue::{CURRENT_TASK, RUN_QUEUE};
+use task::{Task, TaskState};
+use queue::RUN_QUEUE;
+use wait::WaitQueue;
+
+/// Parents blocked in wait4 sleep here until a child exits
+pub static CHILD_EXIT: WaitQueue = WaitQueue::new();
 
 /// Initialize scheduler
 pub fn init() {
@@ -14,10 +20,10 @@ pub fn init() {
     
     // Create PID 1 (Init Task)
     // For now, it's just a kernel thread context
-    let init_task = Arc::new(Mutex::new(Task::new(16384)));
+    let init_task = queue::new_task_ref(Task::new(16384));
     
     // Set as current
-    *CURRENT_TASK.lock() = Some(init_task.clone());
+    queue::set_current_task(init_task.clone());
     
     // Add to run queue
     RUN_QUEUE.lock().tasks.push_back(init_task);
@@ -30,3 +36,90 @@ pub fn schedule() {
     // TODO: CFS-like scheduling
     // Simple round robin stub
 }
+
+/// Charge the current timer tick to whichever task is running
+/// (called from the timer interrupt, so never spin on the locks)
+pub fn account_tick(user_mode: bool) {
+    if let Some(task_arc) = queue::try_current_task() {
+        if let Some(mut task) = task_arc.try_lock() {
+            let ns = clock::tick_ns();
+            task.cpu_ns += ns;
+            if user_mode {
+                task.user_ns += ns;
+            }
+        }
+    }
+}
+
+/// Count a page fault against the current task
+pub fn account_fault(major: bool) {
+    if let Some(task_arc) = queue::try_current_task() {
+        if let Some(mut task) = task_arc.try_lock() {
+            if major {
+                task.maj_faults += 1;
+            } else {
+                task.min_faults += 1;
+            }
+        }
+    }
+}
+
+/// Terminate every thread of the current process, then the current task
+/// The other threads are dropped from the task lists right away: nobody
+/// waits for a thread, only for the process as a whole.
+pub fn exit_group(status: i32) -> ! {
+    let (me, tgid) = {
+        let task_arc = queue::current_task();
+        let task = task_arc.lock();
+        (task.id, task.tgid)
+    };
+    let sibling = |t: &queue::TaskRef| {
+        let task = t.lock();
+        task.tgid == tgid && task.id != me
+    };
+    
+    let mut siblings = 0;
+    queue::ALL_TASKS.lock().retain(|t| {
+        if !sibling(t) {
+            return true;
+        }
+        let mut task = t.lock();
+        task.set_state(TaskState::Terminated);
+        task.exit_status = status;
+        task.set_state(TaskState::Reaped);
+        siblings += 1;
+        false
+    });
+    RUN_QUEUE.lock().tasks.retain(|t| !sibling(t));
+    if siblings > 0 {
+        log::info!("[Sched] exit_group: terminated {} other thread(s) of {}", siblings, tgid);
+    }
+    
+    exit_current(status)
+}
+
+/// Terminate the current task and idle until the scheduler runs something else
+/// The task leaves the run queue here, so nothing schedules it (or saves
+/// a context into it) again; wait4 reaps it later.
+pub fn exit_current(status: i32) -> ! {
+    {
+        let task_arc = queue::current_task();
+        {
+            let mut task = task_arc.lock();
+            task.set_state(TaskState::Terminated);
+            task.exit_status = status;
+        }
+        RUN_QUEUE.lock().tasks.retain(|t| !alloc::sync::Arc::ptr_eq(t, &task_arc));
+    }
+    // Wake waiting parents only after dropping our own lock
+    CHILD_EXIT.wake_all();
+    
+    // Trigger scheduler (TODO)
+    loop {
+        // Keep servicing interrupts (we may have been entered from a fault handler)
+        #[cfg(target_arch = "x86_64")]
+        x86_64::instructions::interrupts::enable_and_hlt();
+        #[cfg(target_arch = "aarch64")]
+        unsafe { core::arch::asm!("wfi") };
+    }
+}
@@ -4,11 +4,19 @@ use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use spin::Mutex;
 use alloc::sync::Arc;
-use crate::sched::task::Task;
+use crate::sched::task::{Task, TaskAlloc, TaskState};
 use spin::Lazy;
 
+/// Shared handle to a task, allocated from the task slab cache
+pub type TaskRef = Arc<Mutex<Task>, TaskAlloc>;
+
+/// Wrap a task in a slab-backed handle
+pub fn new_task_ref(task: Task) -> TaskRef {
+    Arc::new_in(Mutex::new(task), TaskAlloc)
+}
+
 pub struct RunQueue {
-    pub tasks: VecDeque<Arc<Mutex<Task>>>,
+    pub tasks: VecDeque<TaskRef>,
 }
 
 pub static RUN_QUEUE: Lazy<Mutex<RunQueue>> = Lazy::new(|| Mutex::new(RunQueue {
@@ -16,15 +24,92 @@ pub static RUN_QUEUE: Lazy<Mutex<RunQueue>> = Lazy::new(|| Mutex::new(RunQueue {
 }));
 
 /// Current running task (per-CPU in SMP, single for now)
-pub static CURRENT_TASK: Lazy<Mutex<Option<Arc<Mutex<Task>>>>> = Lazy::new(|| Mutex::new(None));
+/// Holds the reference that keeps the task alive; only `set_current_task`
+/// changes it. Readers go through `current_task`/`try_current_task`,
+/// which on x86_64 use the copy cached in per-CPU data instead of this lock.
+pub static CURRENT_TASK: Lazy<Mutex<Option<TaskRef>>> = Lazy::new(|| Mutex::new(None));
+
+/// Make `task` the one running on this CPU, and mark it Running
+/// Its kernel stack becomes the one syscalls run on.
+/// Only ever called on the CPU concerned, with interrupts off, so no
+/// reader of the per-CPU pointer can see it change under it; the old task
+/// is released only after the pointer has moved on.
+pub fn set_current_task(task: TaskRef) {
+    #[cfg(target_arch = "x86_64")]
+    let _irq = InterruptsOff::new();
+    let mut current = CURRENT_TASK.lock();
+    {
+        let mut t = task.lock();
+        if t.state == TaskState::Ready {
+            t.set_state(TaskState::Running);
+        }
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::arch::x86_64::percpu;
+        // Syscalls from the new task land on its own kernel stack
+        percpu::set_kernel_stack_top(task.lock().kernel_stack_top() as u64);
+        percpu::set_current_task(Arc::as_ptr(&task) as u64);
+    }
+    let _old = current.replace(task);
+}
+
+/// The running task, if the scheduler has started
+/// Lock-free on x86_64, so interrupt handlers may call it.
+pub fn try_current_task() -> Option<TaskRef> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ptr = crate::arch::x86_64::percpu::current_task() as *const Mutex<Task>;
+        if ptr.is_null() {
+            return None;
+        }
+        // CURRENT_TASK owns a reference to whatever the per-CPU pointer
+        // names, and only this CPU replaces it (see set_current_task)
+        unsafe {
+            Arc::increment_strong_count_in(ptr, TaskAlloc);
+            Some(Arc::from_raw_in(ptr, TaskAlloc))
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    CURRENT_TASK.lock().clone()
+}
+
+/// The running task
+/// sched::init installs PID 1 before anything can trap into the kernel,
+/// so outside early boot there always is one.
+pub fn current_task() -> TaskRef {
+    try_current_task().expect("no current task")
+}
+
+/// Interrupts disabled until dropped (restored only if they were on)
+#[cfg(target_arch = "x86_64")]
+struct InterruptsOff(bool);
+
+#[cfg(target_arch = "x86_64")]
+impl InterruptsOff {
+    fn new() -> Self {
+        let were_enabled = x86_64::instructions::interrupts::are_enabled();
+        x86_64::instructions::interrupts::disable();
+        Self(were_enabled)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Drop for InterruptsOff {
+    fn drop(&mut self) {
+        if self.0 {
+            x86_64::instructions::interrupts::enable();
+        }
+    }
+}
 
 /// All tasks in the system (for wait4/waitpid lookup)
-pub static ALL_TASKS: Lazy<Mutex<Vec<Arc<Mutex<Task>>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+pub static ALL_TASKS: Lazy<Mutex<Vec<TaskRef>>> = Lazy::new(|| Mutex::new(Vec::new()));
 
 /// Add a new task to the run queue
 pub fn spawn_task(task: Task) -> usize {
     let pid = task.id;
-    let task_arc = Arc::new(Mutex::new(task));
+    let task_arc = new_task_ref(task);
     
     // Add to all tasks list
     ALL_TASKS.lock().push(task_arc.clone());
@@ -36,7 +121,7 @@ pub fn spawn_task(task: Task) -> usize {
 }
 
 /// Get a task by PID
-pub fn get_task_by_pid(pid: usize) -> Option<Arc<Mutex<Task>>> {
+pub fn get_task_by_pid(pid: usize) -> Option<TaskRef> {
     let tasks = ALL_TASKS.lock();
     tasks.iter().find(|t| t.lock().id == pid).cloned()
 }
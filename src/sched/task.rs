@@ -1,20 +1,44 @@
 //! Task / Process Definition
 
+use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::sync::Arc;
-use crate::fs::vfs::Inode;
+use alloc::alloc::{Allocator, AllocError, Global, Layout};
+use core::ptr::NonNull;
+use spin::Mutex;
+use crate::fs::vfs::{Inode, Metadata};
+use crate::mm::slab::SlabCache;
+use crate::mm::vma::VmaList;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// Process ID
 pub type Pid = usize;
 
 /// Task State
+/// Lifecycle: Ready <-> Running, Running -> Blocked -> Ready (or straight
+/// back to Running when it wakes itself), any of those -> Terminated on
+/// exit, and Terminated -> Reaped once the task is off every list.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskState {
     Ready,
     Running,
     Blocked,
     Terminated,
+    Reaped,
+}
+
+impl TaskState {
+    /// Whether the lifecycle allows going from `self` to `next`
+    pub fn can_become(self, next: TaskState) -> bool {
+        use TaskState::*;
+        matches!(
+            (self, next),
+            (Ready, Running) | (Running, Ready) | (Running, Blocked)
+                | (Blocked, Ready) | (Blocked, Running)
+                | (Ready | Running | Blocked, Terminated)
+                | (Terminated, Reaped)
+        )
+    }
 }
 
 /// Helper struct for an open file descriptor
@@ -25,36 +49,191 @@ pub struct FileDescriptor {
     pub flags: u32,
 }
 
+/// User and group ids a task acts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub euid: u32,
+    pub egid: u32,
+}
+
+impl Credentials {
+    /// What init starts with
+    pub const ROOT: Self = Self { uid: 0, gid: 0, euid: 0, egid: 0 };
+
+    /// setuid(2): with an effective uid of root every uid becomes `uid`;
+    /// otherwise only the effective uid may switch, and only to the real uid
+    pub fn set_uid(&mut self, uid: u32) -> Result<(), &'static str> {
+        if self.euid == 0 {
+            self.uid = uid;
+            self.euid = uid;
+        } else if uid == self.uid {
+            self.euid = uid;
+        } else {
+            return Err("not permitted");
+        }
+        Ok(())
+    }
+
+    /// setgid(2), by the same rules as `set_uid`
+    pub fn set_gid(&mut self, gid: u32) -> Result<(), &'static str> {
+        if self.euid == 0 {
+            self.gid = gid;
+            self.egid = gid;
+        } else if gid == self.gid {
+            self.egid = gid;
+        } else {
+            return Err("not permitted");
+        }
+        Ok(())
+    }
+
+    /// Credentials after execve of a file with `image` metadata
+    /// Every id carries over. This is where S_ISUID/S_ISGID (run as the
+    /// file's owner/group) will take effect; they are not honoured yet.
+    pub fn on_exec(self, _image: &Metadata) -> Self {
+        self
+    }
+}
+
 /// A Process / Task Control Block
 pub struct Task {
     pub id: Pid,
     pub parent_id: Pid,
+    // Thread group: the pid of the process this task is a thread of
+    pub tgid: Pid,
     pub state: TaskState,
     pub stack: Vec<u8>,
     pub stack_top: usize,
     pub fd_table: Vec<Option<FileDescriptor>>,
+    // Working directory (absolute, normalized)
+    pub cwd: String,
+    // User and group ids
+    pub creds: Credentials,
+    // Permission bits cleared from files the task creates
+    pub umask: u32,
     // Saved context for context switching
     pub saved_rsp: u64,
     pub saved_rip: u64,
-    // Exit status
+    // Exit status (wait(2) encoding)
     pub exit_status: i32,
+    // Program break (end of the brk heap)
+    pub brk: usize,
+    // User mappings, backed on first touch
+    pub vmas: VmaList,
+    // CPU time in ns (total, and the share in user mode), charged per tick
+    // at the period in force then, so a change of tick rate doesn't rescale it
+    pub cpu_ns: u64,
+    pub user_ns: u64,
+    // CPU time of reaped children (for times()/getrusage)
+    pub child_user_ns: u64,
+    pub child_sys_ns: u64,
+    // Page faults (minor = no I/O needed, major = had to read backing store)
+    pub min_faults: u64,
+    pub maj_faults: u64,
+    pub child_min_faults: u64,
+    pub child_maj_faults: u64,
+    // Resident set: pages currently mapped, and the high-water mark
+    pub rss_pages: AtomicUsize,
+    pub peak_rss_pages: AtomicUsize,
+    // Largest peak RSS among reaped children
+    pub child_max_rss_kb: u64,
 }
 
 static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
 
+/// Mirrors `ArcInner` (strong count, weak count, value) so cache slots are
+/// sized for the allocation behind an `Arc<Mutex<Task>>`
+#[repr(C)]
+struct ArcSlot<T> {
+    _strong: usize,
+    _weak: usize,
+    _data: T,
+}
+
+static TASK_CACHE: SlabCache<ArcSlot<Mutex<Task>>> = SlabCache::new();
+
+/// Allocator for task handles: slots come from the task slab cache, so
+/// fork/exit churn doesn't fragment the general heap
+#[derive(Clone, Copy)]
+pub struct TaskAlloc;
+
+impl TaskAlloc {
+    fn fits(layout: Layout) -> bool {
+        layout.size() <= core::mem::size_of::<ArcSlot<Mutex<Task>>>()
+            && layout.align() <= core::mem::align_of::<ArcSlot<Mutex<Task>>>()
+    }
+}
+
+unsafe impl Allocator for TaskAlloc {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !Self::fits(layout) {
+            return Global.allocate(layout);
+        }
+        let slot = TASK_CACHE.alloc_slot().ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(slot.cast(), layout.size()))
+    }
+    
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if Self::fits(layout) {
+            TASK_CACHE.free_slot(ptr.cast());
+        } else {
+            Global.deallocate(ptr, layout);
+        }
+    }
+}
+
+/// umask init starts with: no write access for group and others
+pub const DEFAULT_UMASK: u32 = 0o022;
+
+/// User heap window handed out by brk (8MB - 12MB)
+pub const USER_HEAP_START: usize = 0x800000;
+pub const USER_HEAP_END: usize = 0xC00000;
+
+/// Window anonymous mmap picks addresses from (12MB - 16MB), kept apart
+/// from the heap so shrinking the break never touches a mapping
+pub const USER_MMAP_START: usize = USER_HEAP_END;
+pub const USER_MMAP_END: usize = 0x1000000;
+
+/// End of the lower canonical half, where user address space stops
+pub const USER_SPACE_END: usize = 0x8000_0000_0000;
+
 impl Task {
     pub fn new(stack_size: usize) -> Self {
-        let pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
+        Self::with_pid(NEXT_PID.fetch_add(1, Ordering::Relaxed), stack_size)
+    }
+    
+    /// A task with a caller-chosen pid
+    /// Pid 0 is never allocated, so self-tests use it for throwaway tasks.
+    pub fn with_pid(pid: Pid, stack_size: usize) -> Self {
         let mut task = Self {
             id: pid,
             parent_id: 0, // Init has no parent
+            tgid: pid,
             state: TaskState::Ready,
             stack: alloc::vec![0; stack_size],
             stack_top: 0,
             fd_table: Vec::new(),
+            cwd: String::from("/"),
+            creds: Credentials::ROOT,
+            umask: DEFAULT_UMASK,
             saved_rsp: 0,
             saved_rip: 0,
             exit_status: 0,
+            brk: USER_HEAP_START,
+            vmas: VmaList::new(),
+            cpu_ns: 0,
+            user_ns: 0,
+            child_user_ns: 0,
+            child_sys_ns: 0,
+            min_faults: 0,
+            maj_faults: 0,
+            child_min_faults: 0,
+            child_maj_faults: 0,
+            rss_pages: AtomicUsize::new(0),
+            peak_rss_pages: AtomicUsize::new(0),
+            child_max_rss_kb: 0,
         };
         
         // Initialize stdio
@@ -67,21 +246,76 @@ impl Task {
     
     /// Fork this task - create a copy with new PID
     pub fn fork(&self, child_rsp: u64, child_rip: u64) -> Self {
-        let child_pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
-        
+        self.fork_with_pid(NEXT_PID.fetch_add(1, Ordering::Relaxed), child_rsp, child_rip)
+    }
+    
+    /// `fork` with a caller-chosen pid (0 for throwaway self-test copies)
+    pub fn fork_with_pid(&self, child_pid: Pid, child_rsp: u64, child_rip: u64) -> Self {
         Self {
             id: child_pid,
             parent_id: self.id,
+            tgid: child_pid,
             state: TaskState::Ready,
             stack: self.stack.clone(),
             stack_top: self.stack_top,
             fd_table: self.fd_table.clone(),
+            cwd: self.cwd.clone(),
+            creds: self.creds,
+            umask: self.umask,
             saved_rsp: child_rsp,
             saved_rip: child_rip,
             exit_status: 0,
+            brk: self.brk,
+            vmas: self.vmas.clone(),
+            cpu_ns: 0,
+            user_ns: 0,
+            child_user_ns: 0,
+            child_sys_ns: 0,
+            min_faults: 0,
+            maj_faults: 0,
+            child_min_faults: 0,
+            child_maj_faults: 0,
+            // The child starts with a copy of the parent's address space
+            rss_pages: AtomicUsize::new(self.rss_pages.load(Ordering::Relaxed)),
+            peak_rss_pages: AtomicUsize::new(self.rss_pages.load(Ordering::Relaxed)),
+            child_max_rss_kb: 0,
         }
     }
     
+    /// Move to `next`, which the lifecycle must allow
+    pub fn set_state(&mut self, next: TaskState) {
+        debug_assert!(self.state.can_become(next), "task {}: {:?} -> {:?}", self.id, self.state, next);
+        self.state = next;
+    }
+    
+    /// 16-byte aligned top of this task's kernel stack
+    pub fn kernel_stack_top(&self) -> usize {
+        (self.stack.as_ptr() as usize + self.stack.len()) & !0xF
+    }
+    
+    /// Nanoseconds spent in the kernel on this task's behalf
+    pub fn sys_ns(&self) -> u64 {
+        self.cpu_ns - self.user_ns
+    }
+    
+    /// Account `pages` newly mapped pages and bump the peak
+    pub fn rss_map(&self, pages: usize) {
+        let now = self.rss_pages.fetch_add(pages, Ordering::Relaxed) + pages;
+        self.peak_rss_pages.fetch_max(now, Ordering::Relaxed);
+    }
+    
+    /// Account `pages` unmapped pages
+    pub fn rss_unmap(&self, pages: usize) {
+        let _ = self.rss_pages.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+            Some(n.saturating_sub(pages))
+        });
+    }
+    
+    /// Peak resident set size in KiB (ru_maxrss)
+    pub fn max_rss_kb(&self) -> u64 {
+        (self.peak_rss_pages.load(Ordering::Relaxed) * crate::mm::PAGE_SIZE / 1024) as u64
+    }
+    
     /// Allocate a new file descriptor
     pub fn add_file(&mut self, file: FileDescriptor) -> usize {
         for (i, slot) in self.fd_table.iter_mut().enumerate() {
@@ -0,0 +1,253 @@
+//! Wait Queues
+//!
+//! A wait queue holds tasks blocked on some event. Sleepers mark
+//! themselves Blocked and idle until a waker flips them back to Ready;
+//! wakers pop tasks off the queue in FIFO order.
+//!
+//! To avoid lost wakeups, a sleeper is queued *before* it re-checks its
+//! condition (`prepare_to_wait`), so a wake that lands in between is
+//! still seen.
+//!
+//! Timed sleeps also register a deadline; the timer interrupt wakes any
+//! sleeper whose deadline has passed. Both wake paths only act on a task
+//! that is still Blocked, so a task is made Ready exactly once.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+use super::clock;
+use super::queue::{try_current_task, TaskRef};
+use super::task::TaskState;
+
+struct TimedSleeper {
+    /// Absolute, in nanoseconds since boot, so a change of tick rate
+    /// doesn't stretch or shrink it
+    deadline: u64,
+    task: TaskRef,
+    /// Set by the timer when it (not an event) made the task Ready
+    fired: bool,
+}
+
+/// Tasks sleeping with a deadline, scanned on every timer tick
+static TIMED: Mutex<Vec<TimedSleeper>> = Mutex::new(Vec::new());
+
+pub struct WaitQueue {
+    waiters: Mutex<VecDeque<TaskRef>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self { waiters: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Block the current task until `wake_one`/`wake_all` picks it
+    pub fn sleep_on(&self) {
+        let waiter = self.prepare_to_wait();
+        self.finish_wait(waiter);
+    }
+
+    /// Sleep until `cond` holds, re-checking it after every wakeup
+    pub fn wait_until(&self, mut cond: impl FnMut() -> bool) {
+        loop {
+            let waiter = self.prepare_to_wait();
+            if cond() {
+                self.cancel_wait(waiter);
+                return;
+            }
+            self.finish_wait(waiter);
+        }
+    }
+
+    /// Like `sleep_on`, but give up after `timeout_ns`
+    /// Returns true if woken by an event, false on timeout.
+    pub fn sleep_on_timeout(&self, timeout_ns: u64) -> bool {
+        let waiter = self.prepare_to_wait();
+        self.finish_wait_timeout(waiter, timeout_ns)
+    }
+
+    /// Like `wait_until`, but give up after `timeout_ns`
+    /// Returns whether `cond` held (false means the timeout expired first).
+    pub fn wait_until_timeout(&self, mut cond: impl FnMut() -> bool, timeout_ns: u64) -> bool {
+        let deadline = clock::uptime_ns().saturating_add(timeout_ns);
+        loop {
+            let waiter = self.prepare_to_wait();
+            if cond() {
+                self.cancel_wait(waiter);
+                return true;
+            }
+            let now = clock::uptime_ns();
+            if now >= deadline {
+                self.cancel_wait(waiter);
+                return false;
+            }
+            self.finish_wait_timeout(waiter, deadline - now);
+        }
+    }
+
+    /// Queue the current task and mark it Blocked, without sleeping yet
+    /// Pair with `finish_wait` (to sleep) or `cancel_wait` (if the event
+    /// turned out to have happened already).
+    pub fn prepare_to_wait(&self) -> Option<TaskRef> {
+        let task = try_current_task()?;
+        self.park(task.clone());
+        Some(task)
+    }
+    
+    /// Mark a running `task` Blocked and queue it behind the other waiters
+    pub fn park(&self, task: TaskRef) {
+        task.lock().set_state(TaskState::Blocked);
+        self.waiters.lock().push_back(task);
+    }
+
+    /// Idle until a waker has made `waiter` Ready again
+    pub fn finish_wait(&self, waiter: Option<TaskRef>) {
+        let task = match waiter {
+            Some(t) => t,
+            None => {
+                // No task context (early boot): just wait for the next interrupt
+                idle();
+                return;
+            }
+        };
+        while task.lock().state == TaskState::Blocked {
+            idle();
+        }
+        make_running(&task);
+    }
+
+    /// `finish_wait` with a deadline `timeout_ns` from now
+    /// Returns true if woken by an event, false if the timer got there first.
+    pub fn finish_wait_timeout(&self, waiter: Option<TaskRef>, timeout_ns: u64) -> bool {
+        let task = match waiter {
+            Some(t) => t,
+            None => {
+                idle();
+                return false;
+            }
+        };
+        TIMED.lock().push(TimedSleeper {
+            deadline: clock::uptime_ns().saturating_add(timeout_ns),
+            task: task.clone(),
+            fired: false,
+        });
+        while task.lock().state == TaskState::Blocked {
+            idle();
+        }
+        
+        let mut timed_out = false;
+        TIMED.lock().retain(|s| {
+            let mine = Arc::ptr_eq(&s.task, &task);
+            timed_out |= mine && s.fired;
+            !mine
+        });
+        // A timed-out task is still queued; wakers skip it, but drop it now
+        if timed_out {
+            self.waiters.lock().retain(|t| !Arc::ptr_eq(t, &task));
+        }
+        make_running(&task);
+        !timed_out
+    }
+
+    /// Undo `prepare_to_wait` without sleeping
+    pub fn cancel_wait(&self, waiter: Option<TaskRef>) {
+        if let Some(task) = waiter {
+            self.waiters.lock().retain(|t| !Arc::ptr_eq(t, &task));
+            make_running(&task);
+        }
+    }
+
+    /// Wake the longest waiter; false if nobody was waiting
+    /// Waiters that already timed out are dropped without counting.
+    pub fn wake_one(&self) -> bool {
+        let mut waiters = self.waiters.lock();
+        while let Some(task) = waiters.pop_front() {
+            if make_ready(&task) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Wake every waiter, returning how many were actually blocked
+    pub fn wake_all(&self) -> usize {
+        let woken: VecDeque<TaskRef> = core::mem::take(&mut *self.waiters.lock());
+        woken.iter().filter(|t| make_ready(t)).count()
+    }
+
+    /// `wake_all` for interrupt handlers: never spins on a lock
+    /// Waiters whose lock is busy stay queued; returns false if any did
+    /// (or the queue itself was busy), so the caller can retry later.
+    pub fn try_wake_all(&self) -> bool {
+        let mut waiters = match self.waiters.try_lock() {
+            Some(w) => w,
+            None => return false,
+        };
+        waiters.retain(|t| match t.try_lock() {
+            Some(mut task) => {
+                if task.state == TaskState::Blocked {
+                    task.set_state(TaskState::Ready);
+                }
+                false
+            }
+            None => true,
+        });
+        waiters.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.lock().is_empty()
+    }
+}
+
+/// Blocked -> Ready; false if someone else already woke the task
+fn make_ready(task: &TaskRef) -> bool {
+    let mut task = task.lock();
+    if task.state == TaskState::Blocked {
+        task.set_state(TaskState::Ready);
+        true
+    } else {
+        false
+    }
+}
+
+/// Back to Running once the wait is over; a task that was killed
+/// meanwhile stays Terminated
+fn make_running(task: &TaskRef) {
+    let mut task = task.lock();
+    if matches!(task.state, TaskState::Ready | TaskState::Blocked) {
+        task.set_state(TaskState::Running);
+    }
+}
+
+/// Wake sleepers whose deadline has passed (called from the timer interrupt,
+/// so never spin on a lock; a busy entry is simply retried next tick)
+pub fn expire_timeouts() {
+    let now = clock::uptime_ns();
+    let mut timed = match TIMED.try_lock() {
+        Some(t) => t,
+        None => return,
+    };
+    for sleeper in timed.iter_mut().filter(|s| !s.fired && s.deadline <= now) {
+        if let Some(mut task) = sleeper.task.try_lock() {
+            if task.state == TaskState::Blocked {
+                task.set_state(TaskState::Ready);
+                sleeper.fired = true;
+            }
+        }
+    }
+}
+
+/// Let interrupts (and the timer's process switch) run until the next one fires
+fn idle() {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let were_enabled = x86_64::instructions::interrupts::are_enabled();
+        x86_64::instructions::interrupts::enable_and_hlt();
+        if !were_enabled {
+            x86_64::instructions::interrupts::disable();
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe { core::arch::asm!("wfi") };
+}
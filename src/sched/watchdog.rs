@@ -0,0 +1,77 @@
+//! Scheduler Watchdog (feature `watchdog`)
+//!
+//! Every timer tick checks whether anything moved since the last one: a
+//! tick taken in user mode, a finished syscall or a context switch all
+//! count. After `STALL_SECS` seconds with none of them, the current task and
+//! the interrupted registers are dumped over serial, once per stall (a
+//! system where every task is legitimately blocked reports the same way).
+//! Locks are only ever try_locked, since the stall may be someone holding
+//! them.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+
+use super::clock;
+use super::queue::try_current_task;
+use crate::arch::x86_64::idt::TrapFrameExt;
+use crate::drivers::serial::SerialWriter;
+
+/// Time without progress before the watchdog reports
+pub const STALL_SECS: u64 = 5;
+
+/// Tick of the last sign of progress
+static LAST_PROGRESS: AtomicU64 = AtomicU64::new(0);
+
+/// The current stall has been reported already
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Something ran: restart the stall timer
+pub fn progress() {
+    LAST_PROGRESS.store(clock::ticks(), Ordering::Relaxed);
+    REPORTED.store(false, Ordering::Relaxed);
+}
+
+/// Called from the timer interrupt with the interrupted frame
+pub fn check(frame: &InterruptStackFrame) {
+    if frame.is_user() {
+        progress();
+        return;
+    }
+    let now = clock::ticks();
+    let last = LAST_PROGRESS.load(Ordering::Relaxed);
+    if now.saturating_sub(last) < STALL_SECS * clock::hz() || REPORTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let mut out = SerialWriter;
+    let _ = writeln!(out, "\n[watchdog] no progress for {} ticks (last at tick {})", now - last, last);
+
+    match try_current_task().as_ref().map(|t| t.try_lock()) {
+        Some(Some(task)) => {
+            let _ = writeln!(out, "  task {} (parent {}), state {:?}", task.id, task.parent_id, task.state);
+        }
+        Some(None) => { let _ = writeln!(out, "  task lock held"); }
+        None => { let _ = writeln!(out, "  no current task"); }
+    }
+    match crate::globals::SCHEDULER.try_lock() {
+        Some(sched) => {
+            let current = sched.as_ref().and_then(|s| s.current_pid);
+            let _ = writeln!(out, "  guest scheduler: current {:?}", current);
+        }
+        None => { let _ = writeln!(out, "  guest scheduler lock held"); }
+    }
+
+    let _ = writeln!(out, "  RIP=0x{:016x} RSP=0x{:016x} RFLAGS=0x{:x} CS=0x{:x}",
+        frame.instruction_pointer.as_u64(), frame.stack_pointer.as_u64(),
+        frame.cpu_flags, frame.code_segment);
+
+    // The interrupted code's frames follow the handler's on this chain
+    use crate::arch::x86_64::backtrace;
+    let _ = writeln!(out, "  backtrace:");
+    unsafe {
+        backtrace::walk(backtrace::current_rbp(), |depth, addr| {
+            let _ = writeln!(out, "    #{:<2} 0x{:016x}", depth, addr);
+        });
+    }
+}
@@ -0,0 +1,21 @@
+//! Kernel Symbol Table
+//!
+//! Generated by build.rs from the linker map (see build-symbols.sh).
+//! Entries are `(rva, name)` sorted by RVA.
+
+include!(concat!(env!("OUT_DIR"), "/symbols.rs"));
+
+/// Resolve a code address to `(function, offset)`
+pub fn resolve(addr: u64) -> Option<(&'static str, usize)> {
+    let rva = addr.checked_sub(crate::arch::x86_64::backtrace::image_base())?;
+    
+    // Last symbol starting at or below `rva`
+    let idx = match SYMBOLS.binary_search_by_key(&rva, |&(start, _)| start) {
+        Ok(i) => i,
+        Err(0) => return None,
+        Err(i) => i - 1,
+    };
+    
+    let (start, name) = SYMBOLS[idx];
+    Some((name, (rva - start) as usize))
+}
@@ -8,43 +8,21 @@
 //! 2. Load the main executable and all shared libraries
 //! 3. Process PT_DYNAMIC section for relocation info
 //! 4. Resolve symbols via DT_SYMTAB, DT_STRTAB
-//! 5. Apply relocations (RELATIVE, GLOB_DAT, JUMP_SLOT)
-//! 6. Call .init sections, then transfer to _start
+//! 5. Apply relocations (RELATIVE, GLOB_DAT, JUMP_SLOT, COPY); with lazy binding
+//!    JUMP_SLOTs are left to `_dl_runtime_resolve` on first call
+//! 6. Make the PT_GNU_RELRO region read-only
+//! 7. Call .init sections, then transfer to _start
 
 use alloc::vec::Vec;
 use alloc::string::String;
+use core::mem::size_of;
+use spin::Mutex;
 
-/// Dynamic section entry
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64Dyn {
-    pub d_tag: i64,
-    pub d_val: u64,
-}
-
-/// Symbol table entry
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64Sym {
-    pub st_name: u32,      // Symbol name offset in string table
-    pub st_info: u8,       // Symbol type and binding
-    pub st_other: u8,      // Reserved
-    pub st_shndx: u16,     // Section header index
-    pub st_value: u64,     // Symbol value (address)
-    pub st_size: u64,      // Symbol size
-}
-
-/// Relocation entry (with addend)
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64Rela {
-    pub r_offset: u64,     // Virtual address
-    pub r_info: u64,       // Relocation type and symbol index
-    pub r_addend: i64,     // Addend
-}
+use crate::elf::{self, DynamicEntries, Elf64Rela, Elf64Sym, Table};
+use super::elf::LoadedSegment;
 
 // Dynamic section tags
-pub const DT_NULL: i64 = 0;
+pub const DT_NULL: i64 = elf::DT_NULL;
 pub const DT_NEEDED: i64 = 1;      // Name of needed library
 pub const DT_PLTRELSZ: i64 = 2;    // Size of PLT relocs
 pub const DT_PLTGOT: i64 = 3;      // Address of PLT/GOT
@@ -59,151 +37,400 @@ pub const DT_SYMENT: i64 = 11;     // Size of one symbol entry
 pub const DT_INIT: i64 = 12;       // Address of init function
 pub const DT_FINI: i64 = 13;       // Address of termination function
 pub const DT_JMPREL: i64 = 23;     // Address of PLT relocs
+pub const DT_BIND_NOW: i64 = 24;   // Bind every PLT slot at load time
+pub const DT_FLAGS: i64 = 30;      // DF_* flags
+pub const DT_FLAGS_1: i64 = 0x6ffffffb; // DF_1_* flags
+
+pub const DF_BIND_NOW: u64 = 0x8;
+pub const DF_1_NOW: u64 = 0x1;
 
 // Relocation types (x86_64)
 pub const R_X86_64_NONE: u32 = 0;
 pub const R_X86_64_64: u32 = 1;        // Direct 64-bit
+pub const R_X86_64_COPY: u32 = 5;      // Copy a data object into the executable
 pub const R_X86_64_GLOB_DAT: u32 = 6;  // Create GOT entry
 pub const R_X86_64_JUMP_SLOT: u32 = 7; // Create PLT entry
 pub const R_X86_64_RELATIVE: u32 = 8;  // Adjust by program base
 
 /// Loaded shared library info
+/// Table addresses are absolute (base applied) and lie inside `segments`.
 pub struct LoadedLibrary {
     pub name: String,
     pub base_addr: u64,
     pub symtab: u64,
+    /// Symbols that fit between DT_SYMTAB and the end of its segment (or
+    /// DT_STRTAB, when that follows it in the same segment)
+    pub sym_count: usize,
     pub strtab: u64,
+    pub strsz: usize,
     pub rela: u64,
     pub relasz: usize,
     pub jmprel: u64,
     pub pltrelsz: usize,
     pub init: u64,
+    /// DT_PLTGOT: GOT[1] and GOT[2] are reserved for the lazy resolver
+    pub pltgot: u64,
+    /// DT_BIND_NOW (or its DT_FLAGS/DT_FLAGS_1 forms) was set
+    pub bind_now: bool,
+    pub relro: Option<(u64, u64)>,  // From PT_GNU_RELRO (absolute vaddr, size)
+    /// Mapped ranges (start, end) of the PT_LOAD segments
+    pub segments: Vec<(u64, u64)>,
+}
+
+/// Libraries COPY relocations take data from, in load order
+static LIBRARIES: Mutex<Vec<&'static LoadedLibrary>> = Mutex::new(Vec::new());
+
+/// Make `lib`'s symbols available to COPY relocations of objects
+/// relocated after it
+pub fn register(lib: &'static LoadedLibrary) {
+    LIBRARIES.lock().push(lib);
+}
+
+/// Most dynamic entries read before giving up on finding DT_NULL
+const MAX_DYNAMIC_ENTRIES: usize = 1024;
+
+impl LoadedLibrary {
+    /// The segment range holding all of [addr, addr + len)
+    fn segment_of(&self, addr: u64, len: usize) -> Option<(u64, u64)> {
+        let end = addr.checked_add(len as u64)?;
+        self.segments.iter().copied().find(|&(start, seg_end)| start <= addr && end <= seg_end)
+    }
+
+    /// `len` bytes of the loaded image at `addr`, if they are all mapped
+    fn bytes(&self, addr: u64, len: usize) -> Option<&[u8]> {
+        self.segment_of(addr, len)?;
+        // Inside a PT_LOAD segment the loader mapped and filled
+        Some(unsafe { core::slice::from_raw_parts(addr as *const u8, len) })
+    }
+
+    /// Relocation table at `addr`, or None if it isn't mapped
+    fn relocations(&self, addr: u64, size: usize) -> Option<Table<'_, Elf64Rela>> {
+        if addr == 0 || size == 0 {
+            return Some(Table::packed(&[]));
+        }
+        self.bytes(addr, size).map(Table::packed)
+    }
+
+    /// Symbol `index` of DT_SYMTAB
+    fn symbol(&self, index: usize) -> Option<Elf64Sym> {
+        if index >= self.sym_count {
+            return None;
+        }
+        let table = self.bytes(self.symtab, self.sym_count * size_of::<Elf64Sym>())?;
+        Table::<Elf64Sym>::packed(table).get(index)
+    }
+
+    /// Name and address of symbol `index`, if this library defines it
+    fn resolve(&self, index: usize) -> Option<(&str, u64)> {
+        let Some(sym) = self.symbol(index) else {
+            log::warn!("[dynlink] Bad symbol index {}", index);
+            return None;
+        };
+        let name = self.string(sym.st_name as usize).unwrap_or("<??>");
+        if sym.st_value == 0 {
+            log::warn!("[dynlink] Unresolved symbol: {}", name);
+            return None;
+        }
+        Some((name, self.base_addr.wrapping_add(sym.st_value)))
+    }
+
+    /// Defined symbol called `name`
+    fn lookup(&self, name: &str) -> Option<Elf64Sym> {
+        let table = self.bytes(self.symtab, self.sym_count * size_of::<Elf64Sym>())?;
+        Table::<Elf64Sym>::packed(table)
+            .find(|sym| sym.st_shndx != 0 && sym.st_value != 0 && self.string(sym.st_name as usize) == Some(name))
+    }
+
+    /// Name at `offset` of DT_STRTAB
+    fn string(&self, offset: usize) -> Option<&str> {
+        elf::str_at(self.bytes(self.strtab, self.strsz)?, offset)
+    }
 }
 
 /// Parse PT_DYNAMIC section and extract tables
-pub fn parse_dynamic(base_addr: u64, dyn_addr: u64) -> Option<LoadedLibrary> {
+/// `dyn_addr`/`dyn_size` give the loaded segment and `segments` the
+/// library's PT_LOAD mappings; every table must lie inside one of them.
+pub fn parse_dynamic(
+    base_addr: u64,
+    dyn_addr: u64,
+    dyn_size: usize,
+    segments: &[LoadedSegment],
+) -> Result<LoadedLibrary, &'static str> {
     let mut lib = LoadedLibrary {
         name: String::from("main"),
         base_addr,
         symtab: 0,
+        sym_count: 0,
         strtab: 0,
+        strsz: 0,
         rela: 0,
         relasz: 0,
         jmprel: 0,
         pltrelsz: 0,
         init: 0,
+        pltgot: 0,
+        bind_now: false,
+        relro: None,
+        segments: segments.iter().map(|s| (s.vaddr, s.vaddr.saturating_add(s.size))).collect(),
     };
     
-    let mut ptr = dyn_addr as *const Elf64Dyn;
-    
-    unsafe {
-        loop {
-            let dyn_entry = *ptr;
-            
-            if dyn_entry.d_tag == DT_NULL {
-                break;
+    lib.segment_of(dyn_addr, dyn_size).ok_or("PT_DYNAMIC is not mapped")?;
+    let dynamic = unsafe { core::slice::from_raw_parts(dyn_addr as *const u8, dyn_size) };
+    let mut entries = DynamicEntries::new(dynamic);
+    let addr = |d_val: u64| base_addr.checked_add(d_val).ok_or("Dynamic address overflows");
+    for dyn_entry in entries.by_ref().take(MAX_DYNAMIC_ENTRIES) {
+        match dyn_entry.d_tag {
+            DT_STRTAB => lib.strtab = addr(dyn_entry.d_val)?,
+            DT_STRSZ => lib.strsz = dyn_entry.d_val as usize,
+            DT_SYMTAB => lib.symtab = addr(dyn_entry.d_val)?,
+            DT_RELA => lib.rela = addr(dyn_entry.d_val)?,
+            DT_RELASZ => lib.relasz = dyn_entry.d_val as usize,
+            DT_JMPREL => lib.jmprel = addr(dyn_entry.d_val)?,
+            DT_PLTRELSZ => lib.pltrelsz = dyn_entry.d_val as usize,
+            DT_INIT => lib.init = dyn_entry.d_val,
+            DT_PLTGOT => lib.pltgot = addr(dyn_entry.d_val)?,
+            DT_BIND_NOW => lib.bind_now = true,
+            DT_FLAGS if dyn_entry.d_val & DF_BIND_NOW != 0 => lib.bind_now = true,
+            DT_FLAGS_1 if dyn_entry.d_val & DF_1_NOW != 0 => lib.bind_now = true,
+            DT_SYMENT if dyn_entry.d_val as usize != size_of::<Elf64Sym>() => return Err("Bad DT_SYMENT"),
+            DT_RELAENT if dyn_entry.d_val as usize != size_of::<Elf64Rela>() => return Err("Bad DT_RELAENT"),
+            DT_NEEDED => {
+                // Would need to load this library
+                log::debug!("[dynlink] Needed library at strtab offset {}", dyn_entry.d_val);
             }
-            
-            match dyn_entry.d_tag {
-                DT_STRTAB => lib.strtab = dyn_entry.d_val,
-                DT_SYMTAB => lib.symtab = dyn_entry.d_val,
-                DT_RELA => lib.rela = dyn_entry.d_val,
-                DT_RELASZ => lib.relasz = dyn_entry.d_val as usize,
-                DT_JMPREL => lib.jmprel = dyn_entry.d_val,
-                DT_PLTRELSZ => lib.pltrelsz = dyn_entry.d_val as usize,
-                DT_INIT => lib.init = dyn_entry.d_val,
-                DT_NEEDED => {
-                    // Would need to load this library
-                    log::debug!("[dynlink] Needed library at strtab offset {}", dyn_entry.d_val);
-                }
-                _ => {}
-            }
-            
-            ptr = ptr.add(1);
+            _ => {}
         }
     }
+    if entries.next().is_some() {
+        return Err("Dynamic section has no DT_NULL");
+    }
+    
+    // Tables must be mapped where they claim to be
+    if lib.strtab != 0 && lib.bytes(lib.strtab, lib.strsz).is_none() {
+        return Err("DT_STRTAB outside the loaded segments");
+    }
+    if lib.relocations(lib.rela, lib.relasz).is_none() || lib.relocations(lib.jmprel, lib.pltrelsz).is_none() {
+        return Err("Relocations outside the loaded segments");
+    }
+    if lib.symtab != 0 {
+        let (_, seg_end) = lib.segment_of(lib.symtab, size_of::<Elf64Sym>()).ok_or("DT_SYMTAB outside the loaded segments")?;
+        // DT_SYMTAB has no size: bound it by its segment, and by the
+        // string table that conventionally comes next
+        let end = if lib.strtab > lib.symtab && lib.strtab < seg_end { lib.strtab } else { seg_end };
+        lib.sym_count = ((end - lib.symtab) as usize) / size_of::<Elf64Sym>();
+    }
     
-    log::info!("[dynlink] Parsed dynamic: symtab=0x{:x}, strtab=0x{:x}", lib.symtab, lib.strtab);
+    log::info!("[dynlink] Parsed dynamic: symtab=0x{:x} ({} symbols), strtab=0x{:x}",
+               lib.symtab, lib.sym_count, lib.strtab);
     
-    Some(lib)
+    Ok(lib)
+}
+
+/// When PLT slots (JUMP_SLOT relocations) are bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// Resolve every slot before the library runs
+    Now,
+    /// Resolve each slot on its first call, as glibc does by default;
+    /// DT_BIND_NOW in the library still forces `Now`
+    Lazy,
 }
 
 /// Apply relocations to loaded library
-pub fn apply_relocations(lib: &LoadedLibrary) {
+/// With lazy binding the GOT points back at `lib`, so it must stay put
+/// for as long as the library's code can run.
+pub fn apply_relocations(lib: &'static LoadedLibrary, binding: Binding) {
     log::info!("[dynlink] Applying {} bytes of relocations", lib.relasz);
     
-    // Apply RELA relocations
-    if lib.rela != 0 && lib.relasz > 0 {
-        let num_relas = lib.relasz / core::mem::size_of::<Elf64Rela>();
-        
-        for i in 0..num_relas {
-            let rela = unsafe {
-                *((lib.rela + (i * core::mem::size_of::<Elf64Rela>()) as u64) as *const Elf64Rela)
-            };
-            
+    // RELA relocations; parse_dynamic checked both tables are mapped
+    for rela in lib.relocations(lib.rela, lib.relasz).into_iter().flatten() {
+        apply_relocation(lib, &rela);
+    }
+    
+    // PLT/GOT relocations (JMPREL)
+    let lazy = binding == Binding::Lazy && !lib.bind_now && install_resolver(lib);
+    for rela in lib.relocations(lib.jmprel, lib.pltrelsz).into_iter().flatten() {
+        if lazy && rela.r_type() == R_X86_64_JUMP_SLOT {
+            defer_slot(lib, &rela);
+        } else {
             apply_relocation(lib, &rela);
         }
     }
     
-    // Apply PLT/GOT relocations (JMPREL)
-    if lib.jmprel != 0 && lib.pltrelsz > 0 {
-        let num_jmprels = lib.pltrelsz / core::mem::size_of::<Elf64Rela>();
+    protect_relro(lib);
+}
+
+/// Point GOT[1] at `lib` and GOT[2] at the resolver trampoline, which
+/// the PLT's first entry pushes and jumps through
+fn install_resolver(lib: &'static LoadedLibrary) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if lib.pltgot != 0 && lib.segment_of(lib.pltgot, 3 * 8).is_some() {
+        let got = lib.pltgot as *mut u64;
+        unsafe {
+            got.add(1).write_unaligned(lib as *const LoadedLibrary as u64);
+            got.add(2).write_unaligned(_dl_runtime_resolve as *const () as u64);
+        }
+        log::debug!("[dynlink] Lazy binding through GOT at 0x{:x}", lib.pltgot);
+        return true;
+    }
+    log::debug!("[dynlink] {}: no usable DT_PLTGOT, binding PLT slots now", lib.name);
+    false
+}
+
+/// Leave a JUMP_SLOT for the resolver: the GOT holds the link-time
+/// address of its PLT stub, which only needs the load base added
+fn defer_slot(lib: &LoadedLibrary, rela: &Elf64Rela) {
+    let Some(slot) = lib.base_addr.checked_add(rela.r_offset).filter(|&t| lib.segment_of(t, 8).is_some()) else {
+        log::warn!("[dynlink] Relocation target 0x{:x} outside the library", rela.r_offset);
+        return;
+    };
+    let slot = slot as *mut u64;
+    let stub = unsafe { slot.read_unaligned() };
+    if stub == 0 {
+        // Nothing to fall back to: bind it now
+        apply_relocation(lib, rela);
+        return;
+    }
+    unsafe { slot.write_unaligned(lib.base_addr.wrapping_add(stub)) };
+}
+
+/// Resolver trampoline, entered from PLT0 with the library (GOT[1]) and
+/// the JMPREL index on the stack. Saves the argument registers, binds
+/// the slot through `dl_fixup` and tail-jumps to the resolved function.
+/// It runs with the library's privileges, so it is only reachable by code
+/// that can execute kernel text (as `call_init` does).
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn _dl_runtime_resolve() {
+    core::arch::naked_asm!(
+        // Entry: [rsp] = lib, [rsp+8] = index, [rsp+16] = caller's return.
+        // rsp is 8 mod 16 here; seven pushes make it 16-aligned for the call.
+        "push rax",       // Vector register count for varargs calls
+        "push rdi",
+        "push rsi",
+        "push rdx",
+        "push rcx",
+        "push r8",
+        "push r9",
+        "sub rsp, 128",
+        "movdqu [rsp + 0], xmm0",
+        "movdqu [rsp + 16], xmm1",
+        "movdqu [rsp + 32], xmm2",
+        "movdqu [rsp + 48], xmm3",
+        "movdqu [rsp + 64], xmm4",
+        "movdqu [rsp + 80], xmm5",
+        "movdqu [rsp + 96], xmm6",
+        "movdqu [rsp + 112], xmm7",
         
-        for i in 0..num_jmprels {
-            let rela = unsafe {
-                *((lib.jmprel + (i * core::mem::size_of::<Elf64Rela>()) as u64) as *const Elf64Rela)
-            };
-            
-            apply_relocation(lib, &rela);
+        "mov rdi, [rsp + 184]", // lib
+        "mov rsi, [rsp + 192]", // index
+        "call {fixup}",
+        "mov r11, rax",
+        
+        "movdqu xmm0, [rsp + 0]",
+        "movdqu xmm1, [rsp + 16]",
+        "movdqu xmm2, [rsp + 32]",
+        "movdqu xmm3, [rsp + 48]",
+        "movdqu xmm4, [rsp + 64]",
+        "movdqu xmm5, [rsp + 80]",
+        "movdqu xmm6, [rsp + 96]",
+        "movdqu xmm7, [rsp + 112]",
+        "add rsp, 128",
+        "pop r9",
+        "pop r8",
+        "pop rcx",
+        "pop rdx",
+        "pop rsi",
+        "pop rdi",
+        "pop rax",
+        "add rsp, 16",    // Drop lib and index
+        "jmp r11",
+        fixup = sym dl_fixup,
+    );
+}
+
+/// Bind PLT slot `index` of `lib` and return the function's address
+/// An unresolvable slot is fatal, as it is for ld.so.
+#[cfg(target_arch = "x86_64")]
+extern "C" fn dl_fixup(lib: &LoadedLibrary, index: usize) -> u64 {
+    let rela = lib.relocations(lib.jmprel, lib.pltrelsz).and_then(|t| t.get(index));
+    let bound = rela.and_then(|rela| {
+        let slot = lib.base_addr.checked_add(rela.r_offset).filter(|&t| lib.segment_of(t, 8).is_some())?;
+        let (name, value) = lib.resolve(rela.r_sym())?;
+        Some((slot, name, value))
+    });
+    match bound {
+        Some((slot, name, value)) => {
+            unsafe { (slot as *mut u64).write_unaligned(value) };
+            log::debug!("[dynlink] Lazily bound {} = 0x{:x}", name, value);
+            value
         }
+        None => panic!("[dynlink] Cannot bind PLT slot {}", index),
+    }
+}
+
+/// Make the RELRO region read-only now that relocations are done
+/// Like ld.so, only whole pages are protected: the end is rounded down.
+fn protect_relro(lib: &LoadedLibrary) {
+    let (vaddr, size) = match lib.relro {
+        Some(r) => r,
+        None => return,
+    };
+    let start = vaddr & !0xFFF;
+    let end = (vaddr + size) & !0xFFF;
+    if end <= start {
+        return;
+    }
+    
+    use crate::mm::vma::PROT_READ;
+    match crate::mm::vmm::protect_current(start as usize, (end - start) as usize, PROT_READ) {
+        Ok(()) => log::debug!("[dynlink] RELRO 0x{:x}-0x{:x} now read-only", start, end),
+        Err(e) => log::warn!("[dynlink] Failed to protect RELRO 0x{:x}-0x{:x}: {}", start, end, e),
     }
 }
 
 fn apply_relocation(lib: &LoadedLibrary, rela: &Elf64Rela) {
-    let r_type = (rela.r_info & 0xFFFFFFFF) as u32;
-    let r_sym = (rela.r_info >> 32) as usize;
+    let r_type = rela.r_type();
+    let r_sym = rela.r_sym();
+    
+    if r_type == R_X86_64_COPY {
+        // Patches st_size bytes rather than one slot
+        copy_relocation(lib, rela);
+        return;
+    }
     
-    let addr = (lib.base_addr + rela.r_offset) as *mut u64;
+    // The 8-byte slot being patched must be inside the library too
+    let target = match lib.base_addr.checked_add(rela.r_offset) {
+        Some(t) if lib.segment_of(t, 8).is_some() => t,
+        _ => {
+            log::warn!("[dynlink] Relocation target 0x{:x} outside the library", rela.r_offset);
+            return;
+        }
+    };
+    let addr = target as *mut u64;
     
     match r_type {
         R_X86_64_RELATIVE => {
             // B + A (base + addend)
             let value = lib.base_addr.wrapping_add(rela.r_addend as u64);
-            unsafe { *addr = value; }
+            unsafe { addr.write_unaligned(value); }
             log::debug!("[dynlink] RELATIVE @ 0x{:x} = 0x{:x}", rela.r_offset, value);
         }
         R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT => {
             // Symbol resolution needed
-            if lib.symtab != 0 {
-                let sym = unsafe {
-                    *((lib.symtab + (r_sym * core::mem::size_of::<Elf64Sym>()) as u64) as *const Elf64Sym)
-                };
-                
-                // Get symbol name from string table
-                let sym_name = if lib.strtab != 0 {
-                    get_string(lib.strtab, sym.st_name as usize)
-                } else {
-                    String::from("<??>")
-                };
-                
-                // If symbol is defined in this library, use its value
-                if sym.st_value != 0 {
-                    let value = lib.base_addr + sym.st_value;
-                    unsafe { *addr = value; }
-                    log::debug!("[dynlink] {} @ 0x{:x} = 0x{:x}", sym_name, rela.r_offset, value);
-                } else {
-                    log::warn!("[dynlink] Unresolved symbol: {}", sym_name);
-                }
+            // resolve() logs why when it can't
+            if let Some((sym_name, value)) = lib.resolve(r_sym) {
+                unsafe { addr.write_unaligned(value); }
+                log::debug!("[dynlink] {} @ 0x{:x} = 0x{:x}", sym_name, rela.r_offset, value);
             }
         }
         R_X86_64_64 => {
             // S + A
-            if lib.symtab != 0 && r_sym > 0 {
-                let sym = unsafe {
-                    *((lib.symtab + (r_sym * core::mem::size_of::<Elf64Sym>()) as u64) as *const Elf64Sym)
-                };
-                let value = (lib.base_addr + sym.st_value).wrapping_add(rela.r_addend as u64);
-                unsafe { *addr = value; }
+            if r_sym > 0 {
+                if let Some(sym) = lib.symbol(r_sym) {
+                    let value = lib.base_addr.wrapping_add(sym.st_value).wrapping_add(rela.r_addend as u64);
+                    unsafe { addr.write_unaligned(value); }
+                }
             }
         }
         R_X86_64_NONE => {}
@@ -213,17 +440,36 @@ fn apply_relocation(lib: &LoadedLibrary, rela: &Elf64Rela) {
     }
 }
 
-fn get_string(strtab: u64, offset: usize) -> String {
-    let ptr = (strtab + offset as u64) as *const u8;
-    let mut len = 0;
+/// R_X86_64_COPY: give the executable's own copy of a data object (in
+/// its .bss) the initial contents from the library that defines it
+fn copy_relocation(lib: &LoadedLibrary, rela: &Elf64Rela) {
+    let Some(sym) = lib.symbol(rela.r_sym()) else {
+        log::warn!("[dynlink] Bad symbol index {}", rela.r_sym());
+        return;
+    };
+    let name = lib.string(sym.st_name as usize).unwrap_or("<??>");
+    let size = sym.st_size as usize;
+    let Some(dst) = lib.base_addr.checked_add(rela.r_offset).filter(|&d| lib.segment_of(d, size).is_some()) else {
+        log::warn!("[dynlink] COPY {}: target 0x{:x} outside the library", name, rela.r_offset);
+        return;
+    };
     
-    unsafe {
-        while *ptr.add(len) != 0 && len < 256 {
-            len += 1;
+    // The first other library defining it, searched in load order like ld.so
+    let libraries = LIBRARIES.lock();
+    let source = libraries.iter().filter(|other| !core::ptr::eq(**other, lib)).find_map(|other| {
+        let def = other.lookup(name)?;
+        let len = size.min(def.st_size as usize);
+        Some((other.bytes(other.base_addr.checked_add(def.st_value)?, len)?, def.st_size))
+    });
+    match source {
+        Some((bytes, def_size)) => {
+            if def_size != sym.st_size {
+                log::warn!("[dynlink] COPY {}: {} bytes here but {} in its library", name, size, def_size);
+            }
+            unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), dst as *mut u8, bytes.len()) };
+            log::info!("[dynlink] COPY {} ({} bytes) @ 0x{:x}", name, bytes.len(), rela.r_offset);
         }
-        
-        let slice = core::slice::from_raw_parts(ptr, len);
-        String::from_utf8_lossy(slice).into_owned()
+        None => log::warn!("[dynlink] COPY {}: no loaded library defines it", name),
     }
 }
 
@@ -5,44 +5,7 @@
 use alloc::vec::Vec;
 use alloc::string::String;
 
-/// ELF64 Header
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64Header {
-    pub e_ident: [u8; 16],      // ELF identification
-    pub e_type: u16,            // Object file type
-    pub e_machine: u16,         // Machine type
-    pub e_version: u32,         // Object file version
-    pub e_entry: u64,           // Entry point address
-    pub e_phoff: u64,           // Program header offset
-    pub e_shoff: u64,           // Section header offset
-    pub e_flags: u32,           // Processor-specific flags
-    pub e_ehsize: u16,          // ELF header size
-    pub e_phentsize: u16,       // Size of program header entry
-    pub e_phnum: u16,           // Number of program header entries
-    pub e_shentsize: u16,       // Size of section header entry
-    pub e_shnum: u16,           // Number of section header entries
-    pub e_shstrndx: u16,        // Section name string table index
-}
-
-/// ELF64 Program Header
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64Phdr {
-    pub p_type: u32,            // Segment type
-    pub p_flags: u32,           // Segment flags
-    pub p_offset: u64,          // Offset in file
-    pub p_vaddr: u64,           // Virtual address in memory
-    pub p_paddr: u64,           // Physical address (ignored)
-    pub p_filesz: u64,          // Size of segment in file
-    pub p_memsz: u64,           // Size of segment in memory
-    pub p_align: u64,           // Alignment
-}
-
-// ELF constants
-pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
-pub const PT_LOAD: u32 = 1;
-pub const PT_INTERP: u32 = 3;
+use crate::elf::{ElfFile, PF_R, PF_W, PF_X, PT_GNU_RELRO, PT_GNU_STACK, PT_INTERP, PT_LOAD};
 
 /// Loaded ELF info
 pub struct LoadedElf {
@@ -52,59 +15,53 @@ pub struct LoadedElf {
     pub phdr_vaddr: u64,
     pub phnum: u16,
     pub phentsize: u16,
+    pub relro: Option<(u64, u64)>,  // (vaddr, size) to make read-only after relocation
+    pub stack_exec: bool,           // PT_GNU_STACK asked for an executable stack
 }
 
 pub struct LoadedSegment {
     pub vaddr: u64,
     pub size: u64,
+    pub prot: u32,      // PROT_* derived from p_flags
+}
+
+/// Translate ELF segment flags into mmap-style protection bits
+pub fn segment_prot(p_flags: u32) -> u32 {
+    use crate::mm::vma::{PROT_EXEC, PROT_READ, PROT_WRITE};
+    let mut prot = 0;
+    if p_flags & PF_R != 0 { prot |= PROT_READ; }
+    if p_flags & PF_W != 0 { prot |= PROT_WRITE; }
+    if p_flags & PF_X != 0 { prot |= PROT_EXEC; }
+    prot
 }
 
 /// Parse and load ELF from buffer
 pub fn load_elf(data: &[u8], base_addr: u64) -> Result<LoadedElf, &'static str> {
-    if data.len() < core::mem::size_of::<Elf64Header>() {
-        return Err("Data too small for ELF header");
-    }
-    
-    // Parse header
-    let header = unsafe {
-        core::ptr::read(data.as_ptr() as *const Elf64Header)
-    };
-    
-    // Verify magic
-    if header.e_ident[0..4] != ELF_MAGIC {
-        return Err("Invalid ELF magic");
-    }
-    
-    // Check 64-bit
-    if header.e_ident[4] != 2 {
-        return Err("Not a 64-bit ELF");
-    }
+    let file = ElfFile::parse(data)?;
+    let header = file.header();
     
     log::info!("[ELF] Entry point: 0x{:x}, Base: 0x{:x}", header.e_entry, base_addr);
     
     let mut segments = Vec::new();
     let mut interp = None;
     let mut phdr_vaddr = 0;
+    let mut relro = None;
+    let mut stack_exec = false; // Non-exec unless PT_GNU_STACK says otherwise
     
     // Load program headers
-    for i in 0..header.e_phnum {
-        let phdr_offset = header.e_phoff as usize + (i as usize * header.e_phentsize as usize);
-        
-        if phdr_offset + core::mem::size_of::<Elf64Phdr>() > data.len() {
-            return Err("Program header out of bounds");
-        }
-        
-        let phdr = unsafe {
-            core::ptr::read(data.as_ptr().add(phdr_offset) as *const Elf64Phdr)
-        };
-        
+    for phdr in file.program_headers() {
         if phdr.p_type == PT_LOAD {
-            let vaddr = base_addr + phdr.p_vaddr;
+            if phdr.p_filesz > phdr.p_memsz {
+                return Err("Segment file size exceeds memory size");
+            }
+            let src = file.segment_data(&phdr)?;
+            let vaddr = base_addr.checked_add(phdr.p_vaddr).ok_or("Segment address overflows")?;
+            vaddr.checked_add(phdr.p_memsz).ok_or("Segment end overflows")?;
             
             // Check if this segment contains the Program Headers
             // This is usually the first LOAD segment
             if phdr.p_offset == 0 {
-                phdr_vaddr = base_addr + header.e_phoff + phdr.p_vaddr;
+                phdr_vaddr = vaddr.wrapping_add(header.e_phoff);
             }
             
             log::info!(
@@ -116,7 +73,6 @@ pub fn load_elf(data: &[u8], base_addr: u64) -> Result<LoadedElf, &'static str>
             crate::mm::paging::make_user_accessible(vaddr, phdr.p_memsz);
             
             // Copy segment data
-            let src = &data[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize];
             unsafe {
                 core::ptr::copy_nonoverlapping(
                     src.as_ptr(),
@@ -135,9 +91,15 @@ pub fn load_elf(data: &[u8], base_addr: u64) -> Result<LoadedElf, &'static str>
             segments.push(LoadedSegment {
                 vaddr,
                 size: phdr.p_memsz,
+                prot: segment_prot(phdr.p_flags),
             });
+        } else if phdr.p_type == PT_GNU_STACK {
+            stack_exec = phdr.p_flags & PF_X != 0;
+        } else if phdr.p_type == PT_GNU_RELRO {
+            let vaddr = base_addr.checked_add(phdr.p_vaddr).ok_or("RELRO address overflows")?;
+            relro = Some((vaddr, phdr.p_memsz));
         } else if phdr.p_type == PT_INTERP {
-            let src = &data[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize];
+            let src = file.segment_data(&phdr)?;
             // Remove null terminator if present
             let path_bytes = if src.last() == Some(&0) {
                 &src[..src.len()-1]
@@ -158,6 +120,8 @@ pub fn load_elf(data: &[u8], base_addr: u64) -> Result<LoadedElf, &'static str>
         phdr_vaddr,
         phnum: header.e_phnum,
         phentsize: header.e_phentsize,
+        relro,
+        stack_exec,
     })
 }
 
@@ -185,7 +149,8 @@ pub struct AuxvEntry {
 }
 
 /// Set up user stack with argv, envp, and auxv
-/// Returns stack pointer
+/// Returns stack pointer, which points at argc and is 16-byte aligned as
+/// the SysV ABI requires at process entry
 pub fn setup_user_stack(
     stack_top: u64, 
     argv: &[&[u8]], 
@@ -235,8 +200,13 @@ pub fn setup_user_stack(
         argv_ptrs.insert(0, sp);
     }
     
-    // Align stack to 16 bytes
+    // Align stack to 16 bytes, with a pad word if an odd number of words
+    // follow: argc must end up on a 16-byte boundary
     sp &= !0xF;
+    let words = 2 * (auxv.len() + 1) + (envp.len() + 1) + (argv.len() + 1) + 1;
+    if words % 2 == 1 {
+        sp -= 8;
+    }
     
     // Push Auxv
     // First push AT_NULL
@@ -281,3 +251,46 @@ pub fn setup_user_stack(
     
     sp
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::{ELFCLASS64, ELF_MAGIC};
+
+    /// ELF header and one PT_LOAD program header
+    fn image(phoff: u64, offset: u64, filesz: u64, vaddr: u64, memsz: u64) -> Vec<u8> {
+        let mut data = alloc::vec![0u8; 64 + 56];
+        data[0..4].copy_from_slice(&ELF_MAGIC);
+        data[4] = ELFCLASS64;
+        data[32..40].copy_from_slice(&phoff.to_le_bytes());   // e_phoff
+        data[54..56].copy_from_slice(&56u16.to_le_bytes());   // e_phentsize
+        data[56..58].copy_from_slice(&1u16.to_le_bytes());    // e_phnum
+        data[64..68].copy_from_slice(&PT_LOAD.to_le_bytes()); // p_type
+        data[72..80].copy_from_slice(&offset.to_le_bytes());  // p_offset
+        data[80..88].copy_from_slice(&vaddr.to_le_bytes());   // p_vaddr
+        data[96..104].copy_from_slice(&filesz.to_le_bytes()); // p_filesz
+        data[104..112].copy_from_slice(&memsz.to_le_bytes()); // p_memsz
+        data
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        let whole = image(64, 0, 16, 0x400000, 16);
+        assert!(load_elf(&whole[..16], 0).is_err());      // shorter than the ELF header
+        assert!(load_elf(&whole[..64 + 20], 0).is_err()); // program header cut short
+    }
+
+    #[test]
+    fn rejects_headers_out_of_bounds() {
+        let bad = [
+            image(0x10000, 0, 16, 0x400000, 16),          // e_phoff past the end
+            image(u64::MAX - 8, 0, 16, 0x400000, 16),     // e_phoff + size overflows
+            image(64, 0x10000, 16, 0x400000, 16),         // p_offset past the end
+            image(64, 0, 0x10000, 0x400000, 0x10000),     // p_filesz past the end
+            image(64, 0, 0, u64::MAX - 0xFFF, 0x2000),    // p_vaddr + p_memsz overflows
+        ];
+        for data in &bad {
+            assert!(load_elf(data, 0).is_err());
+        }
+    }
+}
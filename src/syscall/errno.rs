@@ -0,0 +1,45 @@
+//! Error Numbers
+//!
+//! Handlers return `-errno::EBADF` and friends; the values come from
+//! aether_abi so user programs compare against the same numbers.
+
+pub use aether_abi::errno::*;
+
+use crate::fs::vfs::FsError;
+
+/// A (positive) error number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Errno(pub isize);
+
+/// Outcome of a handler that either yields a value or fails with an errno
+pub type SyscallResult = Result<usize, Errno>;
+
+/// Fold a `SyscallResult` into the syscall return convention
+/// (the value itself, or the negated errno)
+pub fn ret(result: SyscallResult) -> isize {
+    match result {
+        Ok(value) => value as isize,
+        Err(Errno(e)) => -e,
+    }
+}
+
+impl From<FsError> for Errno {
+    fn from(err: FsError) -> Self {
+        Errno(match err {
+            FsError::NotFound => ENOENT,
+            FsError::PermissionDenied => EACCES,
+            FsError::NotADirectory => ENOTDIR,
+            FsError::IsADirectory => EISDIR,
+            FsError::IOError => EIO,
+            FsError::WouldBlock => EAGAIN,
+            FsError::InvalidInput => EINVAL,
+            FsError::BrokenPipe => EPIPE,
+            FsError::AlreadyExists => EEXIST,
+            FsError::NoSpace => ENOSPC,
+            FsError::NotEmpty => ENOTEMPTY,
+            FsError::CrossDevice => EXDEV,
+            FsError::Busy => EBUSY,
+            FsError::TooManyLinks => ELOOP,
+        })
+    }
+}
@@ -1,61 +1,42 @@
 //! POSIX Syscall Interface
+//!
+//! Lock ordering: CURRENT_TASK, then ALL_TASKS, then a Task, then inode
+//! internals; never the reverse. `current_task()` reads the per-CPU copy
+//! and takes no lock at all. Handlers hold a Task lock only to copy out what they
+//! need: never across user-memory accesses, blocking I/O, wait queues or
+//! helpers that lock the task themselves (vmm, the fault path).
 
-mod elf;
+pub mod elf;
 pub mod dynlink;
+pub mod errno;
 
-use crate::sched::queue::CURRENT_TASK;
+use crate::sched::queue::{current_task, try_current_task};
 use crate::sched::task::FileDescriptor;
+use crate::mm::vma::{Backing, MAP_ANONYMOUS, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE};
 use crate::fs;
+use errno::{Errno, SyscallResult};
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Syscall numbers (Linux x86_64 ABI compatible)
-pub mod numbers {
-    // Core I/O
-    pub const SYS_READ: usize = 0;
-    pub const SYS_WRITE: usize = 1;
-    pub const SYS_OPEN: usize = 2;
-    pub const SYS_CLOSE: usize = 3;
-    pub const SYS_STAT: usize = 4;
-    pub const SYS_FSTAT: usize = 5;
-    pub const SYS_LSEEK: usize = 8;
-    pub const SYS_MMAP: usize = 9;
-    pub const SYS_BRK: usize = 12;
-    pub const SYS_IOCTL: usize = 16;
-    
-    // File descriptors
-    pub const SYS_DUP: usize = 32;
-    pub const SYS_DUP2: usize = 33;
-    pub const SYS_PIPE: usize = 22;
-    
-    // Process
-    pub const SYS_GETPID: usize = 39;
-    pub const SYS_CLONE: usize = 56;
-    pub const SYS_FORK: usize = 57;
-    pub const SYS_EXECVE: usize = 59;
-    pub const SYS_EXIT: usize = 60;
-    pub const SYS_WAIT4: usize = 61;
-    
-    // Time
-    pub const SYS_GETTIMEOFDAY: usize = 96;
-    pub const SYS_NANOSLEEP: usize = 35;
-    pub const SYS_CLOCK_GETTIME: usize = 228;
-    
-    // Memory
-    pub const SYS_MUNMAP: usize = 11;
-    
-    // Misc
-    pub const SYS_UNAME: usize = 63;
-    pub const SYS_GETCWD: usize = 79;
-    pub const SYS_CHDIR: usize = 80;
-    pub const SYS_GETUID: usize = 102;
-    pub const SYS_GETGID: usize = 104;
-    pub const SYS_GETEUID: usize = 107;
-    pub const SYS_GETEGID: usize = 108;
-}
+pub use aether_abi::syscall as numbers;
 
 /// Main syscall dispatcher
-pub fn dispatch(nr: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+pub fn dispatch(
+    nr: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
+) -> isize {
+    // Handlers use current_task() freely; sched::init installs PID 1 before
+    // anything can trap into the kernel
+    assert!(try_current_task().is_some(), "[syscall] {} with no current task", nr);
+    
     match nr {
         // Core I/O
         numbers::SYS_READ => sys_read(arg0, arg1, arg2),
@@ -64,24 +45,47 @@ pub fn dispatch(nr: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
         numbers::SYS_CLOSE => sys_close(arg0),
         numbers::SYS_STAT => sys_stat(arg0, arg1),
         numbers::SYS_FSTAT => sys_fstat(arg0, arg1),
+        numbers::SYS_POLL => sys_poll(arg0, arg1, arg2 as i32),
         numbers::SYS_LSEEK => sys_lseek(arg0, arg1 as i64, arg2),
-        numbers::SYS_MMAP => sys_mmap(arg0, arg1, arg2),
+        numbers::SYS_MMAP => sys_mmap(arg0, arg1, arg2, arg3, arg4, arg5),
         numbers::SYS_MUNMAP => sys_munmap(arg0, arg1),
+        numbers::SYS_MPROTECT => sys_mprotect(arg0, arg1, arg2),
         numbers::SYS_BRK => sys_brk(arg0),
         numbers::SYS_IOCTL => sys_ioctl(arg0, arg1, arg2),
+        numbers::SYS_PREAD64 => sys_pread64(arg0, arg1, arg2, arg3 as i64),
+        numbers::SYS_PWRITE64 => sys_pwrite64(arg0, arg1, arg2, arg3 as i64),
         
         // File descriptors
         numbers::SYS_DUP => sys_dup(arg0),
         numbers::SYS_DUP2 => sys_dup2(arg0, arg1),
+        numbers::SYS_DUP3 => sys_dup3(arg0, arg1, arg2),
         numbers::SYS_PIPE => sys_pipe(arg0),
+        numbers::SYS_SENDFILE => sys_sendfile(arg0, arg1, arg2, arg3),
+        numbers::SYS_SOCKETPAIR => sys_socketpair(arg0, arg1, arg2, arg3),
+        numbers::SYS_SOCKET => sys_socket(arg0, arg1, arg2),
+        numbers::SYS_BIND => sys_bind(arg0, arg1, arg2),
+        numbers::SYS_LISTEN => sys_listen(arg0, arg1 as i32),
+        numbers::SYS_ACCEPT => sys_accept(arg0, arg1, arg2),
+        numbers::SYS_CONNECT => sys_connect(arg0, arg1, arg2),
+        numbers::SYS_SENDTO => sys_sendto(arg0, arg1, arg2, arg3),
+        numbers::SYS_RECVFROM => sys_recvfrom(arg0, arg1, arg2, arg3),
+        
+        // Event notification
+        numbers::SYS_EPOLL_CREATE1 => sys_epoll_create1(arg0),
+        numbers::SYS_EVENTFD2 => sys_eventfd2(arg0, arg1),
+        numbers::SYS_EPOLL_CTL => sys_epoll_ctl(arg0, arg1, arg2, arg3),
+        numbers::SYS_EPOLL_WAIT => sys_epoll_wait(arg0, arg1, arg2 as i32, arg3 as i32),
         
         // Process
         numbers::SYS_GETPID => sys_getpid(),
         numbers::SYS_FORK => sys_fork(),
-        numbers::SYS_CLONE => sys_clone(arg0, arg1, arg2),
+        numbers::SYS_CLONE => sys_clone(arg0, arg1, arg2, arg3, arg4),
         numbers::SYS_EXECVE => sys_execve(arg0, arg1, arg2),
         numbers::SYS_EXIT => sys_exit(arg0),
+        numbers::SYS_EXIT_GROUP => sys_exit_group(arg0),
         numbers::SYS_WAIT4 => sys_wait4(arg0 as i32, arg1, arg2),
+        numbers::SYS_FUTEX => sys_futex(arg0, arg1, arg2 as u32, arg3),
+        numbers::SYS_TIMES => sys_times(arg0),
         
         // Time
         numbers::SYS_GETTIMEOFDAY => sys_gettimeofday(arg0, arg1),
@@ -89,17 +93,31 @@ pub fn dispatch(nr: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
         numbers::SYS_CLOCK_GETTIME => sys_clock_gettime(arg0, arg1),
         
         // Misc
+        numbers::SYS_GETRUSAGE => sys_getrusage(arg0 as i32, arg1),
         numbers::SYS_UNAME => sys_uname(arg0),
         numbers::SYS_GETCWD => sys_getcwd(arg0, arg1),
         numbers::SYS_CHDIR => sys_chdir(arg0),
+        numbers::SYS_RENAME => sys_rename(arg0, arg1),
+        numbers::SYS_UTIMENSAT => sys_utimensat(arg0 as i32, arg1, arg2, arg3),
+        numbers::SYS_CHMOD => sys_chmod(arg0, arg1 as u32),
+        numbers::SYS_FCHMOD => sys_fchmod(arg0, arg1 as u32),
+        numbers::SYS_CHOWN => sys_chown(arg0, arg1 as u32, arg2 as u32),
+        numbers::SYS_FCHOWN => sys_fchown(arg0, arg1 as u32, arg2 as u32),
+        numbers::SYS_UMASK => sys_umask(arg0 as u32),
+        numbers::SYS_GETDENTS64 => sys_getdents64(arg0, arg1, arg2),
         numbers::SYS_GETUID => sys_getuid(),
         numbers::SYS_GETGID => sys_getgid(),
         numbers::SYS_GETEUID => sys_geteuid(),
         numbers::SYS_GETEGID => sys_getegid(),
+        numbers::SYS_SETUID => sys_setuid(arg0 as u32),
+        numbers::SYS_SETGID => sys_setgid(arg0 as u32),
+        numbers::SYS_SETHOSTNAME => sys_sethostname(arg0, arg1),
+        numbers::SYS_STATFS => sys_statfs(arg0, arg1),
+        numbers::SYS_FSTATFS => sys_fstatfs(arg0, arg1),
         
         _ => {
             log::warn!("[syscall] Unimplemented syscall: {}", nr);
-            -38 // ENOSYS
+            -errno::ENOSYS
         }
     }
 }
@@ -110,6 +128,7 @@ unsafe fn get_user_string(ptr: usize, _len: usize) -> Option<String> {
     // For now, assume null-terminated if len not provided, or fixed length
     // But SYS_OPEN passes filename ptr, not len.
     // We need to scan for null or limit.
+    if ptr == 0 { return None; }
     let ptr = ptr as *const u8;
     let mut len = 0;
     while *ptr.add(len) != 0 {
@@ -120,13 +139,26 @@ unsafe fn get_user_string(ptr: usize, _len: usize) -> Option<String> {
     String::from_utf8(slice.to_vec()).ok()
 }
 
-fn sys_open(filename: usize, flags: usize, _mode: usize) -> isize {
-    let filename = unsafe { get_user_string(filename, 0) };
-    if filename.is_none() { return -2; } // ENOENT/EFAULT
-    let filename = filename.unwrap();
+/// A user path made absolute against the current working directory
+unsafe fn get_user_path(ptr: usize) -> Option<String> {
+    let path = get_user_string(ptr, 0)?;
+    let task_arc = current_task();
+    let task = task_arc.lock();
+    Some(fs::normalize(&task.cwd, &path))
+}
+
+fn sys_open(filename: usize, flags: usize, mode: usize) -> isize {
+    // No readable name at that address at all
+    let Some(filename) = (unsafe { get_user_path(filename) }) else {
+        return -errno::EFAULT;
+    };
+    
+    // A file O_CREAT makes gets `mode` minus the umask
+    let umask = current_task().lock().umask;
+    let mode = fs::vfs::FileMode(mode as u32 & fs::vfs::FileMode::MASK & !umask);
 
     // Call VFS open
-    match fs::open(&filename, flags as u32) {
+    match fs::open_with_mode(&filename, flags as u32, mode) {
         Ok(inode) => {
             let fd = FileDescriptor {
                 inode,
@@ -134,33 +166,102 @@ fn sys_open(filename: usize, flags: usize, _mode: usize) -> isize {
                 flags: flags as u32,
             };
             
-            // Add to current task
-            let current_lock = CURRENT_TASK.lock();
-            if let Some(task_arc) = current_lock.as_ref() {
-                let mut task = task_arc.lock();
-                task.add_file(fd) as isize
-            } else {
-                -1 // EACCES (No task)
-            }
+            current_task().lock().add_file(fd) as isize
         },
-        Err(_) => -2, // ENOENT
+        Err(e) => fs_errno(e),
+    }
+}
+
+/// Killed by writing to a pipe nobody reads
+const SIGPIPE: i32 = 13;
+
+/// open(2) flag: I/O on the descriptor returns EAGAIN instead of blocking
+const O_NONBLOCK: u32 = 0o4000;
+
+/// open(2)/dup3(2) flag: the descriptor is closed across execve
+pub const O_CLOEXEC: u32 = 0o2000000;
+
+/// Drop the descriptors marked O_CLOEXEC (or SOCK_/EPOLL_/EFD_CLOEXEC,
+/// the same bit), as execve does before starting the new image
+pub fn close_on_exec(task: &mut crate::sched::task::Task) {
+    for slot in task.fd_table.iter_mut() {
+        if slot.as_ref().is_some_and(|f| f.flags & O_CLOEXEC != 0) {
+            *slot = None;
+        }
+    }
+}
+
+/// Translate a VFS error into a negative errno
+fn fs_errno(err: fs::vfs::FsError) -> isize {
+    -Errno::from(err).0
+}
+
+/// Take what a read/write needs from an open file, so the I/O itself can
+/// run (and block, for pipes) without the task lock held
+fn file_snapshot(fd: usize) -> Option<(Arc<dyn fs::vfs::Inode>, u64, u32)> {
+    let task = current_task();
+    let task = task.lock();
+    task.get_file(fd).map(|file| (file.inode.clone(), file.offset, file.flags))
+}
+
+/// The inode behind `fd`, without keeping the task locked
+fn fd_inode(fd: usize) -> Option<Arc<dyn fs::vfs::Inode>> {
+    file_snapshot(fd).map(|(inode, _, _)| inode)
+}
+
+/// Move the file position on after I/O, unless the fd was reused meanwhile
+fn advance_offset(fd: usize, inode: &Arc<dyn fs::vfs::Inode>, bytes: usize) {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    if let Some(Some(file)) = task.fd_table.get_mut(fd) {
+        if Arc::ptr_eq(&file.inode, inode) {
+            file.offset += bytes as u64;
+        }
     }
 }
 
 fn sys_read(fd: usize, buf_ptr: usize, count: usize) -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-        if let Some(file_opt) = task.fd_table.get_mut(fd) {
-            if let Some(file) = file_opt {
-                let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, count) };
-                let bytes = file.inode.read_at(file.offset, buf);
-                file.offset += bytes as u64;
-                return bytes as isize;
-            }
+    if is_console_stdin(fd) {
+        return read_console_stdin(buf_ptr, count);
+    }
+    let (inode, offset, flags) = match file_snapshot(fd) {
+        Some(f) => f,
+        None => return -errno::EBADF,
+    };
+    if inode.metadata().file_type == fs::vfs::FileType::Directory {
+        return -errno::EISDIR;
+    }
+    if !user_buffer_ok(buf_ptr, count) {
+        return -errno::EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, count) };
+    match inode.read(offset, buf, flags & O_NONBLOCK != 0) {
+        Ok(bytes) => {
+            advance_offset(fd, &inode, bytes);
+            bytes as isize
         }
+        Err(e) => fs_errno(e),
+    }
+}
+
+/// Read typed keys, blocking until there is at least one
+fn read_console_stdin(buf_ptr: usize, count: usize) -> isize {
+    use crate::drivers::console::{input_pending, read_input};
+    
+    if count == 0 {
+        return 0;
+    }
+    if !user_buffer_ok(buf_ptr, count) {
+        return -errno::EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, count) };
+    loop {
+        let len = read_input(buf);
+        if len > 0 {
+            return len as isize;
+        }
+        POLL_WAIT.wait_until(input_pending);
     }
-    -9 // EBADF
 }
 
 fn sys_write(fd: usize, buf_ptr: usize, count: usize) -> isize {
@@ -168,6 +269,7 @@ fn sys_write(fd: usize, buf_ptr: usize, count: usize) -> isize {
     if fd == 1 || fd == 2 {
         unsafe {
             let slice = core::slice::from_raw_parts(buf_ptr as *const u8, count);
+            crate::drivers::console::write(slice);
             if let Ok(s) = core::str::from_utf8(slice) {
                 // Use kernel console for now
                 // Since this is bare metal, we use console_println from aether-user or just log
@@ -177,39 +279,141 @@ fn sys_write(fd: usize, buf_ptr: usize, count: usize) -> isize {
         return count as isize;
     }
 
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-         if let Some(file_opt) = task.fd_table.get_mut(fd) {
-            if let Some(file) = file_opt {
-                let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, count) };
-                let bytes = file.inode.write_at(file.offset, buf);
-                file.offset += bytes as u64;
-                return bytes as isize;
-            }
+    let (inode, offset, flags) = match file_snapshot(fd) {
+        Some(f) => f,
+        None => return -errno::EBADF,
+    };
+    if !user_buffer_ok(buf_ptr, count) {
+        return -errno::EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, count) };
+    match inode.write(offset, buf, flags & O_NONBLOCK != 0) {
+        Ok(bytes) => {
+            advance_offset(fd, &inode, bytes);
+            bytes as isize
+        }
+        Err(fs::vfs::FsError::BrokenPipe) => {
+            // No signal dispositions yet, so SIGPIPE always takes its default action
+            log::debug!("[syscall::write] fd {} has no readers, raising SIGPIPE", fd);
+            crate::sched::exit_current(SIGPIPE)
         }
+        Err(e) => fs_errno(e),
     }
-    -9 // EBADF
 }
 
-fn sys_exit(code: usize) -> isize {
-    log::info!("[syscall::exit] Process exited with code {}", code);
+/// Whether [ptr, ptr + len) is a plausible user buffer
+/// There is no per-page access check yet; this only rejects NULL and
+/// ranges that wrap around.
+fn user_buffer_ok(ptr: usize, len: usize) -> bool {
+    len == 0 || (ptr != 0 && ptr.checked_add(len).is_some())
+}
+
+/// Inode for positioned I/O on `fd`, or the errno to fail with
+fn positioned_inode(fd: usize, buf_ptr: usize, count: usize, offset: i64) -> Result<Arc<dyn fs::vfs::Inode>, Errno> {
+    let inode = fd_inode(fd).ok_or(Errno(errno::EBADF))?;
+    if !user_buffer_ok(buf_ptr, count) {
+        return Err(Errno(errno::EFAULT));
+    }
+    if offset < 0 {
+        return Err(Errno(errno::EINVAL));
+    }
+    if matches!(inode.metadata().file_type, fs::vfs::FileType::Pipe | fs::vfs::FileType::Socket) {
+        return Err(Errno(errno::ESPIPE));
+    }
+    Ok(inode)
+}
+
+/// Read at `offset` without moving the file position
+fn sys_pread64(fd: usize, buf_ptr: usize, count: usize, offset: i64) -> isize {
+    let result: SyscallResult = positioned_inode(fd, buf_ptr, count, offset).map(|inode| {
+        let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, count) };
+        inode.read_at(offset as u64, buf)
+    });
+    errno::ret(result)
+}
+
+/// Write at `offset` without moving the file position
+fn sys_pwrite64(fd: usize, buf_ptr: usize, count: usize, offset: i64) -> isize {
+    let result: SyscallResult = positioned_inode(fd, buf_ptr, count, offset).map(|inode| {
+        let buf = unsafe { core::slice::from_raw_parts(buf_ptr as *const u8, count) };
+        inode.write_at(offset as u64, buf)
+    });
+    errno::ret(result)
+}
+
+/// Bounce buffer size for sendfile
+const SENDFILE_CHUNK: usize = 4096;
+
+/// Copy up to `count` bytes from `in_fd` to `out_fd` inside the kernel
+/// With a non-NULL `offset_ptr` the source is read from `*offset_ptr`
+/// (updated afterwards) and its file position is left alone. Stops early on
+/// a short read or write and returns the bytes transferred.
+fn sys_sendfile(out_fd: usize, in_fd: usize, offset_ptr: usize, count: usize) -> isize {
+    let (in_inode, in_offset, _) = match file_snapshot(in_fd) {
+        Some(f) => f,
+        None => return -errno::EBADF,
+    };
+    // stdout/stderr without an entry go to the console, like write()
+    let out = file_snapshot(out_fd);
+    if out.is_none() && out_fd != 1 && out_fd != 2 {
+        return -errno::EBADF;
+    }
+    let mut offset = if offset_ptr != 0 {
+        let start = unsafe { *(offset_ptr as *const i64) };
+        if start < 0 {
+            return -errno::EINVAL;
+        }
+        start as u64
+    } else {
+        in_offset
+    };
     
-    // Update task state
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-        task.state = crate::sched::task::TaskState::Terminated;
+    let mut bounce = alloc::vec![0u8; SENDFILE_CHUNK.min(count)];
+    let mut out_offset = out.as_ref().map_or(0, |(_, off, _)| *off);
+    let mut total = 0;
+    while total < count {
+        let want = bounce.len().min(count - total);
+        let read = in_inode.read_at(offset, &mut bounce[..want]);
+        if read == 0 {
+            break;
+        }
+        let written = match &out {
+            Some((inode, _, _)) => inode.write_at(out_offset, &bounce[..read]),
+            None => {
+                crate::drivers::console::write(&bounce[..read]);
+                read
+            }
+        };
+        offset += written as u64;
+        out_offset += written as u64;
+        total += written;
+        if written < read || read < want {
+            break;
+        }
     }
     
-    // Trigger scheduler (TODO)
-    loop {
-        // Halt cpu to simplify
-        #[cfg(target_arch = "x86_64")]
-        unsafe { core::arch::asm!("hlt") };
-        #[cfg(target_arch = "aarch64")]
-        unsafe { core::arch::asm!("wfi") };
+    if offset_ptr != 0 {
+        unsafe { *(offset_ptr as *mut i64) = offset as i64 };
+    } else {
+        advance_offset(in_fd, &in_inode, total);
+    }
+    if let Some((inode, _, _)) = &out {
+        advance_offset(out_fd, inode, total);
     }
+    total as isize
+}
+
+/// End the calling thread only
+fn sys_exit(code: usize) -> isize {
+    log::info!("[syscall::exit] Process exited with code {}", code);
+    // wait(2) status: exit code in bits 8..15
+    crate::sched::exit_current(((code & 0xff) << 8) as i32)
+}
+
+/// End every thread of the calling process (what libc's exit() uses)
+fn sys_exit_group(code: usize) -> isize {
+    log::info!("[syscall::exit_group] Process exited with code {}", code);
+    crate::sched::exit_group(((code & 0xff) << 8) as i32)
 }
 
 // ============================================================================
@@ -217,176 +421,990 @@ fn sys_exit(code: usize) -> isize {
 // ============================================================================
 
 /// Program break management (heap allocation)
-/// For now, we use a simple linear allocator
-static mut PROGRAM_BREAK: usize = 0x800000; // Start at 8MB
-
+/// For now, we use a simple linear allocator per task
 fn sys_brk(addr: usize) -> isize {
-    unsafe {
-        if addr == 0 {
-            // Query current break
-            return PROGRAM_BREAK as isize;
-        }
+    use crate::sched::task::{USER_HEAP_START, USER_HEAP_END};
+    
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    
+    if addr == 0 {
+        // Query current break
+        return task.brk as isize;
+    }
+    
+    if addr >= USER_HEAP_START && addr <= USER_HEAP_END {
+        // Valid range (8MB - 12MB)
+        let old_break = task.brk;
+        task.brk = addr;
         
-        if addr >= 0x800000 && addr <= 0x1000000 {
-            // Valid range (8MB - 16MB)
-            let old_break = PROGRAM_BREAK;
-            PROGRAM_BREAK = addr;
-            
-            // Make the new region user-accessible
-            crate::mm::paging::make_user_accessible(old_break as u64, (addr - old_break) as u64);
-            
-            log::debug!("[syscall::brk] Program break: 0x{:x} -> 0x{:x}", old_break, addr);
-            return addr as isize;
+        // Record the new region; frames are allocated on first touch
+        let page = crate::mm::PAGE_SIZE;
+        let old_end = (old_break + page - 1) & !(page - 1);
+        let new_end = (addr + page - 1) & !(page - 1);
+        if new_end > old_end {
+            task.vmas.insert(old_end, new_end, PROT_READ | PROT_WRITE,
+                             MAP_PRIVATE | MAP_ANONYMOUS, Backing::Anonymous);
+        } else if new_end < old_end {
+            task.vmas.remove(new_end, old_end);
+            let freed = crate::mm::paging::unmap_user_range(new_end as u64, (old_end - new_end) as u64);
+            task.rss_unmap(freed);
         }
         
-        -12 // ENOMEM
+        log::debug!("[syscall::brk] Program break: 0x{:x} -> 0x{:x}", old_break, addr);
+        return addr as isize;
     }
+    
+    -errno::ENOMEM
 }
 
 /// Get process ID
 fn sys_getpid() -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let task = task_arc.lock();
-        return task.id as isize;
-    }
-    1 // Default PID if no task
+    current_task().lock().id as isize
 }
 
-/// Memory map (simplified stub)
-fn sys_mmap(addr: usize, length: usize, _prot: usize) -> isize {
-    // Simple anonymous mapping at requested address
-    if addr == 0 {
-        // Kernel chooses address
-        unsafe {
-            let new_addr = PROGRAM_BREAK;
-            let aligned_len = (length + 4095) & !4095;
-            PROGRAM_BREAK += aligned_len;
-            
-            crate::mm::paging::make_user_accessible(new_addr as u64, aligned_len as u64);
-            log::debug!("[syscall::mmap] Mapped {} bytes at 0x{:x}", aligned_len, new_addr);
-            return new_addr as isize;
-        }
+/// Memory map
+/// Only anonymous mappings are supported; without MAP_FIXED the address is
+/// taken as a hint and ignored, like Linux does when the hint is taken.
+fn sys_mmap(addr: usize, length: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    use crate::mm::vma::{MAP_FIXED, MAP_SHARED};
+    use crate::sched::task::{USER_MMAP_START, USER_MMAP_END};
+    
+    let flags = flags as u32;
+    if length == 0 || (flags & MAP_SHARED != 0) == (flags & MAP_PRIVATE != 0) {
+        return -errno::EINVAL;
+    }
+    if flags & MAP_ANONYMOUS == 0 {
+        log::warn!("[syscall::mmap] File mapping of fd {} (offset 0x{:x}) not supported", fd, offset);
+        return -errno::ENODEV;
+    }
+    let vma_flags = flags & (MAP_SHARED | MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED);
+    
+    let Some(aligned_len) = page_len(length) else {
+        return -errno::ENOMEM;
+    };
+    
+    // Anonymous mapping, backed lazily by the page-fault handler
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    
+    if flags & MAP_FIXED == 0 {
+        // Kernel chooses address: first hole in the mmap window
+        let new_addr = match task.vmas.find_free(USER_MMAP_START, USER_MMAP_END, aligned_len) {
+            Some(a) => a,
+            None => {
+                log::warn!("[syscall::mmap] Out of address space for {} bytes", aligned_len);
+                return -errno::ENOMEM;
+            }
+        };
+        task.vmas.insert(new_addr, new_addr + aligned_len, prot as u32, vma_flags, Backing::Anonymous);
+        log::debug!("[syscall::mmap] Mapped {} bytes at 0x{:x}", aligned_len, new_addr);
+        return new_addr as isize;
     }
     
-    // Fixed address mapping
-    let aligned_len = (length + 4095) & !4095;
-    crate::mm::paging::make_user_accessible(addr as u64, aligned_len as u64);
+    // Fixed address mapping; the null page stays unmapped
+    if addr & 4095 != 0 {
+        return -errno::EINVAL;
+    }
+    if addr < crate::mm::PAGE_SIZE {
+        return -errno::EPERM;
+    }
+    let Some(end) = user_range_end(addr, aligned_len) else {
+        return -errno::ENOMEM;
+    };
+    // Whatever was mapped there is replaced, frames included
+    let freed = crate::mm::paging::unmap_user_range(addr as u64, aligned_len as u64);
+    task.rss_unmap(freed);
+    task.vmas.insert(addr, end, prot as u32, vma_flags, Backing::Anonymous);
     log::debug!("[syscall::mmap] Mapped {} bytes at 0x{:x} (fixed)", aligned_len, addr);
     addr as isize
 }
 
+/// `length` rounded up to whole pages, or None if that overflows
+fn page_len(length: usize) -> Option<usize> {
+    let page = crate::mm::PAGE_SIZE;
+    length.checked_add(page - 1).map(|len| len & !(page - 1))
+}
+
+/// End of the user range [addr, addr + len), or None if it leaves user space
+fn user_range_end(addr: usize, len: usize) -> Option<usize> {
+    use crate::sched::task::USER_SPACE_END;
+    addr.checked_add(len).filter(|&end| end <= USER_SPACE_END)
+}
+
 // ============================================================================
 // File Syscalls (Phase 14 - POSIX)
 // ============================================================================
 
 fn sys_close(fd: usize) -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    if fd < task.fd_table.len() {
+        task.fd_table[fd] = None;
+        return 0;
+    }
+    -errno::EBADF
+}
+
+/// st_mode for an inode: file type bits plus its permission bits
+fn stat_mode(meta: &fs::vfs::Metadata) -> u32 {
+    use fs::vfs::FileType;
+    let kind = match meta.file_type {
+        FileType::File => 0o100000,      // S_IFREG
+        FileType::Directory => 0o040000, // S_IFDIR
+        FileType::Device => 0o020000,    // S_IFCHR
+        FileType::Pipe => 0o010000,      // S_IFIFO
+        FileType::Symlink => 0o120000,   // S_IFLNK
+        FileType::Socket => 0o140000,    // S_IFSOCK
+    };
+    kind | (meta.mode.0 & fs::vfs::FileMode::MASK)
+}
+
+/// Fill a struct stat (x86_64 layout, 144 bytes)
+unsafe fn write_stat(statbuf: usize, meta: &fs::vfs::Metadata) {
+    let buf = statbuf as *mut u8;
+    core::ptr::write_bytes(buf, 0, 144);
+    *(buf.add(16) as *mut u64) = 1;                   // st_nlink
+    *(buf.add(24) as *mut u32) = stat_mode(meta);     // st_mode
+    *(buf.add(28) as *mut u32) = meta.uid;            // st_uid
+    *(buf.add(32) as *mut u32) = meta.gid;            // st_gid
+    *(buf.add(48) as *mut i64) = meta.size as i64;    // st_size
+    *(buf.add(56) as *mut i64) = 4096;                // st_blksize
+    *(buf.add(64) as *mut i64) = meta.size.div_ceil(512) as i64; // st_blocks
+    // st_atim, st_mtim, st_ctim as { tv_sec, tv_nsec }
+    let times = [meta.times.atime, meta.times.mtime, meta.times.ctime];
+    for (i, ns) in times.iter().enumerate() {
+        let ts = buf.add(72 + 16 * i) as *mut u64;
+        *ts = ns / 1_000_000_000;
+        *ts.add(1) = ns % 1_000_000_000;
+    }
+}
+
+fn sys_stat(path: usize, statbuf: usize) -> isize {
+    let path = match unsafe { get_user_path(path) } {
+        Some(p) => p,
+        None => return -errno::EFAULT,
+    };
+    if statbuf == 0 {
+        return -errno::EFAULT;
+    }
+    match fs::open(&path, 0) {
+        Ok(inode) => {
+            unsafe { write_stat(statbuf, &inode.metadata()) };
+            0
+        }
+        Err(e) => fs_errno(e),
+    }
+}
+
+fn sys_fstat(fd: usize, statbuf: usize) -> isize {
+    use fs::vfs::{FileMode, FileType, Metadata};
+    
+    if statbuf == 0 {
+        return -errno::EFAULT;
+    }
+    let meta = match fd_inode(fd) {
+        Some(inode) => inode.metadata(),
+        // The console behind stdio without a table entry
+        None if fd <= 2 => Metadata {
+            size: 0,
+            mode: FileMode::all(FileMode::READ | FileMode::WRITE),
+            file_type: FileType::Device,
+            times: fs::vfs::FileTimes::default(),
+            uid: 0,
+            gid: 0,
+        },
+        None => return -errno::EBADF,
+    };
+    unsafe { write_stat(statbuf, &meta) };
+    0
+}
+
+/// Fill a struct statfs (x86_64 layout, 120 bytes)
+unsafe fn write_statfs(buf: usize, st: &fs::vfs::StatFs) {
+    const ST_RDONLY: u64 = 1;
+    let out = core::slice::from_raw_parts_mut(buf as *mut u64, 15);
+    out.fill(0);
+    out[0] = st.magic;          // f_type
+    out[1] = st.block_size;     // f_bsize
+    out[2] = st.blocks;         // f_blocks
+    out[3] = st.blocks_free;    // f_bfree
+    out[4] = st.blocks_free;    // f_bavail (no root reserve)
+    out[5] = st.files;          // f_files
+    out[6] = st.files_free;     // f_ffree
+    // out[7]: f_fsid left zero
+    out[8] = st.name_max;       // f_namelen
+    out[9] = st.block_size;     // f_frsize
+    out[10] = if st.read_only { ST_RDONLY } else { 0 }; // f_flags
+}
+
+fn sys_statfs(path: usize, buf: usize) -> isize {
+    let path = match unsafe { get_user_path(path) } {
+        Some(p) => p,
+        None => return -errno::EFAULT,
+    };
+    if buf == 0 {
+        return -errno::EFAULT;
+    }
+    match fs::open(&path, 0) {
+        Ok(inode) => {
+            unsafe { write_statfs(buf, &inode.statfs()) };
+            0
+        }
+        Err(e) => fs_errno(e),
+    }
+}
+
+fn sys_fstatfs(fd: usize, buf: usize) -> isize {
+    if buf == 0 {
+        return -errno::EFAULT;
+    }
+    match fd_inode(fd) {
+        Some(inode) => {
+            unsafe { write_statfs(buf, &inode.statfs()) };
+            0
+        }
+        None => -errno::EBADF,
+    }
+}
+
+fn sys_rename(oldpath: usize, newpath: usize) -> isize {
+    let (old, new) = match unsafe { (get_user_path(oldpath), get_user_path(newpath)) } {
+        (Some(old), Some(new)) => (old, new),
+        _ => return -errno::EFAULT,
+    };
+    match fs::rename(&old, &new) {
+        Ok(()) => {
+            log::debug!("[syscall::rename] {} -> {}", old, new);
+            0
+        }
+        Err(e) => fs_errno(e),
+    }
+}
+
+const AT_FDCWD: i32 = -100;
+const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+/// utimensat tv_nsec values meaning "the current time" and "leave as is"
+const UTIME_NOW: i64 = (1 << 30) - 1;
+const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+/// Set a file's access and modification times
+/// A NULL path means `dirfd` itself (futimens). Other relative paths only
+/// work against AT_FDCWD: descriptors don't remember their paths.
+fn sys_utimensat(dirfd: i32, path: usize, times: usize, flags: usize) -> isize {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+        return -errno::EINVAL;
+    }
+    let now = crate::sched::clock::uptime_ns();
+    // One timespec of the pair: Some(new time) or None to keep the old one
+    let read = |i: usize| -> Result<Option<u64>, isize> {
+        if times == 0 {
+            return Ok(Some(now));
+        }
+        let ts = times + 16 * i;
+        match unsafe { *(ts as *const i64).add(1) } {
+            UTIME_NOW => Ok(Some(now)),
+            UTIME_OMIT => Ok(None),
+            _ => read_timespec(ts).map(Some).ok_or(-errno::EINVAL),
+        }
+    };
+    let (atime, mtime) = match (read(0), read(1)) {
+        (Ok(a), Ok(m)) => (a, m),
+        (Err(e), _) | (_, Err(e)) => return e,
+    };
+    
+    let inode = if path == 0 {
+        match fd_inode(dirfd as usize) {
+            Some(inode) => inode,
+            None => return -errno::EBADF,
+        }
+    } else {
+        let Some(path) = (unsafe { get_user_string(path, 0) }) else {
+            return -errno::EFAULT;
+        };
+        if !path.starts_with('/') && dirfd != AT_FDCWD {
+            return -errno::EOPNOTSUPP;
+        }
+        let path = fs::normalize(&current_task().lock().cwd, &path);
+        match fs::open(&path, 0) {
+            Ok(inode) => inode,
+            Err(e) => return fs_errno(e),
+        }
+    };
+    if atime.is_none() && mtime.is_none() {
+        return 0;
+    }
+    match inode.set_times(atime, mtime) {
+        Ok(()) => 0,
+        Err(e) => fs_errno(e),
+    }
+}
+
+/// Inode at a user path, or a negative errno
+fn path_inode(path: usize) -> Result<Arc<dyn fs::vfs::Inode>, isize> {
+    let path = unsafe { get_user_path(path) }.ok_or(-errno::EFAULT)?;
+    fs::open(&path, 0).map_err(fs_errno)
+}
+
+fn sys_chmod(path: usize, mode: u32) -> isize {
+    match path_inode(path) {
+        Ok(inode) => chmod_inode(&*inode, mode),
+        Err(e) => e,
+    }
+}
+
+fn sys_fchmod(fd: usize, mode: u32) -> isize {
+    match fd_inode(fd) {
+        Some(inode) => chmod_inode(&*inode, mode),
+        None => -errno::EBADF,
+    }
+}
+
+fn chmod_inode(inode: &dyn fs::vfs::Inode, mode: u32) -> isize {
+    match inode.set_mode(fs::vfs::FileMode(mode & fs::vfs::FileMode::MASK)) {
+        Ok(()) => 0,
+        Err(e) => fs_errno(e),
+    }
+}
+
+fn sys_chown(path: usize, uid: u32, gid: u32) -> isize {
+    match path_inode(path) {
+        Ok(inode) => chown_inode(&*inode, uid, gid),
+        Err(e) => e,
+    }
+}
+
+fn sys_fchown(fd: usize, uid: u32, gid: u32) -> isize {
+    match fd_inode(fd) {
+        Some(inode) => chown_inode(&*inode, uid, gid),
+        None => -errno::EBADF,
+    }
+}
+
+/// An id of -1 leaves that field unchanged
+fn chown_inode(inode: &dyn fs::vfs::Inode, uid: u32, gid: u32) -> isize {
+    let keep = |id: u32| (id != u32::MAX).then_some(id);
+    match inode.set_owner(keep(uid), keep(gid)) {
+        Ok(()) => 0,
+        Err(e) => fs_errno(e),
+    }
+}
+
+/// Set the file creation mask, returning the old one
+fn sys_umask(mask: u32) -> isize {
+    let task = current_task();
+    let mut task = task.lock();
+    core::mem::replace(&mut task.umask, mask & 0o777) as isize
+}
+
+/// struct linux_dirent64 d_type values
+fn dirent_type(file_type: fs::vfs::FileType) -> u8 {
+    use fs::vfs::FileType;
+    match file_type {
+        FileType::Pipe => 1,      // DT_FIFO
+        FileType::Device => 2,    // DT_CHR
+        FileType::Directory => 4, // DT_DIR
+        FileType::File => 8,      // DT_REG
+        FileType::Symlink => 10,  // DT_LNK
+        FileType::Socket => 12,   // DT_SOCK
+    }
+}
+
+/// Read directory entries as struct linux_dirent64
+/// The descriptor offset counts entries already returned.
+fn sys_getdents64(fd: usize, dirp: usize, count: usize) -> isize {
+    let (inode, offset, _) = match file_snapshot(fd) {
+        Some(f) => f,
+        None => return -errno::EBADF,
+    };
+    if !user_buffer_ok(dirp, count) {
+        return -errno::EFAULT;
+    }
+    let entries = match inode.poll() {
+        Ok(e) => e,
+        Err(e) => return fs_errno(e),
+    };
+    
+    let out = unsafe { core::slice::from_raw_parts_mut(dirp as *mut u8, count) };
+    let mut pos = 0;
+    let mut consumed = 0;
+    for (index, (name, ino)) in entries.iter().enumerate().skip(offset as usize) {
+        // d_ino, d_off, d_reclen, d_type, then the NUL-terminated name
+        let reclen = (19 + name.len() + 1).next_multiple_of(8);
+        if pos + reclen > out.len() {
+            if pos == 0 {
+                return -errno::EINVAL; // buffer too small for one entry
+            }
+            break;
+        }
+        let d_type = inode.lookup(name).map_or(0, |child| dirent_type(child.metadata().file_type));
+        let rec = &mut out[pos..pos + reclen];
+        rec.fill(0);
+        rec[0..8].copy_from_slice(&ino.to_ne_bytes());
+        rec[8..16].copy_from_slice(&(index as i64 + 1).to_ne_bytes());
+        rec[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+        rec[18] = d_type;
+        rec[19..19 + name.len()].copy_from_slice(name.as_bytes());
+        pos += reclen;
+        consumed += 1;
+    }
+    advance_offset(fd, &inode, consumed);
+    pos as isize
+}
+
+fn sys_lseek(fd: usize, offset: i64, whence: usize) -> isize {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    if let Some(Some(file)) = task.fd_table.get_mut(fd) {
+        match whence {
+            0 => file.offset = offset as u64,           // SEEK_SET
+            1 => file.offset = (file.offset as i64 + offset) as u64, // SEEK_CUR
+            2 => { /* SEEK_END - would need file size */ }
+            _ => return -errno::EINVAL,
+        }
+        return file.offset as isize;
+    }
+    -errno::EBADF
+}
+
+fn sys_ioctl(_fd: usize, cmd: usize, _arg: usize) -> isize {
+    // Common ioctl commands - return success for terminal queries
+    match cmd {
+        0x5401 => 0,  // TCGETS - pretend we're a terminal
+        0x5402 => 0,  // TCSETS
+        0x5413 => {   // TIOCGWINSZ - get window size
+            // Would fill in winsize struct if arg is valid
+            0
+        }
+        _ => {
+            log::debug!("[syscall::ioctl] Unknown cmd: 0x{:x}", cmd);
+            -errno::ENOTTY
+        }
+    }
+}
+
+fn sys_dup(oldfd: usize) -> isize {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    if let Some(file) = task.get_file(oldfd).cloned() {
+        return task.add_file(file) as isize;
+    }
+    -errno::EBADF
+}
+
+fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    if let Some(file) = task.get_file(oldfd).cloned() {
+        // Extend table if needed
+        while task.fd_table.len() <= newfd {
+            task.fd_table.push(None);
+        }
+        task.fd_table[newfd] = Some(file);
+        return newfd as isize;
+    }
+    -errno::EBADF
+}
+
+/// dup2 with flags: O_CLOEXEC marks `newfd` close-on-exec, and
+/// `oldfd == newfd` is an error rather than a no-op
+fn sys_dup3(oldfd: usize, newfd: usize, flags: usize) -> isize {
+    if flags & !(O_CLOEXEC as usize) != 0 || oldfd == newfd {
+        return -errno::EINVAL;
+    }
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    let Some(mut file) = task.get_file(oldfd).cloned() else {
+        return -errno::EBADF;
+    };
+    file.flags = file.flags & !O_CLOEXEC | flags as u32;
+    while task.fd_table.len() <= newfd {
+        task.fd_table.push(None);
+    }
+    task.fd_table[newfd] = Some(file);
+    newfd as isize
+}
+
+fn sys_pipe(pipefd: usize) -> isize {
+    if pipefd == 0 {
+        return -errno::EFAULT;
+    }
+    let (read_end, write_end) = fs::pipe::pipe();
+    let (rfd, wfd) = {
+        let task_arc = current_task();
         let mut task = task_arc.lock();
-        if fd < task.fd_table.len() {
-            task.fd_table[fd] = None;
-            return 0;
+        (task.add_file(FileDescriptor { inode: read_end, offset: 0, flags: 0 }),   // O_RDONLY
+         task.add_file(FileDescriptor { inode: write_end, offset: 0, flags: 1 }))  // O_WRONLY
+    };
+    unsafe {
+        let fds = pipefd as *mut i32;
+        *fds = rfd as i32;
+        *fds.add(1) = wfd as i32;
+    }
+    log::debug!("[syscall::pipe] fds [{}, {}]", rfd, wfd);
+    0
+}
+
+/// Create a connected pair of AF_UNIX stream sockets
+fn sys_socketpair(domain: usize, type_: usize, protocol: usize, sv: usize) -> isize {
+    use crate::fs::socket::{socketpair, AF_UNIX, SOCK_CLOEXEC, SOCK_NONBLOCK, SOCK_STREAM};
+    
+    if domain != AF_UNIX {
+        return -errno::EAFNOSUPPORT;
+    }
+    if type_ & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != SOCK_STREAM {
+        return -errno::EINVAL;
+    }
+    if protocol != 0 {
+        return -errno::EPROTONOSUPPORT;
+    }
+    if sv == 0 {
+        return -errno::EFAULT;
+    }
+    // SOCK_NONBLOCK is O_NONBLOCK and SOCK_CLOEXEC is O_CLOEXEC, O_RDWR for both
+    let flags = (type_ & (SOCK_NONBLOCK | SOCK_CLOEXEC)) as u32 | 2;
+    let (a, b) = socketpair();
+    let (fd_a, fd_b) = {
+        let task_arc = current_task();
+        let mut task = task_arc.lock();
+        (task.add_file(FileDescriptor { inode: a, offset: 0, flags }),
+         task.add_file(FileDescriptor { inode: b, offset: 0, flags }))
+    };
+    unsafe {
+        let fds = sv as *mut i32;
+        *fds = fd_a as i32;
+        *fds.add(1) = fd_b as i32;
+    }
+    log::debug!("[syscall::socketpair] fds [{}, {}]", fd_a, fd_b);
+    0
+}
+
+// ============================================================================
+// Sockets (AF_INET, loopback only)
+// ============================================================================
+
+const IPPROTO_TCP: usize = 6;
+/// recvfrom/sendto flag: this call only, as if O_NONBLOCK
+const MSG_DONTWAIT: usize = 0x40;
+/// sizeof(struct sockaddr_in)
+const SOCKADDR_IN_LEN: usize = 16;
+
+/// Translate a socket error into a negative errno
+fn net_errno(err: crate::net::NetError) -> isize {
+    use crate::net::NetError;
+    match err {
+        NetError::AddrInUse => -errno::EADDRINUSE,
+        NetError::AddrNotAvailable => -errno::EADDRNOTAVAIL,
+        NetError::NetworkUnreachable => -errno::ENETUNREACH,
+        NetError::ConnectionReset => -errno::ECONNRESET,
+        NetError::AlreadyConnected => -errno::EISCONN,
+        NetError::NotConnected => -errno::ENOTCONN,
+        NetError::ConnectionRefused => -errno::ECONNREFUSED,
+        NetError::InvalidState => -errno::EINVAL,
+        NetError::WouldBlock => -errno::EAGAIN,
+    }
+}
+
+/// Run `f` on the TCP socket behind `fd`, with its O_NONBLOCK setting
+fn with_tcp_socket(fd: usize, f: impl FnOnce(&crate::net::tcp::TcpSocket, bool) -> isize) -> isize {
+    let Some((inode, _, flags)) = file_snapshot(fd) else {
+        return -errno::EBADF;
+    };
+    match inode.as_any().and_then(|a| a.downcast_ref::<crate::net::tcp::TcpSocket>()) {
+        Some(socket) => f(socket, flags & O_NONBLOCK != 0),
+        None => -errno::ENOTSOCK,
+    }
+}
+
+/// Parse a user struct sockaddr_in
+fn read_sockaddr(ptr: usize, len: usize) -> Result<crate::net::SocketAddr, isize> {
+    if len < SOCKADDR_IN_LEN {
+        return Err(-errno::EINVAL);
+    }
+    if !user_buffer_ok(ptr, SOCKADDR_IN_LEN) {
+        return Err(-errno::EFAULT);
+    }
+    let raw = unsafe { core::slice::from_raw_parts(ptr as *const u8, SOCKADDR_IN_LEN) };
+    if u16::from_ne_bytes([raw[0], raw[1]]) as usize != crate::net::AF_INET {
+        return Err(-errno::EAFNOSUPPORT);
+    }
+    let port = u16::from_be_bytes([raw[2], raw[3]]);
+    Ok(crate::net::SocketAddr::new([raw[4], raw[5], raw[6], raw[7]], port))
+}
+
+/// Check that `write_sockaddr` could fill `ptr`/`len_ptr`
+/// Returns how many address bytes fit (0 when no address is wanted).
+fn sockaddr_out_len(ptr: usize, len_ptr: usize) -> Result<usize, isize> {
+    if ptr == 0 {
+        return Ok(0);
+    }
+    if !user_buffer_ok(len_ptr, 4) {
+        return Err(-errno::EFAULT);
+    }
+    let len = (unsafe { *(len_ptr as *const u32) } as usize).min(SOCKADDR_IN_LEN);
+    if !user_buffer_ok(ptr, len) {
+        return Err(-errno::EFAULT);
+    }
+    Ok(len)
+}
+
+/// Fill a user struct sockaddr_in and its socklen_t, truncating like Linux
+fn write_sockaddr(ptr: usize, len_ptr: usize, addr: crate::net::SocketAddr) -> Result<(), isize> {
+    let len = sockaddr_out_len(ptr, len_ptr)?;
+    if ptr == 0 {
+        return Ok(());
+    }
+    let len_ref = unsafe { &mut *(len_ptr as *mut u32) };
+    let mut raw = [0u8; SOCKADDR_IN_LEN];
+    raw[0..2].copy_from_slice(&(crate::net::AF_INET as u16).to_ne_bytes());
+    raw[2..4].copy_from_slice(&addr.port.to_be_bytes());
+    raw[4..8].copy_from_slice(&addr.ip);
+    unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, len) }.copy_from_slice(&raw[..len]);
+    *len_ref = SOCKADDR_IN_LEN as u32;
+    Ok(())
+}
+
+fn sys_socket(domain: usize, type_: usize, protocol: usize) -> isize {
+    use crate::fs::socket::{SOCK_CLOEXEC, SOCK_NONBLOCK, SOCK_STREAM};
+    
+    if domain != crate::net::AF_INET {
+        return -errno::EAFNOSUPPORT;
+    }
+    if type_ & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != SOCK_STREAM {
+        return -errno::ESOCKTNOSUPPORT;
+    }
+    if protocol != 0 && protocol != IPPROTO_TCP {
+        return -errno::EPROTONOSUPPORT;
+    }
+    let flags = (type_ & (SOCK_NONBLOCK | SOCK_CLOEXEC)) as u32 | 2; // O_RDWR
+    let socket = Arc::new(crate::net::tcp::TcpSocket::new());
+    current_task().lock().add_file(FileDescriptor { inode: socket, offset: 0, flags }) as isize
+}
+
+fn sys_bind(fd: usize, addr: usize, addrlen: usize) -> isize {
+    let addr = match read_sockaddr(addr, addrlen) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    with_tcp_socket(fd, |socket, _| match socket.bind(addr) {
+        Ok(()) => 0,
+        Err(e) => net_errno(e),
+    })
+}
+
+fn sys_listen(fd: usize, backlog: i32) -> isize {
+    with_tcp_socket(fd, |socket, _| match socket.listen(backlog.max(0) as usize) {
+        Ok(()) => {
+            log::debug!("[syscall::listen] fd {} on {:?}", fd, socket.local_addr());
+            0
+        }
+        Err(e) => net_errno(e),
+    })
+}
+
+/// accept(fd, addr, addrlen)
+/// The address buffers are checked first: a bad one must not cost the
+/// caller a connection that was already taken off the queue.
+fn sys_accept(fd: usize, addr: usize, addrlen: usize) -> isize {
+    if let Err(e) = sockaddr_out_len(addr, addrlen) {
+        return e;
+    }
+    with_tcp_socket(fd, |listener, nonblock| {
+        let (socket, peer) = match listener.accept(nonblock) {
+            Ok(a) => a,
+            Err(e) => return net_errno(e),
+        };
+        if let Err(e) = write_sockaddr(addr, addrlen, peer) {
+            return e;
         }
+        log::debug!("[syscall::accept] fd {} from {}", fd, peer);
+        // Plain accept: the new fd inherits neither O_NONBLOCK nor O_CLOEXEC
+        let file = FileDescriptor { inode: Arc::new(socket), offset: 0, flags: 2 }; // O_RDWR
+        current_task().lock().add_file(file) as isize
+    })
+}
+
+fn sys_connect(fd: usize, addr: usize, addrlen: usize) -> isize {
+    let addr = match read_sockaddr(addr, addrlen) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    with_tcp_socket(fd, |socket, _| match socket.connect(addr) {
+        Ok(()) => 0,
+        Err(e) => net_errno(e),
+    })
+}
+
+/// send(2) is sendto with no address; stream sockets ignore one anyway
+fn sys_sendto(fd: usize, buf: usize, len: usize, flags: usize) -> isize {
+    if !user_buffer_ok(buf, len) {
+        return -errno::EFAULT;
+    }
+    let data = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+    with_tcp_socket(fd, |socket, nonblock| {
+        match socket.send(data, nonblock || flags & MSG_DONTWAIT != 0) {
+            Ok(n) => n as isize,
+            Err(e) => net_errno(e),
+        }
+    })
+}
+
+/// recv(2) is recvfrom with no address; the peer of a stream is fixed
+fn sys_recvfrom(fd: usize, buf: usize, len: usize, flags: usize) -> isize {
+    if !user_buffer_ok(buf, len) {
+        return -errno::EFAULT;
+    }
+    let data = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+    with_tcp_socket(fd, |socket, nonblock| {
+        match socket.recv(data, nonblock || flags & MSG_DONTWAIT != 0) {
+            Ok(n) => n as isize,
+            Err(e) => net_errno(e),
+        }
+    })
+}
+
+/// struct pollfd
+#[repr(C)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+/// Upper bound on pollfds per call (RLIMIT_NOFILE-ish)
+const POLL_MAX_FDS: usize = 1024;
+
+/// Pollers and console stdin readers sleep here; the keyboard interrupt
+/// wakes it, other readiness sources don't yet
+static POLL_WAIT: crate::sched::wait::WaitQueue = crate::sched::wait::WaitQueue::new();
+
+/// Some poller could not be woken from the interrupt; retried every tick
+static POLL_WAKE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Wake every poller (keyboard interrupt, so never spins on a lock)
+pub fn wake_pollers() {
+    POLL_WAKE_PENDING.store(!POLL_WAIT.try_wake_all(), Ordering::Relaxed);
+}
+
+/// Retry a wakeup the keyboard interrupt could not deliver (timer interrupt)
+pub fn retry_wake_pollers() {
+    if POLL_WAKE_PENDING.load(Ordering::Relaxed) {
+        wake_pollers();
     }
-    -9 // EBADF
 }
 
-fn sys_stat(_path: usize, _statbuf: usize) -> isize {
-    // TODO: Implement stat - for now return stub
-    log::debug!("[syscall::stat] Stub - returning success");
-    0
+/// fd 0 with nothing installed reads typed keys from the console
+fn is_console_stdin(fd: usize) -> bool {
+    fd == 0 && fd_inode(0).is_none()
 }
 
-fn sys_fstat(fd: usize, statbuf: usize) -> isize {
-    // Write a minimal stat structure
-    if statbuf != 0 {
-        unsafe {
-            let buf = statbuf as *mut u64;
-            // Minimal stat: just set st_mode to regular file (0100644)
-            *buf.add(1) = 0o100644; // st_mode at offset 8
-            // Set st_size to 0
-            *buf.add(6) = 0; // st_size at offset 48
+/// Wait for readiness on a set of fds
+/// Polls on console stdin alone sleep until a key wakes them; anything
+/// else is re-sampled every tick, since those sources don't wake pollers,
+/// until something is ready or the timeout (negative = forever) expires.
+fn sys_poll(fds_ptr: usize, nfds: usize, timeout_ms: i32) -> isize {
+    use crate::sched::clock;
+    
+    if nfds > POLL_MAX_FDS {
+        return -errno::EINVAL;
+    }
+    if nfds != 0 && fds_ptr == 0 {
+        return -errno::EFAULT;
+    }
+    let fds = unsafe { core::slice::from_raw_parts_mut(fds_ptr as *mut PollFd, nfds) };
+    
+    let timeout = (timeout_ms >= 0).then(|| timeout_ms as u64 * 1_000_000);
+    let start = clock::uptime_ns();
+    loop {
+        let ready = poll_once(fds);
+        if ready != 0 {
+            return ready;
+        }
+        let elapsed = clock::uptime_ns() - start;
+        if timeout.is_some_and(|t| elapsed >= t) {
+            return 0;
+        }
+        if fds.iter().all(|p| p.fd < 0 || is_console_stdin(p.fd as usize)) {
+            let left = timeout.map_or(u64::MAX, |t| t - elapsed);
+            POLL_WAIT.wait_until_timeout(|| poll_once(fds) != 0, left);
+        } else {
+            POLL_WAIT.sleep_on_timeout(clock::tick_ns());
         }
     }
-    log::debug!("[syscall::fstat] fd={} - returning stub", fd);
-    0
 }
 
-fn sys_lseek(fd: usize, offset: i64, whence: usize) -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-        if let Some(file_opt) = task.fd_table.get_mut(fd) {
-            if let Some(file) = file_opt {
-                match whence {
-                    0 => file.offset = offset as u64,           // SEEK_SET
-                    1 => file.offset = (file.offset as i64 + offset) as u64, // SEEK_CUR
-                    2 => { /* SEEK_END - would need file size */ }
-                    _ => return -22, // EINVAL
-                }
-                return file.offset as isize;
-            }
+/// Sample every pollfd once, returning the ready count (or -EBADF)
+fn poll_once(fds: &mut [PollFd]) -> isize {
+    use crate::fs::vfs::{POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT};
+    
+    let mut ready = 0;
+    for pfd in fds.iter_mut() {
+        pfd.revents = 0;
+        // Negative fds are skipped, per POSIX
+        if pfd.fd < 0 {
+            continue;
+        }
+        let fd = pfd.fd as usize;
+        let events = match fd_inode(fd) {
+            Some(inode) => inode.readiness(),
+            // stdin reads typed keys; stdout/stderr go straight to the console
+            None if fd == 0 => if crate::drivers::console::input_pending() { POLLIN } else { 0 },
+            None if fd == 1 || fd == 2 => POLLOUT,
+            None => return -errno::EBADF,
+        };
+        // POLLERR/POLLHUP/POLLNVAL are reported whether or not they were requested
+        let revents = events & (pfd.events as u16 | POLLERR | POLLHUP | POLLNVAL);
+        if revents != 0 {
+            pfd.revents = revents as i16;
+            ready += 1;
         }
     }
-    -9 // EBADF
+    ready
 }
 
-fn sys_ioctl(_fd: usize, cmd: usize, _arg: usize) -> isize {
-    // Common ioctl commands - return success for terminal queries
-    match cmd {
-        0x5401 => 0,  // TCGETS - pretend we're a terminal
-        0x5402 => 0,  // TCSETS
-        0x5413 => {   // TIOCGWINSZ - get window size
-            // Would fill in winsize struct if arg is valid
-            0
-        }
-        _ => {
-            log::debug!("[syscall::ioctl] Unknown cmd: 0x{:x}", cmd);
-            -25 // ENOTTY
-        }
+/// Create an epoll instance
+fn sys_epoll_create1(flags: usize) -> isize {
+    use crate::fs::epoll::{Epoll, EPOLL_CLOEXEC};
+    
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return -errno::EINVAL;
     }
+    let task_arc = current_task();
+    let epoll: Arc<dyn fs::vfs::Inode> = Arc::new(Epoll::new());
+    task_arc.lock().add_file(FileDescriptor { inode: epoll, offset: 0, flags: flags as u32 }) as isize
 }
 
-fn sys_dup(oldfd: usize) -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-        if let Some(file_opt) = task.fd_table.get(oldfd) {
-            if let Some(file) = file_opt.clone() {
-                return task.add_file(file) as isize;
-            }
+/// Create an eventfd counter
+fn sys_eventfd2(initval: usize, flags: usize) -> isize {
+    use crate::fs::eventfd::{EventFd, EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
+    
+    if flags & !(EFD_CLOEXEC | EFD_NONBLOCK | EFD_SEMAPHORE) != 0 {
+        return -errno::EINVAL;
+    }
+    let task_arc = current_task();
+    // initval is an unsigned int
+    let eventfd: Arc<dyn fs::vfs::Inode> =
+        Arc::new(EventFd::new(initval as u32 as u64, flags));
+    // EFD_NONBLOCK is O_NONBLOCK, so the descriptor flags carry it
+    let fd_flags = (flags & (EFD_CLOEXEC | EFD_NONBLOCK)) as u32;
+    task_arc.lock().add_file(FileDescriptor { inode: eventfd, offset: 0, flags: fd_flags }) as isize
+}
+
+/// Add, modify or remove an fd in an epoll interest list
+fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event_ptr: usize) -> isize {
+    use crate::fs::epoll::{Epoll, EpollEvent, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD};
+    
+    let (ep_inode, target) = match (fd_inode(epfd), fd_inode(fd)) {
+        (Some(e), Some(t)) => (e, t),
+        _ => return -errno::EBADF,
+    };
+    let epoll = match ep_inode.as_any().and_then(|a| a.downcast_ref::<Epoll>()) {
+        Some(e) => e,
+        None => return -errno::EINVAL,
+    };
+    // Nested epoll sets could form a cycle; keep them flat
+    if epfd == fd || target.as_any().is_some_and(|a| a.is::<Epoll>()) {
+        return -errno::EINVAL;
+    }
+    
+    let read_event = || -> Option<EpollEvent> {
+        if event_ptr == 0 {
+            return None;
         }
+        Some(unsafe { core::ptr::read_unaligned(event_ptr as *const EpollEvent) })
+    };
+    let ok = match op {
+        EPOLL_CTL_ADD => match read_event() {
+            Some(event) => epoll.add(fd, &target, event),
+            None => return -errno::EFAULT,
+        },
+        EPOLL_CTL_MOD => match read_event() {
+            Some(event) => epoll.modify(fd, event),
+            None => return -errno::EFAULT,
+        },
+        EPOLL_CTL_DEL => epoll.remove(fd),
+        _ => return -errno::EINVAL,
+    };
+    match (ok, op) {
+        (true, _) => 0,
+        (false, EPOLL_CTL_ADD) => -errno::EEXIST,
+        (false, _) => -errno::ENOENT,
     }
-    -9 // EBADF
 }
 
-fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
-    let current_lock = CURRENT_TASK.lock();
-    if let Some(task_arc) = current_lock.as_ref() {
-        let mut task = task_arc.lock();
-        if let Some(file_opt) = task.fd_table.get(oldfd) {
-            if let Some(file) = file_opt.clone() {
-                // Extend table if needed
-                while task.fd_table.len() <= newfd {
-                    task.fd_table.push(None);
-                }
-                task.fd_table[newfd] = Some(file);
-                return newfd as isize;
-            }
+/// Collect ready events from an epoll instance
+/// Level-triggered; like poll, the set is re-sampled every tick until
+/// something is ready or the timeout (negative = forever) expires.
+fn sys_epoll_wait(epfd: usize, events_ptr: usize, maxevents: i32, timeout_ms: i32) -> isize {
+    use crate::fs::epoll::{Epoll, EpollEvent};
+    use crate::sched::clock;
+    
+    if maxevents <= 0 {
+        return -errno::EINVAL;
+    }
+    if events_ptr == 0 {
+        return -errno::EFAULT;
+    }
+    let inode = match fd_inode(epfd) {
+        Some(i) => i,
+        None => return -errno::EBADF,
+    };
+    let epoll = match inode.as_any().and_then(|a| a.downcast_ref::<Epoll>()) {
+        Some(e) => e,
+        None => return -errno::EINVAL,
+    };
+    let out = unsafe {
+        core::slice::from_raw_parts_mut(events_ptr as *mut EpollEvent, maxevents as usize)
+    };
+    
+    let timeout = (timeout_ms >= 0).then(|| timeout_ms as u64 * 1_000_000);
+    let start = clock::uptime_ns();
+    loop {
+        let ready = epoll.ready(out);
+        if ready != 0 {
+            return ready as isize;
         }
+        if timeout.is_some_and(|t| clock::uptime_ns() - start >= t) {
+            return 0;
+        }
+        POLL_WAIT.sleep_on_timeout(clock::tick_ns());
     }
-    -9 // EBADF
 }
 
-fn sys_pipe(_pipefd: usize) -> isize {
-    log::warn!("[syscall::pipe] Pipe not implemented");
-    -38 // ENOSYS
+fn sys_munmap(addr: usize, length: usize) -> isize {
+    if addr & 4095 != 0 || length == 0 {
+        return -errno::EINVAL;
+    }
+    let Some(end) = page_len(length).and_then(|len| user_range_end(addr, len)) else {
+        return -errno::EINVAL;
+    };
+    let aligned_len = end - addr;
+    
+    let task_arc = current_task();
+    let mut task = task_arc.lock();
+    task.vmas.remove(addr, end);
+    let freed = crate::mm::paging::unmap_user_range(addr as u64, aligned_len as u64);
+    task.rss_unmap(freed);
+    log::debug!("[syscall::munmap] Unmapped {} bytes at 0x{:x}", aligned_len, addr);
+    0
 }
 
-fn sys_munmap(_addr: usize, _length: usize) -> isize {
-    // Stub - pretend to unmap
-    log::debug!("[syscall::munmap] Stub - returning success");
+/// Change protection of an existing mapping
+fn sys_mprotect(addr: usize, length: usize, prot: usize) -> isize {
+    if addr & 4095 != 0 {
+        return -errno::EINVAL;
+    }
+    let Some(aligned_len) = page_len(length).filter(|&len| user_range_end(addr, len).is_some()) else {
+        return -errno::ENOMEM;
+    };
+    if aligned_len == 0 {
+        return 0;
+    }
+    
+    if crate::mm::vmm::protect_current(addr, aligned_len, prot as u32).is_err() {
+        return -errno::ENOMEM;
+    }
+    log::debug!("[syscall::mprotect] 0x{:x}+{} -> prot {:#x}", addr, aligned_len, prot);
     0
 }
 
@@ -399,17 +1417,7 @@ fn sys_munmap(_addr: usize, _length: usize) -> isize {
 fn sys_fork() -> isize {
     log::info!("[syscall::fork] Creating child process...");
     
-    // Get current task
-    let current_lock = CURRENT_TASK.lock();
-    let current_arc = match current_lock.as_ref() {
-        Some(t) => t.clone(),
-        None => {
-            log::warn!("[syscall::fork] No current task");
-            return -1;
-        }
-    };
-    drop(current_lock);
-    
+    let current_arc = current_task();
     let parent = current_arc.lock();
     let parent_pid = parent.id;
     
@@ -441,64 +1449,119 @@ fn sys_fork() -> isize {
     child_pid as isize
 }
 
-fn sys_clone(_flags: usize, _stack: usize, _parent_tid: usize) -> isize {
-    // clone is similar to fork but with more options
-    // For now, just call fork
-    log::info!("[syscall::clone] Using fork implementation");
-    sys_fork()
+const CLONE_PARENT_SETTID: usize = 0x0010_0000;
+
+/// clone(flags, stack, parent_tid, child_tid, tls)
+/// Threads aren't supported, so this is fork plus CLONE_PARENT_SETTID;
+/// the stack, child_tid and tls arguments are ignored.
+fn sys_clone(flags: usize, _stack: usize, parent_tid: usize, _child_tid: usize, _tls: usize) -> isize {
+    log::info!("[syscall::clone] Using fork implementation (flags {:#x})", flags);
+    let pid = sys_fork();
+    if pid > 0 && flags & CLONE_PARENT_SETTID != 0 && parent_tid != 0 {
+        unsafe { *(parent_tid as *mut i32) = pid as i32 };
+    }
+    pid
 }
 
 fn sys_execve(pathname: usize, argv: usize, envp: usize) -> isize {
     // Get pathname string
-    let path = unsafe { get_user_string(pathname, 0) };
+    let path = unsafe { get_user_path(pathname) };
     if path.is_none() {
         log::warn!("[syscall::execve] Invalid pathname");
-        return -14; // EFAULT
+        return -errno::EFAULT;
     }
-    let path = path.unwrap();
+    let mut path = path.unwrap();
     
-    log::info!("[syscall::execve] Loading: {}", path);
-    
-    // Open the file
-    let inode = match fs::open(&path, 0) {
-        Ok(inode) => inode,
-        Err(_) => {
-            log::warn!("[syscall::execve] File not found: {}", path);
-            return -2; // ENOENT
+    // Copy argv/envp out of user memory before the new image overwrites them
+    let (mut argv_vec, envp_vec) = match unsafe { copy_exec_args(argv, envp) } {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("[syscall::execve] Bad argv/envp: {}", e);
+            return e;
         }
     };
     
-    // Read file contents
-    // REAL IMPLEMENTATION: Should use mmap or read by chunks
-    // For now, increase buffer to 1MB to handle reasonably sized binaries
-    let mut buffer = alloc::vec![0u8; 1024 * 1024]; 
-    let len = inode.read_at(0, &mut buffer);
+    // Follow #! lines to the real interpreter
+    let mut depth = 0;
+    let (image, buffer) = loop {
+        log::info!("[syscall::execve] Loading: {}", path);
+        
+        // Open the file
+        let inode = match fs::open(&path, 0) {
+            Ok(inode) => inode,
+            Err(e) => {
+                log::warn!("[syscall::execve] Can't open {}: {}", path, e);
+                return fs_errno(e);
+            }
+        };
+        
+        // Read file contents
+        let buffer = match read_executable(&*inode) {
+            Ok(b) => b,
+            Err(e) => return e,
+        };
+        
+        if !buffer.starts_with(b"#!") {
+            break (inode, buffer);
+        }
+        
+        depth += 1;
+        if depth > MAX_SHEBANG_DEPTH {
+            log::warn!("[syscall::execve] Too many levels of #! in {}", path);
+            return -errno::ELOOP;
+        }
+        let (interp, interp_arg) = match parse_shebang(&buffer) {
+            Some(s) => s,
+            None => {
+                log::warn!("[syscall::execve] Bad #! line in {}", path);
+                return -errno::ENOEXEC;
+            }
+        };
+        log::info!("[syscall::execve] Script {} -> interpreter {}", path, interp);
+        
+        // argv becomes: interpreter [arg] script original-argv[1..]
+        let mut new_argv = alloc::vec![interp.clone().into_bytes()];
+        if let Some(arg) = interp_arg {
+            new_argv.push(arg);
+        }
+        new_argv.push(path.into_bytes());
+        new_argv.extend(argv_vec.into_iter().skip(1));
+        argv_vec = new_argv;
+        path = interp;
+    };
     
-    if len < 64 { // Minimum ELF size roughly
+    if buffer.len() < 64 { // Minimum ELF size roughly
         log::warn!("[syscall::execve] File too small");
-        return -8; // ENOEXEC
+        return -errno::ENOEXEC;
     }
     
-    let buffer_slice = &buffer[..len];
-    let header = unsafe { *(buffer_slice.as_ptr() as *const elf::Elf64Header) };
+    let buffer_slice = &buffer[..];
+    let e_type = match crate::elf::ElfFile::parse(buffer_slice) {
+        Ok(file) => file.header().e_type,
+        Err(e) => {
+            log::warn!("[syscall::execve] ELF load error: {}", e);
+            return -errno::ENOEXEC;
+        }
+    };
     
     // Determine Main Load Base
-    // ET_DYN (3) = PIE, needs base address (e.g. 0x00400000)
-    // ET_EXEC (2) = Fixed, base = 0
-    let main_base = if header.e_type == 3 { 0x00400000 } else { 0 };
+    // ET_DYN = PIE, needs base address (e.g. 0x00400000)
+    // ET_EXEC = Fixed, base = 0
+    let main_base = if e_type == crate::elf::ET_DYN { 0x00400000 } else { 0 };
     
     // Load Main ELF
     let loaded = match elf::load_elf(buffer_slice, main_base) {
         Ok(l) => l,
         Err(e) => {
             log::warn!("[syscall::execve] ELF load error: {}", e);
-            return -8; // ENOEXEC
+            return -errno::ENOEXEC;
         }
     };
     
     // Prepare Auxv
     let mut auxv = Vec::new();
     let entry_point;
+    let mut interp_segments = Vec::new();
     
     // Check for Interpreter
     if let Some(interp_path) = loaded.interp {
@@ -507,26 +1570,29 @@ fn sys_execve(pathname: usize, argv: usize, envp: usize) -> isize {
         // Open Interpreter
         let interp_inode = match fs::open(&interp_path, 0) {
             Ok(inode) => inode,
-            Err(_) => {
-                log::warn!("[syscall::execve] Interpreter not found: {}", interp_path);
-                return -2; // ENOENT
+            Err(e) => {
+                log::warn!("[syscall::execve] Can't open interpreter {}: {}", interp_path, e);
+                return fs_errno(e);
             }
         };
         
-        let mut interp_buf = alloc::vec![0u8; 256 * 1024]; // 256KB constraint for ld.so
-        let interp_len = interp_inode.read_at(0, &mut interp_buf);
+        let interp_buf = match read_executable(&*interp_inode) {
+            Ok(b) => b,
+            Err(e) => return e,
+        };
         
         // Load Interpreter at high address (e.g. 0x7ffff7dd5000)
         let interp_base = 0x7ffff7dd5000;
-        let interp_loaded = match elf::load_elf(&interp_buf[..interp_len], interp_base) {
+        let interp_loaded = match elf::load_elf(&interp_buf, interp_base) {
              Ok(l) => l,
              Err(e) => {
                  log::warn!("[syscall::execve] Interpreter load error: {}", e);
-                 return -8; // ENOEXEC
+                 return -errno::ENOEXEC;
              }
         };
         
         entry_point = interp_loaded.entry_point;
+        interp_segments = interp_loaded.segments;
         
         // Auxv for Interpreter
         auxv.push(elf::AuxvEntry { key: elf::AT_PHDR, val: loaded.phdr_vaddr });
@@ -546,34 +1612,37 @@ fn sys_execve(pathname: usize, argv: usize, envp: usize) -> isize {
         auxv.push(elf::AuxvEntry { key: elf::AT_PAGESZ, val: 4096 });
     }
     
-    // Parse argv
-    let mut argv_vec: Vec<&[u8]> = Vec::new();
-    if argv != 0 {
-        unsafe {
-            let mut ptr = argv as *const usize;
-            while *ptr != 0 {
-                let arg_ptr = *ptr as *const u8;
-                let mut len = 0;
-                while *arg_ptr.add(len) != 0 {
-                    len += 1;
-                    if len > 1024 { break; }
-                }
-                argv_vec.push(core::slice::from_raw_parts(arg_ptr, len));
-                ptr = ptr.add(1);
-            }
-        }
-    }
-    
-    // Parse envp (simplified)
-    let envp_vec: Vec<&[u8]> = Vec::new();
+    let argv_refs: Vec<&[u8]> = argv_vec.iter().map(|a| a.as_slice()).collect();
+    let envp_refs: Vec<&[u8]> = envp_vec.iter().map(|e| e.as_slice()).collect();
     
     // Set up new stack
     let stack_top = 0x7FFFFF000000u64;
     let stack_size = 128 * 1024; // 128KB stack
     crate::mm::paging::make_user_accessible(stack_top - stack_size, stack_size);
     
+    // Describe the new image to the fault handler
+    {
+        let task_arc = current_task();
+        let mut task = task_arc.lock();
+        task.creds = task.creds.on_exec(&image.metadata());
+        close_on_exec(&mut task);
+        task.vmas.clear();
+        for seg in loaded.segments.iter().chain(interp_segments.iter()) {
+            let start = seg.vaddr as usize & !4095;
+            let end = (seg.vaddr + seg.size + 4095) as usize & !4095;
+            task.vmas.insert(start, end, seg.prot, MAP_PRIVATE, Backing::Image);
+        }
+        let mut stack_prot = PROT_READ | PROT_WRITE;
+        if loaded.stack_exec {
+            stack_prot |= PROT_EXEC;
+        }
+        task.vmas.insert((stack_top - stack_size) as usize, stack_top as usize,
+                         stack_prot, MAP_PRIVATE | MAP_ANONYMOUS, Backing::Image);
+        crate::mm::paging::protect_user_range(stack_top - stack_size, stack_size, stack_prot);
+    }
+    
     // Set up stack with argv/envp/auxv
-    let user_sp = elf::setup_user_stack(stack_top, &argv_vec, &envp_vec, &auxv);
+    let user_sp = elf::setup_user_stack(stack_top, &argv_refs, &envp_refs, &auxv);
     
     log::info!("[syscall::execve] Stack at 0x{:x}, entry 0x{:x}", user_sp, entry_point);
     
@@ -591,48 +1660,406 @@ fn sys_execve(pathname: usize, argv: usize, envp: usize) -> isize {
     -1
 }
 
-fn sys_wait4(_pid: i32, _wstatus: usize, _options: usize) -> isize {
-    log::warn!("[syscall::wait4] Wait4 not implemented");
-    -10 // ECHILD - no child processes
+/// Environment handed to the first user process
+pub const DEFAULT_ENV: &[&[u8]] = &[b"PATH=/bin", b"HOME=/", b"TERM=linux"];
+
+/// Build the initial stack (argv, envp, auxv) for the boot-time process
+/// Returns the user stack pointer.
+pub fn setup_init_stack(stack_top: u64, path: &[u8]) -> u64 {
+    let auxv = [elf::AuxvEntry { key: elf::AT_PAGESZ, val: 4096 }];
+    elf::setup_user_stack(stack_top, &[path], DEFAULT_ENV, &auxv)
+}
+
+/// Most bytes execve copies for argv and envp together (strings, their
+/// NULs and the pointers to them), leaving the rest of the new 128K stack
+/// to the program
+const ARG_MAX: usize = 64 * 1024;
+
+/// Copy a NULL-terminated array of user C strings (argv/envp)
+/// Every string and pointer is charged to `budget`. EFAULT if the array or
+/// a string runs outside user space, E2BIG once the budget is spent.
+unsafe fn copy_user_string_array(array: usize, budget: &mut usize) -> Result<Vec<Vec<u8>>, isize> {
+    const PTR_SIZE: usize = core::mem::size_of::<usize>();
+    
+    let mut out = Vec::new();
+    if array == 0 {
+        return Ok(out);
+    }
+    let mut slot = array;
+    loop {
+        if user_range_end(slot, PTR_SIZE).is_none() {
+            return Err(-errno::EFAULT);
+        }
+        let arg = (slot as *const usize).read_unaligned();
+        if arg == 0 {
+            return Ok(out);
+        }
+        *budget = budget.checked_sub(PTR_SIZE).ok_or(-errno::E2BIG)?;
+        
+        let mut len = 0;
+        loop {
+            if user_range_end(arg, len + 1).is_none() {
+                return Err(-errno::EFAULT);
+            }
+            if len >= *budget {
+                return Err(-errno::E2BIG); // No room left for the NUL
+            }
+            if *(arg as *const u8).add(len) == 0 {
+                break;
+            }
+            len += 1;
+        }
+        *budget -= len + 1;
+        out.push(core::slice::from_raw_parts(arg as *const u8, len).to_vec());
+        slot += PTR_SIZE; // Still in user space: checked above
+    }
+}
+
+/// execve's argv and envp, sharing one ARG_MAX budget
+unsafe fn copy_exec_args(argv: usize, envp: usize) -> Result<(Vec<Vec<u8>>, Vec<Vec<u8>>), isize> {
+    let mut budget = ARG_MAX;
+    let argv = copy_user_string_array(argv, &mut budget)?;
+    let envp = copy_user_string_array(envp, &mut budget)?;
+    Ok((argv, envp))
+}
+
+/// Longest #! line accepted, excluding the newline (as Linux)
+const SHEBANG_MAX: usize = 127;
+
+/// Deepest chain of scripts naming scripts as their interpreter
+const MAX_SHEBANG_DEPTH: usize = 4;
+
+/// Parse "#!interp [arg]" into the interpreter path and its optional single argument
+fn parse_shebang(buf: &[u8]) -> Option<(String, Option<Vec<u8>>)> {
+    let limit = core::cmp::min(buf.len(), SHEBANG_MAX + 1);
+    let line = match buf[..limit].iter().position(|&b| b == b'\n') {
+        Some(nl) => &buf[2..nl],
+        None if buf.len() <= SHEBANG_MAX => &buf[2..],
+        None => return None, // Line too long: interpreter path would be truncated
+    };
+    
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+    let start = line.iter().position(|b| !is_blank(b))?;
+    let line = &line[start..];
+    let end = line.iter().rposition(|b| !is_blank(b))? + 1;
+    let line = &line[..end];
+    
+    let (interp, arg) = match line.iter().position(is_blank) {
+        Some(sp) => {
+            let rest = &line[sp..];
+            let skip = rest.iter().position(|b| !is_blank(b)).unwrap_or(rest.len());
+            (&line[..sp], Some(rest[skip..].to_vec()))
+        }
+        None => (line, None),
+    };
+    let interp = String::from_utf8(interp.to_vec()).ok()?;
+    Some((interp, arg))
+}
+
+/// Largest executable execve will load
+const MAX_EXEC_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Read a whole executable into memory, sized from its metadata
+fn read_executable(inode: &dyn fs::vfs::Inode) -> Result<Vec<u8>, isize> {
+    let size = inode.metadata().size;
+    if size == 0 {
+        log::warn!("[syscall::execve] Empty file");
+        return Err(-errno::ENOEXEC);
+    }
+    if size > MAX_EXEC_SIZE {
+        log::warn!("[syscall::execve] Executable too large: {} bytes", size);
+        return Err(-errno::E2BIG);
+    }
+    
+    let mut buffer = alloc::vec![0u8; size as usize];
+    let total = fs::vfs::read_exact_at(inode, 0, &mut buffer);
+    buffer.truncate(total);
+    Ok(buffer)
+}
+
+const WNOHANG: usize = 1;
+
+/// Wait for a child to terminate and reap it
+/// Sleeps on CHILD_EXIT until a matching child has exited, unless WNOHANG.
+fn sys_wait4(pid: i32, wstatus: usize, options: usize) -> isize {
+    use crate::sched::queue::ALL_TASKS;
+    use crate::sched::task::TaskState;
+    
+    let parent_arc = current_task();
+    let parent_pid = parent_arc.lock().id;
+    
+    // Err if no child matches `pid`, Ok(Some(index)) once one has exited
+    let find_child = |all_tasks: &[crate::sched::queue::TaskRef]| -> Result<Option<usize>, ()> {
+        let mut have_children = false;
+        for (i, t) in all_tasks.iter().enumerate() {
+            let task = t.lock();
+            if task.parent_id != parent_pid || (pid > 0 && task.id != pid as usize) {
+                continue;
+            }
+            have_children = true;
+            if task.state == TaskState::Terminated {
+                return Ok(Some(i));
+            }
+        }
+        if have_children { Ok(None) } else { Err(()) }
+    };
+    
+    if options & WNOHANG == 0 {
+        crate::sched::CHILD_EXIT.wait_until(|| find_child(&ALL_TASKS.lock()) != Ok(None));
+    }
+    let mut all_tasks = ALL_TASKS.lock();
+    let idx = match find_child(&all_tasks) {
+        Ok(Some(i)) => i,
+        Ok(None) => return 0, // WNOHANG, nothing exited yet
+        Err(()) => return -errno::ECHILD,
+    };
+    
+    // Reap: fold the child's CPU time into the parent's child counters
+    // (copy the child's totals out first: two Task locks are never held at once)
+    let child_arc = all_tasks.remove(idx);
+    drop(all_tasks);
+    let (child_pid, exit_status, user, sys, min_flt, maj_flt, max_rss) = {
+        let mut child = child_arc.lock();
+        child.set_state(TaskState::Reaped);
+        (child.id, child.exit_status,
+         child.user_ns + child.child_user_ns,
+         child.sys_ns() + child.child_sys_ns,
+         child.min_faults + child.child_min_faults,
+         child.maj_faults + child.child_maj_faults,
+         child.max_rss_kb().max(child.child_max_rss_kb))
+    };
+    {
+        let mut parent = parent_arc.lock();
+        parent.child_user_ns += user;
+        parent.child_sys_ns += sys;
+        parent.child_min_faults += min_flt;
+        parent.child_maj_faults += maj_flt;
+        parent.child_max_rss_kb = parent.child_max_rss_kb.max(max_rss);
+    }
+    
+    if wstatus != 0 {
+        unsafe { *(wstatus as *mut i32) = exit_status; }
+    }
+    child_pid as isize
+}
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_PRIVATE_FLAG: usize = 128;
+const FUTEX_CLOCK_REALTIME: usize = 256;
+
+/// Wait queues for futex words, keyed by user address
+/// Holding this lock across the value check and the enqueue is what makes
+/// FUTEX_WAIT atomic with respect to FUTEX_WAKE.
+static FUTEXES: spin::Mutex<alloc::collections::BTreeMap<usize, Arc<crate::sched::wait::WaitQueue>>> =
+    spin::Mutex::new(alloc::collections::BTreeMap::new());
+
+/// Fast userspace mutex: FUTEX_WAIT / FUTEX_WAKE
+/// FUTEX_WAIT takes an optional relative timeout (struct timespec).
+fn sys_futex(uaddr: usize, op: usize, val: u32, timeout: usize) -> isize {
+    use crate::sched::wait::WaitQueue;
+    
+    if uaddr == 0 || uaddr & 3 != 0 {
+        return -errno::EINVAL;
+    }
+    match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT => {
+            let timeout_ns = match timeout {
+                0 => None,
+                ptr => match read_timespec(ptr) {
+                    None => return -errno::EINVAL,
+                    ns => ns,
+                },
+            };
+            let mut futexes = FUTEXES.lock();
+            let current = unsafe { core::ptr::read_volatile(uaddr as *const u32) };
+            if current != val {
+                return -errno::EAGAIN;
+            }
+            let queue = futexes.entry(uaddr).or_insert_with(|| Arc::new(WaitQueue::new())).clone();
+            let waiter = queue.prepare_to_wait();
+            drop(futexes);
+            match timeout_ns {
+                None => queue.finish_wait(waiter),
+                Some(ns) => {
+                    if !queue.finish_wait_timeout(waiter, ns) {
+                        // Drop the queue if we were its last waiter
+                        let mut futexes = FUTEXES.lock();
+                        if queue.is_empty() && futexes.get(&uaddr).is_some_and(|q| Arc::ptr_eq(q, &queue)) {
+                            futexes.remove(&uaddr);
+                        }
+                        return -errno::ETIMEDOUT;
+                    }
+                }
+            }
+            0
+        }
+        FUTEX_WAKE => {
+            let mut futexes = FUTEXES.lock();
+            let queue = match futexes.get(&uaddr) {
+                Some(q) => q.clone(),
+                None => return 0,
+            };
+            let mut woken = 0;
+            while woken < val as usize && queue.wake_one() {
+                woken += 1;
+            }
+            if queue.is_empty() {
+                futexes.remove(&uaddr);
+            }
+            woken as isize
+        }
+        _ => -errno::ENOSYS,
+    }
 }
 
 // ============================================================================
 // Time Syscalls
 // ============================================================================
 
-static mut BOOT_TIME: u64 = 0;
-
 fn sys_gettimeofday(tv: usize, _tz: usize) -> isize {
     if tv != 0 {
+        let uptime_ns = crate::sched::clock::uptime_ns();
         unsafe {
             let timeval = tv as *mut u64;
-            // Fake time: return boot time + some counter
-            BOOT_TIME += 1;
-            *timeval = BOOT_TIME;        // tv_sec
-            *timeval.add(1) = 0;         // tv_usec
+            // No RTC yet: wall clock starts at boot
+            *timeval = uptime_ns / 1_000_000_000;                // tv_sec
+            *timeval.add(1) = (uptime_ns % 1_000_000_000) / 1000; // tv_usec
+        }
+    }
+    0
+}
+
+/// Read a relative struct timespec { tv_sec, tv_nsec } as nanoseconds
+/// None if either field is out of range.
+fn read_timespec(ptr: usize) -> Option<u64> {
+    let (sec, nsec) = unsafe {
+        let ts = ptr as *const i64;
+        (*ts, *ts.add(1))
+    };
+    if sec < 0 || !(0..1_000_000_000).contains(&nsec) {
+        return None;
+    }
+    Some((sec as u64).saturating_mul(1_000_000_000).saturating_add(nsec as u64))
+}
+
+/// Sleepers in nanosleep; never woken early, only by their deadline
+static SLEEPERS: crate::sched::wait::WaitQueue = crate::sched::wait::WaitQueue::new();
+
+fn sys_nanosleep(req: usize, rem: usize) -> isize {
+    if req == 0 {
+        return -errno::EFAULT;
+    }
+    let ns = match read_timespec(req) {
+        Some(ns) => ns,
+        None => return -errno::EINVAL,
+    };
+    SLEEPERS.sleep_on_timeout(ns);
+    
+    // Nothing interrupts a sleep yet, so there is never time remaining
+    if rem != 0 {
+        unsafe {
+            let ts = rem as *mut i64;
+            *ts = 0;
+            *ts.add(1) = 0;
         }
     }
     0
 }
 
-fn sys_nanosleep(req: usize, _rem: usize) -> isize {
-    if req != 0 {
-        // Read timespec but just spin for now
-        // In real OS we'd schedule another task
-        for _ in 0..10000 {
-            core::hint::spin_loop();
+/// Process times in clock_t units (1/USER_HZ seconds)
+/// Returns the time since boot in the same units, like Linux
+fn sys_times(buf: usize) -> isize {
+    use crate::sched::clock::{ns_to_clock_t, uptime_ns};
+    
+    if buf != 0 {
+        let (utime, stime, cutime, cstime) = {
+            let task_arc = current_task();
+            let task = task_arc.lock();
+            (task.user_ns, task.sys_ns(), task.child_user_ns, task.child_sys_ns)
+        };
+        unsafe {
+            // struct tms: 4 x clock_t
+            let tms = buf as *mut u64;
+            *tms = ns_to_clock_t(utime);          // tms_utime
+            *tms.add(1) = ns_to_clock_t(stime);   // tms_stime
+            *tms.add(2) = ns_to_clock_t(cutime);  // tms_cutime
+            *tms.add(3) = ns_to_clock_t(cstime);  // tms_cstime
+        }
+    }
+    ns_to_clock_t(uptime_ns()) as isize
+}
+
+const RUSAGE_SELF: i32 = 0;
+const RUSAGE_CHILDREN: i32 = -1;
+const RUSAGE_THREAD: i32 = 1;
+
+/// Resource usage
+/// Untracked fields (ixrss, nswap, context switches, ...) are reported as zero.
+fn sys_getrusage(who: i32, usage: usize) -> isize {
+    let (utime_ns, stime_ns, stime, maxrss, minflt, majflt) = {
+        let task_arc = current_task();
+        let task = task_arc.lock();
+        match who {
+            RUSAGE_SELF | RUSAGE_THREAD => (task.user_ns, task.sys_ns(), task.max_rss_kb(),
+                                            task.min_faults, task.maj_faults),
+            RUSAGE_CHILDREN => (task.child_user_ns, task.child_sys_ns, task.child_max_rss_kb,
+                                task.child_min_faults, task.child_maj_faults),
+            _ => return -errno::EINVAL,
         }
+    };
+    
+    if usage == 0 {
+        return -errno::EFAULT;
+    }
+    
+    unsafe {
+        // struct rusage: 2 x timeval, then 14 longs (144 bytes)
+        let ru = usage as *mut u64;
+        core::ptr::write_bytes(ru, 0, 18);
+        
+        *ru = utime_ns / 1_000_000_000;                  // ru_utime.tv_sec
+        *ru.add(1) = (utime_ns % 1_000_000_000) / 1000;  // ru_utime.tv_usec
+        *ru.add(2) = stime_ns / 1_000_000_000;           // ru_stime.tv_sec
+        *ru.add(3) = (stime_ns % 1_000_000_000) / 1000;  // ru_stime.tv_usec
+        *ru.add(4) = maxrss;                              // ru_maxrss (KiB)
+        *ru.add(8) = minflt;                              // ru_minflt
+        *ru.add(9) = majflt;                              // ru_majflt
     }
     0
 }
 
+// Clock IDs
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+const CLOCK_MONOTONIC_RAW: usize = 4;
+const CLOCK_REALTIME_COARSE: usize = 5;
+const CLOCK_MONOTONIC_COARSE: usize = 6;
+const CLOCK_BOOTTIME: usize = 7;
+
 fn sys_clock_gettime(clock_id: usize, tp: usize) -> isize {
+    use crate::sched::clock;
+    
+    let ns = match clock_id {
+        // No RTC yet: every wall/monotonic clock counts from boot
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW |
+        CLOCK_REALTIME_COARSE | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => clock::uptime_ns(),
+        
+        // Tasks are single-threaded, so process and thread CPU time coincide
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            current_task().lock().cpu_ns
+        }
+        
+        _ => return -errno::EINVAL,
+    };
+    
     if tp != 0 {
         unsafe {
             let timespec = tp as *mut u64;
-            BOOT_TIME += 1;
-            *timespec = BOOT_TIME;        // tv_sec
-            *timespec.add(1) = 0;         // tv_nsec
+            *timespec = ns / 1_000_000_000;        // tv_sec
+            *timespec.add(1) = ns % 1_000_000_000; // tv_nsec
         }
     }
     log::debug!("[syscall::clock_gettime] clock_id={}", clock_id);
@@ -643,46 +2070,147 @@ fn sys_clock_gettime(clock_id: usize, tp: usize) -> isize {
 // Misc Syscalls
 // ============================================================================
 
+/// Length of each struct utsname field, NUL included
+const UTSNAME_FIELD: usize = 65;
+
+/// Longest hostname sethostname accepts (HOST_NAME_MAX)
+const HOST_NAME_MAX: usize = 64;
+
+/// Node name reported by uname; there is no gethostname syscall, libc
+/// reads it from there
+pub static HOSTNAME: spin::Lazy<spin::Mutex<String>> =
+    spin::Lazy::new(|| spin::Mutex::new(String::from("aether")));
+
+const UNAME_MACHINE: &str = if cfg!(target_arch = "aarch64") { "aarch64" } else { "x86_64" };
+
 fn sys_uname(buf: usize) -> isize {
-    if buf != 0 {
-        unsafe {
-            let ptr = buf as *mut u8;
-            // struct utsname: 5 fields of 65 bytes each
-            let sysname = b"Aether\0";
-            let nodename = b"aether\0";
-            let release = b"0.1.0\0";
-            let version = b"#1 SMP\0";
-            let machine = b"x86_64\0";
-            
-            core::ptr::copy_nonoverlapping(sysname.as_ptr(), ptr, sysname.len());
-            core::ptr::copy_nonoverlapping(nodename.as_ptr(), ptr.add(65), nodename.len());
-            core::ptr::copy_nonoverlapping(release.as_ptr(), ptr.add(130), release.len());
-            core::ptr::copy_nonoverlapping(version.as_ptr(), ptr.add(195), version.len());
-            core::ptr::copy_nonoverlapping(machine.as_ptr(), ptr.add(260), machine.len());
-        }
+    if buf == 0 {
+        return -errno::EFAULT;
+    }
+    let hostname = HOSTNAME.lock().clone();
+    // struct utsname: sysname, nodename, release, version, machine, domainname
+    let fields = [
+        "Aether",
+        hostname.as_str(),
+        env!("CARGO_PKG_VERSION"),
+        concat!("#1 ", env!("AETHER_BUILD_TIME")),
+        UNAME_MACHINE,
+        "(none)",
+    ];
+    let out = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, fields.len() * UTSNAME_FIELD) };
+    for (slot, value) in out.chunks_mut(UTSNAME_FIELD).zip(fields) {
+        let len = value.len().min(UTSNAME_FIELD - 1);
+        slot.fill(0);
+        slot[..len].copy_from_slice(&value.as_bytes()[..len]);
     }
     0
 }
 
+/// Set the hostname (root only)
+fn sys_sethostname(name: usize, len: usize) -> isize {
+    if sys_geteuid() != 0 {
+        return -errno::EPERM;
+    }
+    if len > HOST_NAME_MAX {
+        return -errno::EINVAL;
+    }
+    if !user_buffer_ok(name, len) {
+        return -errno::EFAULT;
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(name as *const u8, len) };
+    if bytes.contains(&0) {
+        return -errno::EINVAL;
+    }
+    match core::str::from_utf8(bytes) {
+        Ok(new) => {
+            log::info!("[syscall::sethostname] {}", new);
+            *HOSTNAME.lock() = String::from(new);
+            0
+        }
+        Err(_) => -errno::EINVAL,
+    }
+}
+
 fn sys_getcwd(buf: usize, size: usize) -> isize {
-    if buf != 0 && size > 1 {
-        unsafe {
-            let ptr = buf as *mut u8;
-            *ptr = b'/';
-            *ptr.add(1) = 0;
+    let cwd = current_task().lock().cwd.clone();
+    if size < cwd.len() + 1 {
+        return -errno::ERANGE;
+    }
+    if buf == 0 {
+        return -errno::EFAULT;
+    }
+    unsafe {
+        let ptr = buf as *mut u8;
+        core::ptr::copy_nonoverlapping(cwd.as_ptr(), ptr, cwd.len());
+        *ptr.add(cwd.len()) = 0;
+    }
+    buf as isize
+}
+
+fn sys_chdir(path: usize) -> isize {
+    let path = match unsafe { get_user_path(path) } {
+        Some(p) => p,
+        None => return -errno::EFAULT,
+    };
+    match fs::open(&path, 0) {
+        Ok(inode) if inode.metadata().file_type == fs::vfs::FileType::Directory => {
+            current_task().lock().cwd = path;
+            0
         }
-        return buf as isize;
+        Ok(_) => -errno::ENOTDIR,
+        Err(e) => fs_errno(e),
     }
-    -34 // ERANGE
 }
 
-fn sys_chdir(_path: usize) -> isize {
-    // Stub - pretend to change directory
-    log::debug!("[syscall::chdir] Stub - returning success");
-    0
+fn sys_getuid() -> isize { current_task().lock().creds.uid as isize }
+fn sys_getgid() -> isize { current_task().lock().creds.gid as isize }
+fn sys_geteuid() -> isize { current_task().lock().creds.euid as isize }
+fn sys_getegid() -> isize { current_task().lock().creds.egid as isize }
+
+fn sys_setuid(uid: u32) -> isize {
+    let task = current_task();
+    let mut task = task.lock();
+    match task.creds.set_uid(uid) {
+        Ok(()) => {
+            log::debug!("[syscall::setuid] pid {} -> uid {}", task.id, uid);
+            0
+        }
+        Err(_) => -errno::EPERM,
+    }
+}
+
+fn sys_setgid(gid: u32) -> isize {
+    let task = current_task();
+    let mut task = task.lock();
+    match task.creds.set_gid(gid) {
+        Ok(()) => {
+            log::debug!("[syscall::setgid] pid {} -> gid {}", task.id, gid);
+            0
+        }
+        Err(_) => -errno::EPERM,
+    }
 }
 
-fn sys_getuid() -> isize { 0 }   // root
-fn sys_getgid() -> isize { 0 }   // root
-fn sys_geteuid() -> isize { 0 }  // root
-fn sys_getegid() -> isize { 0 }  // root
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebang_interpreter_and_argument() {
+        assert_eq!(parse_shebang(b"#!/bin/sh\necho hi\n"), Some((String::from("/bin/sh"), None)));
+        assert_eq!(parse_shebang(b"#!/bin/sh"), Some((String::from("/bin/sh"), None)));
+        // Blanks around the interpreter go; the rest is one argument
+        assert_eq!(
+            parse_shebang(b"#! /usr/bin/env\t python3 -u \n"),
+            Some((String::from("/usr/bin/env"), Some(b"python3 -u".to_vec())))
+        );
+    }
+
+    #[test]
+    fn shebang_rejects_blank_and_overlong_lines() {
+        assert_eq!(parse_shebang(b"#!  \t\n"), None);
+        let mut long = b"#!/".to_vec();
+        long.resize(SHEBANG_MAX + 8, b'a');
+        assert_eq!(parse_shebang(&long), None);
+    }
+}
@@ -3,6 +3,10 @@ use lazy_static::lazy_static;
 use core::ptr;
 use core::slice;
 use log::info;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use aether_core::scheduler::ProcessId;
+use x86_64::instructions::interrupts::without_interrupts;
 
 // Basic GOP Info
 struct VideoState {
@@ -13,63 +17,159 @@ struct VideoState {
     stride: usize,
 }
 
+// Only ever reached through the VIDEO mutex, so Send is all we need
 unsafe impl Send for VideoState {}
-unsafe impl Sync for VideoState {}
 
-// Guest Buffer (Shadow FB)
-static mut GUEST_FB: *const u32 = ptr::null();
+/// A guest's shadow framebuffer, keyed by the PID of the process that owns it
+struct GuestBuffer {
+    pid: ProcessId,
+    base: *const u32,
+}
+
+unsafe impl Send for GuestBuffer {}
 
 lazy_static! {
     static ref VIDEO: Mutex<Option<VideoState>> = Mutex::new(None);
+    static ref GUEST_BUFFERS: Mutex<Vec<GuestBuffer>> = Mutex::new(Vec::new());
 }
 
+/// Guest currently shown on screen (0 = none chosen yet, show the first one)
+static FOCUSED_PID: AtomicU64 = AtomicU64::new(0);
+
+/// Frames presented per second at most, whatever the timer rate
+pub const PRESENT_HZ: u64 = 50;
+
+/// Uptime of the last present, in nanoseconds
+static LAST_PRESENT_NS: AtomicU64 = AtomicU64::new(0);
+
 // Initialize real hardware framebuffer
 pub fn init(base: *mut u8, size: usize, width: usize, height: usize, stride: usize) {
     info!("[Aether::Video] Initializing GOP: {:p} ({}x{})", base, width, height);
-    let mut video = VIDEO.lock();
-    *video = Some(VideoState {
-        base: base as *mut u32,
-        size,
-        width,
-        height,
-        stride,
+    without_interrupts(|| {
+        *VIDEO.lock() = Some(VideoState {
+            base: base as *mut u32,
+            size,
+            width,
+            height,
+            stride,
+        });
+    });
+}
+
+// Register where a Guest is writing pixels
+pub fn register_guest_buffer(pid: ProcessId, ptr: *const u8) {
+    // Guest writes to FB_ADDR (0x100000)
+    // We assume 32-bit color (4 bytes)
+    without_interrupts(|| {
+        let mut buffers = GUEST_BUFFERS.lock();
+        buffers.retain(|b| b.pid != pid);
+        buffers.push(GuestBuffer { pid, base: ptr as *const u32 });
+    });
+}
+
+/// Forget a guest's buffer; if it had focus, the next guest gets it, and
+/// with none left the screen is cleared rather than showing a dead guest
+/// May run in the timer interrupt (the scheduler reaping a guest): the
+/// locks are only ever held elsewhere with interrupts off, so they are free.
+pub fn unregister_guest_buffer(pid: ProcessId) {
+    without_interrupts(|| {
+        let mut buffers = GUEST_BUFFERS.lock();
+        buffers.retain(|b| b.pid != pid);
+        let next = buffers.first().map_or(0, |b| b.pid);
+        let none_left = buffers.is_empty();
+        drop(buffers);
+        
+        let _ = FOCUSED_PID.compare_exchange(pid, next, Ordering::AcqRel, Ordering::Acquire);
+        if none_left {
+            clear();
+        }
     });
 }
 
-// Register where the Guest is writing pixels
-pub fn set_guest_buffer(ptr: *const u8) {
-    unsafe {
-        // Guest writes to FB_ADDR (0x100000)
-        // We assume 32-bit color (4 bytes)
-        GUEST_FB = ptr as *const u32;
+/// Paint the whole screen black
+fn clear() {
+    if let Some(ref v) = *VIDEO.lock() {
+        for y in 0..v.height {
+            unsafe { ptr::write_bytes(v.base.add(y * v.stride), 0, v.width) };
+        }
+    }
+}
+
+/// Choose which guest the compositor puts on screen
+pub fn set_focus(pid: ProcessId) {
+    FOCUSED_PID.store(pid, Ordering::Release);
+}
+
+/// Called on every timer tick: blit only once a frame period has passed
+/// The full-screen copy is the bulk of the handler's time, so doing it at
+/// `PRESENT_HZ` rather than per tick keeps it from adding jitter to
+/// scheduling when the timer runs fast.
+pub fn present_tick() {
+    let now = crate::sched::clock::uptime_ns();
+    let last = LAST_PRESENT_NS.load(Ordering::Relaxed);
+    if last != 0 && now.saturating_sub(last) < 1_000_000_000 / PRESENT_HZ {
+        return;
     }
+    LAST_PRESENT_NS.store(now, Ordering::Relaxed);
+    blit();
 }
 
 pub fn blit() {
     // This is called from Interrupt Handler! Be super careful.
-    // spin::Mutex is safe in interrupts.
+    // spin::Mutex is safe in interrupts, but never spin on the registry here:
+    // if a spawn is mid-registration, just skip this frame.
     
-    if let Some(ref v) = *VIDEO.lock() {
+    let video = match VIDEO.try_lock() {
+        Some(v) => v,
+        None => return,
+    };
+    if let Some(ref v) = *video {
+        let buffers = match GUEST_BUFFERS.try_lock() {
+            Some(b) => b,
+            None => return,
+        };
+        
+        let focused = FOCUSED_PID.load(Ordering::Acquire);
+        let guest = buffers.iter()
+            .find(|b| b.pid == focused)
+            .or_else(|| buffers.first());
+        
+        let src = match guest {
+            Some(g) if !g.base.is_null() => g.base,
+            _ => return,
+        };
+        // A mode larger than the guest's FB region would read past it
+        if aether_abi::mmio::framebuffer_len(v.width, v.height).is_none() {
+            return;
+        }
+        
+        // Note: src is from UefiBackend::new allocation, packed at `width`
+        // pixels per line. dst is MMIO, `stride` pixels per line, which
+        // GOP may pad past `width`.
         unsafe {
-            if GUEST_FB.is_null() { return; }
-            
-            // Optimization: Only blit if we have a guest buffer
-            // Copy line by line handling stride
-            let src = GUEST_FB;
             let dst = v.base;
-            
-            // Simple byte copy for now?
-            // If stride == width, we can do one big copy
-            // Usually stride matches width in pixels for 32bpp
-            
-            // To prevent tearing or slowness, maybe copy in chunks?
-            // For verification, just copy everything.
-            // 640x480 * 4 = 1.2MB. memcpy is fast.
-            
-            // Note: src is from UefiBackend::new allocation.
-            // dst is MMIO.
-            
-            ptr::copy_nonoverlapping(src, dst, v.width * v.height);
+            if v.stride == v.width {
+                copy_pixels(src, dst, v.width * v.height);
+            } else {
+                for y in 0..v.height {
+                    copy_pixels(src.add(y * v.width), dst.add(y * v.stride), v.width);
+                }
+            }
         }
     }
 }
+
+/// Copy `count` 32-bit pixels, two at a time with `rep movsq`
+/// Safety: both ranges must be valid and must not overlap.
+unsafe fn copy_pixels(src: *const u32, dst: *mut u32, count: usize) {
+    core::arch::asm!(
+        "rep movsq",
+        inout("rcx") count / 2 => _,
+        inout("rsi") src => _,
+        inout("rdi") dst => _,
+        options(nostack, preserves_flags)
+    );
+    if count & 1 != 0 {
+        ptr::write(dst.add(count - 1), ptr::read(src.add(count - 1)));
+    }
+}